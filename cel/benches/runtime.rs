@@ -1,9 +1,11 @@
+use cel::common::types::INT_TYPE;
 use cel::context::{Context, VariableResolver};
+use cel::structs::{StructRegistry, StructType};
 use cel::{Program, Value};
 use criterion::{black_box, criterion_group, BenchmarkId, Criterion};
 use std::collections::HashMap;
 
-const EXPRESSIONS: [(&str, &str); 36] = [
+const EXPRESSIONS: [(&str, &str); 37] = [
     ("ternary_1", "(1 || 2) ? 1 : 2"),
     ("ternary_2", "(1 ? 2 : 3) ? 1 : 2"),
     ("or_1", "1 || 2"),
@@ -36,7 +38,8 @@ const EXPRESSIONS: [(&str, &str); 36] = [
     ("max negative", "max(-1, 0, 1)"),
     ("max float", "max(-1.0, 0.0, 1.0)"),
     ("duration", "duration('1s')"),
-    ("timestamp", "timestamp('2023-05-28T00:00:00Z')"), // ("complex", "Account{user_id: 123}.user_id == 123"),
+    ("timestamp", "timestamp('2023-05-28T00:00:00Z')"),
+    ("complex", "Account{user_id: 123}.user_id == 123"),
     ("variable resolver", "banana"),
     ("variable hashmap", "apple"),
     ("stress", "true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true && true"),
@@ -68,6 +71,9 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             ctx.add_variable_from_value("foo", HashMap::from([("bar", 1)]));
             ctx.add_variable_from_value("apple", true);
             ctx.set_variable_resolver(&Resolver);
+            let mut types = StructRegistry::default();
+            types.add(StructType::new("Account").field("user_id", INT_TYPE));
+            ctx.set_type_provider(types);
             b.iter(|| program.execute(&ctx))
         });
     }
@@ -0,0 +1,81 @@
+use crate::common::types::Type;
+
+/// A single signature of a function.
+///
+/// Member overloads are invoked with the receiver as their first argument, i.e.
+/// `'foo'.startsWith('f')` matches a member overload declared with the arguments
+/// `(string, string)`.
+///
+/// Argument and result types may reference type parameters (see [`Type::new_type_param`]),
+/// which are bound independently on every call: `_==_` is declared as `(A, A) -> bool`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OverloadDecl {
+    pub id: String,
+    pub args: Vec<Type<'static>>,
+    pub result: Type<'static>,
+    pub member: bool,
+}
+
+impl OverloadDecl {
+    /// Declares an overload called as a global function, e.g. `size(list)`.
+    pub fn global<S: Into<String>>(id: S, args: &[Type<'static>], result: Type<'static>) -> Self {
+        OverloadDecl {
+            id: id.into(),
+            args: args.to_vec(),
+            result,
+            member: false,
+        }
+    }
+
+    /// Declares an overload called on a receiver, e.g. `list.size()`. The receiver
+    /// type is the first entry of `args`.
+    pub fn member<S: Into<String>>(id: S, args: &[Type<'static>], result: Type<'static>) -> Self {
+        OverloadDecl {
+            id: id.into(),
+            args: args.to_vec(),
+            result,
+            member: true,
+        }
+    }
+}
+
+/// The declaration of a function and all of its overloads.
+///
+/// # Example
+/// ```
+/// use cel::checker::{FunctionDecl, OverloadDecl};
+/// use cel::common::types::{INT_TYPE, STRING_TYPE};
+///
+/// let decl = FunctionDecl::new("shout")
+///     .overload(OverloadDecl::global("shout_string", &[STRING_TYPE], STRING_TYPE))
+///     .overload(OverloadDecl::member("string_shout_int", &[STRING_TYPE, INT_TYPE], STRING_TYPE));
+/// assert_eq!(decl.overloads.len(), 2);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionDecl {
+    pub name: String,
+    pub overloads: Vec<OverloadDecl>,
+}
+
+impl FunctionDecl {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        FunctionDecl {
+            name: name.into(),
+            overloads: Vec::new(),
+        }
+    }
+
+    pub fn overload(mut self, overload: OverloadDecl) -> Self {
+        self.overloads.push(overload);
+        self
+    }
+
+    /// Adds the overloads of `other`, skipping the ones whose id is already declared.
+    pub(crate) fn merge(&mut self, other: FunctionDecl) {
+        for overload in other.overloads {
+            if !self.overloads.iter().any(|o| o.id == overload.id) {
+                self.overloads.push(overload);
+            }
+        }
+    }
+}
@@ -0,0 +1,1197 @@
+use crate::common::ast::{
+    operators, CallExpr, ComprehensionExpr, EntryExpr, Expr, IdedExpr, SelectExpr,
+};
+use crate::common::types::{
+    Kind, Type, BOOL_TYPE, BYTES_TYPE, DOUBLE_TYPE, DURATION_TYPE, DYN_TYPE, ERROR_TYPE, INT_TYPE,
+    LIST_TYPE, MAP_TYPE, MAX_INTERNED, NULL_TYPE, STRING_TYPE, TIMESTAMP_TYPE, UINT_TYPE,
+};
+use crate::common::value::CelVal;
+use crate::structs::TypeProvider;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::sync::Arc;
+
+mod decls;
+mod standard;
+
+pub use decls::{FunctionDecl, OverloadDecl};
+
+/// Statically checks parsed expressions against a set of variable and function declarations.
+///
+/// A checker created with [`Checker::new`] knows about all the operators and functions that
+/// [`crate::Context::default`] provides an implementation for. Anything else, including every
+/// variable, needs to be declared before it can be referenced.
+///
+/// # Example
+/// ```
+/// use cel::checker::Checker;
+/// use cel::common::types::{BOOL_TYPE, INT_TYPE};
+/// use cel::parser::Parser;
+///
+/// let mut checker = Checker::new();
+/// checker.add_variable("x", INT_TYPE);
+///
+/// let expr = Parser::new().parse("x > 10").unwrap();
+/// let checked = checker.check(&expr).unwrap();
+/// assert_eq!(checked.result_type(), &BOOL_TYPE);
+///
+/// let expr = Parser::new().parse("x + 'a'").unwrap();
+/// assert!(checker.check(&expr).is_err());
+/// ```
+#[derive(Clone)]
+pub struct Checker {
+    variables: HashMap<String, Type<'static>>,
+    functions: HashMap<String, FunctionDecl>,
+    types: Option<Arc<dyn TypeProvider>>,
+}
+
+impl Default for Checker {
+    fn default() -> Self {
+        let mut checker = Checker::empty();
+        for decl in standard::declarations() {
+            checker.add_function(decl);
+        }
+        checker
+    }
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Checker::default()
+    }
+
+    /// Creates a checker without any declarations, not even for the standard operators.
+    pub fn empty() -> Self {
+        Checker {
+            variables: HashMap::new(),
+            functions: HashMap::new(),
+            types: None,
+        }
+    }
+
+    pub fn add_variable<S: Into<String>>(&mut self, name: S, ty: Type<'static>) {
+        self.variables.insert(name.into(), ty);
+    }
+
+    /// Declares a function. If a function of the same name is already declared, the overloads
+    /// are added to the existing declaration.
+    pub fn add_function(&mut self, decl: FunctionDecl) {
+        match self.functions.get_mut(&decl.name) {
+            Some(existing) => existing.merge(decl),
+            None => {
+                self.functions.insert(decl.name.clone(), decl);
+            }
+        }
+    }
+
+    /// Sets the provider of the struct types that expressions can construct and select
+    /// fields from. Without a provider, struct literals are checked to be of their named type
+    /// but their fields are `dyn`.
+    pub fn set_type_provider<P: TypeProvider + 'static>(&mut self, provider: P) {
+        self.types = Some(Arc::new(provider));
+    }
+
+    /// Infers the type of every node in `expr`, reporting all the type errors found along
+    /// the way.
+    pub fn check(&self, expr: &IdedExpr) -> Result<CheckedExpr, CheckErrors> {
+        let mut state = CheckerState {
+            checker: self,
+            scopes: Vec::new(),
+            types: HashMap::new(),
+            substitutions: HashMap::new(),
+            bound: Vec::new(),
+            type_vars: 0,
+            errors: Vec::new(),
+        };
+        state.check(expr);
+        if !state.errors.is_empty() {
+            return Err(CheckErrors {
+                errors: state.errors,
+            });
+        }
+        let mut types = HashMap::with_capacity(state.types.len());
+        for (id, ty) in &state.types {
+            match state.substitute(ty, true).to_type() {
+                Some(ty) => {
+                    types.insert(*id, ty);
+                }
+                None => {
+                    return Err(CheckErrors {
+                        errors: vec![CheckError {
+                            expr_id: *id,
+                            msg: format!(
+                                "more than {MAX_INTERNED} distinct types were constructed at runtime"
+                            ),
+                        }],
+                    });
+                }
+            }
+        }
+        Ok(CheckedExpr {
+            root: expr.id,
+            types,
+        })
+    }
+}
+
+/// The result of successfully checking an expression.
+#[derive(Clone, Debug)]
+pub struct CheckedExpr {
+    root: u64,
+    types: HashMap<u64, Type<'static>>,
+}
+
+impl CheckedExpr {
+    /// The type the expression evaluates to.
+    pub fn result_type(&self) -> &Type<'static> {
+        &self.types[&self.root]
+    }
+
+    /// The type of the node with the given id, if it was part of the checked expression.
+    pub fn type_of(&self, id: u64) -> Option<&Type<'static>> {
+        self.types.get(&id)
+    }
+
+    pub fn types(&self) -> &HashMap<u64, Type<'static>> {
+        &self.types
+    }
+}
+
+#[derive(Debug)]
+pub struct CheckErrors {
+    pub errors: Vec<CheckError>,
+}
+
+impl Display for CheckErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, e) in self.errors.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{e}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for CheckErrors {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckError {
+    pub expr_id: u64,
+    pub msg: String,
+}
+
+impl Display for CheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ERROR: {}", self.msg)
+    }
+}
+
+impl Error for CheckError {}
+
+struct CheckerState<'a> {
+    checker: &'a Checker,
+    /// Variables introduced by comprehensions, innermost last.
+    scopes: Vec<(&'a str, CheckType)>,
+    types: HashMap<u64, CheckType>,
+    /// Bindings of the type variables introduced while checking.
+    substitutions: HashMap<usize, CheckType>,
+    /// The type variables in `substitutions`, in the order they were bound, so that bindings
+    /// can be undone without copying them.
+    bound: Vec<usize>,
+    type_vars: usize,
+    errors: Vec<CheckError>,
+}
+
+impl<'a> CheckerState<'a> {
+    fn check(&mut self, expr: &'a IdedExpr) -> CheckType {
+        let ty = match &expr.expr {
+            Expr::Literal(val) => literal_type(val).into(),
+            Expr::Ident(name) => match self.lookup(name) {
+                Some(ty) => ty,
+                None => {
+                    self.error(expr.id, format!("undeclared reference to '{name}'"));
+                    ERROR_TYPE.into()
+                }
+            },
+            Expr::Select(select) => self.check_select(expr, select),
+            Expr::Call(call) => self.check_call(expr.id, call),
+            Expr::List(list) => {
+                let mut elem = None;
+                for (i, element) in list.elements.iter().enumerate() {
+                    let mut ty = self.check(element);
+                    if list.optional_indices.contains(&i) {
+                        ty = self.optional_value(element.id, &ty);
+                    }
+                    elem = Some(self.join_type(elem, ty));
+                }
+                let elem = elem.unwrap_or_else(|| self.fresh());
+                CheckType::Type(LIST_TYPE, vec![elem])
+            }
+            Expr::Map(map) => {
+                let mut key: Option<CheckType> = None;
+                let mut value: Option<CheckType> = None;
+                for entry in &map.entries {
+                    if let EntryExpr::MapEntry(entry) = &entry.expr {
+                        let k = self.check(&entry.key);
+                        let mut v = self.check(&entry.value);
+                        if entry.optional {
+                            v = self.optional_value(entry.value.id, &v);
+                        }
+                        key = Some(self.join_type(key, k));
+                        value = Some(self.join_type(value, v));
+                    }
+                }
+                let key = key.unwrap_or_else(|| self.fresh());
+                let value = value.unwrap_or_else(|| self.fresh());
+                CheckType::Type(MAP_TYPE, vec![key, value])
+            }
+            Expr::Struct(s) => {
+                let struct_type = match &self.checker.types {
+                    Some(types) => {
+                        let struct_type = types.find_struct_type(&s.type_name);
+                        if struct_type.is_none() {
+                            self.error(
+                                expr.id,
+                                format!("undeclared reference to '{}'", s.type_name),
+                            );
+                        }
+                        struct_type
+                    }
+                    None => None,
+                };
+                for entry in &s.entries {
+                    if let EntryExpr::StructField(field) = &entry.expr {
+                        let mut actual = self.check(&field.value);
+                        if field.optional {
+                            actual = self.optional_value(field.value.id, &actual);
+                        }
+                        let Some(struct_type) = &struct_type else {
+                            continue;
+                        };
+                        match struct_type.field_decl(&field.field) {
+                            Some(decl) => {
+                                if !self.is_assignable(&decl.ty.clone().into(), &actual) {
+                                    let actual = self.substitute(&actual, true);
+                                    self.error(
+                                        entry.id,
+                                        format!(
+                                            "expected type of field '{}' is '{}' but provided type is '{actual}'",
+                                            field.field, decl.ty
+                                        ),
+                                    );
+                                }
+                            }
+                            None => {
+                                self.error(entry.id, format!("undefined field '{}'", field.field))
+                            }
+                        }
+                    }
+                }
+                CheckType::Object(s.type_name.clone())
+            }
+            Expr::Comprehension(comprehension) => self.check_comprehension(comprehension),
+            Expr::Unspecified => {
+                self.error(expr.id, "unspecified expression");
+                ERROR_TYPE.into()
+            }
+        };
+        self.types.insert(expr.id, ty.clone());
+        ty
+    }
+
+    fn check_select(&mut self, expr: &'a IdedExpr, select: &'a SelectExpr) -> CheckType {
+        // `a.b.c` may refer to a variable declared with a qualified name, unless `a` is
+        // shadowed by a comprehension variable.
+        if !select.test {
+            if let Some(name) = expr.to_qualified_name() {
+                if !self.is_local(&name) {
+                    if let Some(ty) = self.checker.variables.get(&name) {
+                        return ty.clone().into();
+                    }
+                }
+            }
+        }
+
+        let operand = self.check(&select.operand);
+        let field = self.field_type(expr.id, &operand, &select.field);
+        if select.test {
+            BOOL_TYPE.into()
+        } else {
+            field
+        }
+    }
+
+    /// The type of `operand.field`. Selecting a field of an optional value results in an
+    /// optional value, e.g. `optional.of(m).a`.
+    fn field_type(&mut self, id: u64, operand: &CheckType, field: &str) -> CheckType {
+        let operand = self.substitute(operand, false);
+        match operand.kind() {
+            Kind::Map => operand.parameters()[1].clone(),
+            Kind::Struct => match self
+                .checker
+                .types
+                .as_ref()
+                .and_then(|types| types.find_struct_type(operand.name()))
+            {
+                Some(struct_type) => match struct_type.field_decl(field) {
+                    Some(decl) => decl.ty.clone().into(),
+                    None => {
+                        self.error(id, format!("undefined field '{field}'"));
+                        ERROR_TYPE.into()
+                    }
+                },
+                None => DYN_TYPE.into(),
+            },
+            Kind::Opaque if operand.is_optional() => {
+                let value = self.field_type(id, &operand.parameters()[0], field);
+                CheckType::Type(OPTIONAL_TYPE, vec![value])
+            }
+            Kind::Opaque | Kind::Any | Kind::Dyn | Kind::Error | Kind::TypeParam => DYN_TYPE.into(),
+            _ => {
+                self.error(
+                    id,
+                    format!("type '{operand}' does not support field selection"),
+                );
+                ERROR_TYPE.into()
+            }
+        }
+    }
+
+    fn check_call(&mut self, id: u64, call: &'a CallExpr) -> CheckType {
+        if call.func_name == operators::OPT_SELECT && call.args.len() == 2 {
+            if let Expr::Literal(CelVal::String(field)) = &call.args[1].expr {
+                let operand = self.check(&call.args[0]);
+                self.check(&call.args[1]);
+                let operand = self.substitute(&operand, false);
+                let operand = if operand.is_optional() {
+                    operand.parameters()[0].clone()
+                } else {
+                    operand
+                };
+                let field = self.field_type(id, &operand, field);
+                return CheckType::Type(OPTIONAL_TYPE, vec![field]);
+            }
+        }
+
+        // A namespaced function, e.g. `optional.of(x)`, is called without a receiver.
+        let namespaced = call.target.as_ref().and_then(|target| {
+            let name = format!("{}.{}", target.to_qualified_name()?, call.func_name);
+            if self.is_local(&name) {
+                return None;
+            }
+            self.checker
+                .functions
+                .get_key_value(&name)
+                .map(|(name, _)| name)
+        });
+
+        let mut args = Vec::with_capacity(call.args.len() + 1);
+        if let Some(target) = call.target.as_ref().filter(|_| namespaced.is_none()) {
+            args.push(self.check(target));
+        }
+        for arg in &call.args {
+            args.push(self.check(arg));
+        }
+
+        let name = namespaced.unwrap_or(&call.func_name);
+        let Some(decl) = self.checker.functions.get(name) else {
+            self.error(id, format!("undeclared reference to '{name}'"));
+            return ERROR_TYPE.into();
+        };
+
+        let member = call.target.is_some() && namespaced.is_none();
+        let snapshot = self.bound.len();
+        let mut matched: Option<(CheckType, Vec<(usize, CheckType)>)> = None;
+        let mut result: Option<CheckType> = None;
+        for overload in decl
+            .overloads
+            .iter()
+            .filter(|o| o.member == member && o.args.len() == args.len())
+        {
+            let mut params = HashMap::new();
+            let params_ok = overload.args.iter().zip(&args).all(|(param, arg)| {
+                let param = self.instantiate(param, &mut params);
+                self.is_assignable(&param, arg)
+            });
+            if params_ok {
+                let ty = self.instantiate(&overload.result, &mut params);
+                let ty = self.substitute(&ty, false);
+                result = Some(match result {
+                    None => {
+                        let bindings = self.bound[snapshot..]
+                            .iter()
+                            .map(|var| (*var, self.substitutions[var].clone()))
+                            .collect();
+                        matched = Some((ty.clone(), bindings));
+                        ty
+                    }
+                    Some(previous) if previous == ty => previous,
+                    Some(_) => DYN_TYPE.into(),
+                });
+            }
+            self.rollback(snapshot);
+        }
+
+        match (result, matched) {
+            (Some(result), Some((ty, bindings))) if result == ty => {
+                // A single (or unambiguous) match keeps the bindings it inferred, so that
+                // e.g. `[].map(x, x + 1)` knows the type of `x`.
+                for (var, ty) in bindings {
+                    self.bind(var, ty);
+                }
+                ty
+            }
+            (Some(result), _) => result,
+            (None, _) => {
+                let args: Vec<String> = args
+                    .iter()
+                    .map(|a| self.substitute(a, true).to_string())
+                    .collect();
+                let signature = if member {
+                    format!("{}.({})", args[0], args[1..].join(", "))
+                } else {
+                    format!("({})", args.join(", "))
+                };
+                self.error(
+                    id,
+                    format!("found no matching overload for '{name}' applied to '{signature}'"),
+                );
+                ERROR_TYPE.into()
+            }
+        }
+    }
+
+    fn check_comprehension(&mut self, comprehension: &'a ComprehensionExpr) -> CheckType {
+        let range = self.check(&comprehension.iter_range);
+        let range = self.substitute(&range, false);
+        // With two variables, lists are iterated as (index, element) and maps as (key, value).
+        let (iter_type, iter_type2) = match range.kind() {
+            Kind::List if comprehension.iter_var2.is_some() => {
+                (INT_TYPE.into(), range.parameters()[0].clone())
+            }
+            Kind::List => (range.parameters()[0].clone(), DYN_TYPE.into()),
+            Kind::Map => (range.parameters()[0].clone(), range.parameters()[1].clone()),
+            Kind::Dyn | Kind::Error | Kind::TypeParam => (DYN_TYPE.into(), DYN_TYPE.into()),
+            _ => {
+                self.error(
+                    comprehension.iter_range.id,
+                    format!("expression of type '{range}' cannot be the range of a comprehension (must be list, map, or dynamic)"),
+                );
+                (DYN_TYPE.into(), DYN_TYPE.into())
+            }
+        };
+
+        let accu = self.check(&comprehension.accu_init);
+        self.scopes.push((&comprehension.accu_var, accu.clone()));
+        self.scopes.push((&comprehension.iter_var, iter_type));
+        if let Some(iter_var2) = &comprehension.iter_var2 {
+            self.scopes.push((iter_var2, iter_type2));
+        }
+
+        let cond = self.check(&comprehension.loop_cond);
+        if !self.is_assignable(&BOOL_TYPE.into(), &cond) {
+            let cond = self.substitute(&cond, true);
+            self.error(
+                comprehension.loop_cond.id,
+                format!("expected type 'bool' but found '{cond}'"),
+            );
+        }
+        let step = self.check(&comprehension.loop_step);
+        if !self.is_assignable(&accu, &step) {
+            let accu = self.substitute(&accu, true);
+            let step = self.substitute(&step, true);
+            self.error(
+                comprehension.loop_step.id,
+                format!("expected type '{accu}' but found '{step}'"),
+            );
+        }
+
+        // The iteration variables are out of scope for the result.
+        self.scopes.pop();
+        if comprehension.iter_var2.is_some() {
+            self.scopes.pop();
+        }
+        let result = self.check(&comprehension.result);
+        self.scopes.pop();
+        result
+    }
+
+    /// Returns whether the root identifier of a qualified name, e.g. `a` in `a.b.c`, is
+    /// a comprehension variable.
+    fn is_local(&self, qualified_name: &str) -> bool {
+        let root = qualified_name.split('.').next().unwrap_or_default();
+        self.scopes.iter().any(|(n, _)| *n == root)
+    }
+
+    fn lookup(&self, name: &str) -> Option<CheckType> {
+        self.scopes
+            .iter()
+            .rev()
+            .find(|(n, _)| *n == name)
+            .map(|(_, ty)| ty.clone())
+            .or_else(|| self.checker.variables.get(name).cloned().map(Into::into))
+    }
+
+    /// The type of the value of an optional list element, map entry or struct field,
+    /// e.g. `int` for `[?optional.of(1)]`.
+    fn optional_value(&mut self, id: u64, ty: &CheckType) -> CheckType {
+        let value = self.fresh();
+        if self.is_assignable(&CheckType::Type(OPTIONAL_TYPE, vec![value.clone()]), ty) {
+            return value;
+        }
+        let ty = self.substitute(ty, true);
+        self.error(
+            id,
+            format!("expected type 'optional_type' but found '{ty}'"),
+        );
+        ERROR_TYPE.into()
+    }
+
+    /// The common type of the elements of a list or map literal, or `dyn` if they disagree.
+    fn join_type(&mut self, previous: Option<CheckType>, ty: CheckType) -> CheckType {
+        match previous {
+            None => ty,
+            Some(previous) if self.is_assignable(&previous, &ty) => previous,
+            Some(_) => DYN_TYPE.into(),
+        }
+    }
+
+    fn fresh(&mut self) -> CheckType {
+        let var = CheckType::Var(self.type_vars);
+        self.type_vars += 1;
+        var
+    }
+
+    /// Replaces the type parameters of a declaration with fresh type variables, so that
+    /// every call binds them independently.
+    fn instantiate(
+        &mut self,
+        ty: &Type<'static>,
+        params: &mut HashMap<&'static str, CheckType>,
+    ) -> CheckType {
+        if ty.kind() == Kind::TypeParam {
+            if let Some(var) = params.get(ty.name()) {
+                return var.clone();
+            }
+            let var = self.fresh();
+            params.insert(ty.name(), var.clone());
+            return var;
+        }
+        let parameters = ty
+            .parameters()
+            .iter()
+            .map(|p| self.instantiate(p, params))
+            .collect();
+        CheckType::Type(ty.clone(), parameters)
+    }
+
+    /// Applies the current bindings to `ty`. When `finalize` is set, type variables that are
+    /// still unbound become `dyn`.
+    fn substitute(&self, ty: &CheckType, finalize: bool) -> CheckType {
+        match ty {
+            CheckType::Var(var) => match self.substitutions.get(var) {
+                Some(bound) => self.substitute(bound, finalize),
+                None if finalize => DYN_TYPE.into(),
+                None => ty.clone(),
+            },
+            CheckType::Object(_) => ty.clone(),
+            CheckType::Type(base, parameters) => CheckType::Type(
+                base.clone(),
+                parameters
+                    .iter()
+                    .map(|p| self.substitute(p, finalize))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Returns whether a value of type `actual` can be used where `expected` is required,
+    /// binding type variables as needed. Bindings are left untouched if it can't.
+    fn is_assignable(&mut self, expected: &CheckType, actual: &CheckType) -> bool {
+        let snapshot = self.bound.len();
+        let assignable = self.assign(expected, actual);
+        if !assignable {
+            self.rollback(snapshot);
+        }
+        assignable
+    }
+
+    fn assign(&mut self, expected: &CheckType, actual: &CheckType) -> bool {
+        let expected = self.substitute(expected, false);
+        let actual = self.substitute(actual, false);
+        if expected == actual {
+            return true;
+        }
+        match (&expected, &actual) {
+            // Binding the newer variable keeps chains of bindings short, e.g. for the elements
+            // of `[[], [], []]`.
+            (CheckType::Var(a), CheckType::Var(b)) => {
+                self.bind(*a.max(b), CheckType::Var(*a.min(b)))
+            }
+            (CheckType::Var(var), _) => self.bind(*var, actual),
+            (_, CheckType::Var(var)) => self.bind(*var, expected),
+            _ if expected.is_dyn_or_error() || actual.is_dyn_or_error() => true,
+            _ => match (expected.kind(), actual.kind()) {
+                // Type parameters of declared variables aren't bound by a check.
+                (Kind::TypeParam, _) | (_, Kind::TypeParam) => true,
+                (Kind::Struct | Kind::Opaque, Kind::NullType) => true,
+                (expected_kind, actual_kind) => {
+                    expected_kind == actual_kind
+                        && expected.name() == actual.name()
+                        && expected.parameters().len() == actual.parameters().len()
+                        && expected
+                            .parameters()
+                            .iter()
+                            .zip(actual.parameters())
+                            .all(|(e, a)| self.assign(e, a))
+                }
+            },
+        }
+    }
+
+    fn bind(&mut self, var: usize, ty: CheckType) -> bool {
+        if ty.occurs(var) {
+            return false;
+        }
+        self.substitutions.insert(var, ty);
+        self.bound.push(var);
+        true
+    }
+
+    /// Undoes the bindings made since `bound` had the given length.
+    fn rollback(&mut self, len: usize) {
+        for var in self.bound.drain(len..) {
+            self.substitutions.remove(&var);
+        }
+    }
+
+    fn error<S: Into<String>>(&mut self, expr_id: u64, msg: S) {
+        self.errors.push(CheckError {
+            expr_id,
+            msg: msg.into(),
+        });
+    }
+}
+
+/// The base of the optional types inferred while checking, e.g. `optional_type(_var0)`.
+const OPTIONAL_TYPE: Type<'static> = Type::new_opaque_type("optional_type");
+
+/// A type inferred while checking. Unlike [`Type`], it owns its parameters, so that the type
+/// variables of a check and the types built from them are dropped along with it, rather than
+/// interned for the lifetime of the process. The types of a successful check are only
+/// interned once their variables are resolved.
+#[derive(Clone, Debug)]
+enum CheckType {
+    /// A type variable, bound in [`CheckerState::substitutions`].
+    Var(usize),
+    /// The struct type named by a struct literal, e.g. `Account` in `Account{id: 1}`.
+    Object(String),
+    /// A type whose own parameters are replaced by the given ones, e.g. `list` with the
+    /// parameter `_var0` for `[]`.
+    Type(Type<'static>, Vec<CheckType>),
+}
+
+impl CheckType {
+    fn kind(&self) -> Kind {
+        match self {
+            CheckType::Var(_) => Kind::TypeParam,
+            CheckType::Object(_) => Kind::Struct,
+            CheckType::Type(ty, _) => ty.kind(),
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            CheckType::Var(_) => "",
+            CheckType::Object(name) => name,
+            CheckType::Type(ty, _) => ty.name(),
+        }
+    }
+
+    fn parameters(&self) -> &[CheckType] {
+        match self {
+            CheckType::Type(_, parameters) => parameters,
+            _ => &[],
+        }
+    }
+
+    fn is_dyn_or_error(&self) -> bool {
+        matches!(self.kind(), Kind::Dyn | Kind::Error)
+    }
+
+    fn is_optional(&self) -> bool {
+        self.kind() == Kind::Opaque
+            && self.name() == "optional_type"
+            && self.parameters().len() == 1
+    }
+
+    fn occurs(&self, var: usize) -> bool {
+        match self {
+            CheckType::Var(v) => *v == var,
+            _ => self.parameters().iter().any(|p| p.occurs(var)),
+        }
+    }
+
+    /// Interns this type, once substituted with `finalize`. Returns `None` if the interner is
+    /// full.
+    fn to_type(&self) -> Option<Type<'static>> {
+        match self {
+            CheckType::Var(_) => Some(DYN_TYPE),
+            CheckType::Object(name) => Type::try_new_object(name),
+            CheckType::Type(ty, _) if ty.kind() == Kind::TypeParam => Some(DYN_TYPE),
+            CheckType::Type(ty, parameters) if parameters.is_empty() => Some(ty.clone()),
+            CheckType::Type(ty, parameters) => {
+                let parameters = parameters
+                    .iter()
+                    .map(CheckType::to_type)
+                    .collect::<Option<Vec<_>>>()?;
+                ty.try_with_parameters(&parameters.iter().collect::<Vec<_>>())
+            }
+        }
+    }
+}
+
+impl From<Type<'static>> for CheckType {
+    fn from(ty: Type<'static>) -> Self {
+        let parameters = ty
+            .parameters()
+            .iter()
+            .map(|p| (*p).clone().into())
+            .collect();
+        CheckType::Type(ty, parameters)
+    }
+}
+
+impl PartialEq for CheckType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (CheckType::Var(a), CheckType::Var(b)) => a == b,
+            (CheckType::Var(_), _) | (_, CheckType::Var(_)) => false,
+            _ => {
+                self.kind() == other.kind()
+                    && self.name() == other.name()
+                    && self.parameters() == other.parameters()
+            }
+        }
+    }
+}
+
+impl Display for CheckType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckType::Var(var) => return write!(f, "_var{var}"),
+            _ => f.write_str(self.name())?,
+        }
+        if !self.parameters().is_empty() {
+            f.write_str("(")?;
+            for (i, param) in self.parameters().iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{param}")?;
+            }
+            f.write_str(")")?;
+        }
+        Ok(())
+    }
+}
+
+fn literal_type(val: &CelVal) -> Type<'static> {
+    match val {
+        CelVal::Boolean(_) => BOOL_TYPE,
+        CelVal::Bytes(_) => BYTES_TYPE,
+        CelVal::Double(_) => DOUBLE_TYPE,
+        CelVal::Duration(_) => DURATION_TYPE,
+        CelVal::Int(_) => INT_TYPE,
+        CelVal::Null => NULL_TYPE,
+        CelVal::String(_) => STRING_TYPE,
+        CelVal::Timestamp(_) => TIMESTAMP_TYPE,
+        CelVal::UInt(_) => UINT_TYPE,
+        _ => DYN_TYPE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::structs::{StructRegistry, StructType};
+
+    fn check(checker: &Checker, expr: &str) -> Result<String, String> {
+        let ast = Parser::new().parse(expr).unwrap();
+        checker
+            .check(&ast)
+            .map(|checked| checked.result_type().to_string())
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn literals_and_operators() {
+        let checker = Checker::new();
+        for (expr, expected) in [
+            ("1 + 2", "int"),
+            ("1u * 2u", "uint"),
+            ("1.5 / 2.0", "double"),
+            ("'a' + 'b'", "string"),
+            ("b'a' + b'b'", "bytes"),
+            ("1 < 2.0", "bool"),
+            ("!true || false && true", "bool"),
+            ("true ? 1 : 2", "int"),
+            ("null", "null_type"),
+            ("'abc'.size()", "int"),
+            ("size([1, 2])", "int"),
+            ("string(1)", "string"),
+            ("'abc'.startsWith('a')", "bool"),
+        ] {
+            assert_eq!(check(&checker, expr), Ok(expected.to_string()), "{expr}");
+        }
+    }
+
+    #[test]
+    fn default_functions() {
+        #[cfg_attr(
+            not(any(feature = "encoders", feature = "regex", feature = "chrono")),
+            allow(unused_mut)
+        )]
+        let mut tests = vec![
+            ("contains", "'abc'.contains('b')", "bool"),
+            ("size", "size('abc')", "int"),
+            ("max", "max(1, 2)", "int"),
+            ("min", "min([1.0, 2.0])", "double"),
+            ("startsWith", "'abc'.startsWith('a')", "bool"),
+            ("endsWith", "'abc'.endsWith('c')", "bool"),
+            ("string", "string(1)", "string"),
+            ("bytes", "bytes('a')", "bytes"),
+            ("double", "double(1)", "double"),
+            ("int", "int('1')", "int"),
+            ("uint", "uint(1)", "uint"),
+            ("optional.of", "optional.of(1)", "optional_type(int)"),
+            ("optional.none", "optional.none()", "optional_type(dyn)"),
+            (
+                "optional.ofNonZeroValue",
+                "optional.ofNonZeroValue('a')",
+                "optional_type(string)",
+            ),
+            ("hasValue", "optional.of(1).hasValue()", "bool"),
+            ("value", "optional.of(1).value()", "int"),
+            ("orValue", "optional.of(1).orValue(2)", "int"),
+            (
+                "or",
+                "optional.of(1).or(optional.none())",
+                "optional_type(int)",
+            ),
+        ];
+        #[cfg(feature = "regex")]
+        tests.push(("matches", "'abc'.matches('a.c')", "bool"));
+        #[cfg(feature = "encoders")]
+        tests.extend([
+            ("base64.encode", "base64.encode(b'a')", "string"),
+            ("base64.decode", "base64.decode('YQ==')", "bytes"),
+            ("base64.urlEncode", "base64.urlEncode(b'a')", "string"),
+            ("base64.urlDecode", "base64.urlDecode('YQ')", "bytes"),
+        ]);
+        #[cfg(feature = "chrono")]
+        let accessors: Vec<_> = [
+            "getFullYear",
+            "getMonth",
+            "getDayOfYear",
+            "getDayOfMonth",
+            "getDate",
+            "getDayOfWeek",
+            "getHours",
+            "getMinutes",
+            "getSeconds",
+            "getMilliseconds",
+        ]
+        .into_iter()
+        .map(|name| (name, format!("timestamp('2023-05-28T02:00:00Z').{name}()")))
+        .collect();
+        #[cfg(feature = "chrono")]
+        {
+            tests.push(("duration", "duration('1s')", "google.protobuf.Duration"));
+            tests.push((
+                "timestamp",
+                "timestamp('2023-05-28T02:00:00Z')",
+                "google.protobuf.Timestamp",
+            ));
+            for (name, expr) in &accessors {
+                tests.push((name, expr, "int"));
+            }
+        }
+
+        let crate::Context::Root { functions, .. } = crate::Context::default() else {
+            unreachable!()
+        };
+        let mut registered: Vec<_> = functions.names().collect();
+        registered.sort();
+        let mut tested: Vec<_> = tests.iter().map(|(name, _, _)| *name).collect();
+        tested.sort();
+        assert_eq!(tested, registered);
+
+        let checker = Checker::new();
+        for (_, expr, expected) in tests {
+            assert_eq!(check(&checker, expr), Ok(expected.to_string()), "{expr}");
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn timestamp_accessors() {
+        let checker = Checker::new();
+        let ts = "timestamp('2023-05-28T02:00:00Z')";
+        assert_eq!(
+            check(&checker, &format!("{ts}.getHours()")),
+            Ok("int".to_string())
+        );
+        assert_eq!(
+            check(&checker, &format!("{ts}.getHours('+05:30')")),
+            Ok("int".to_string())
+        );
+        assert_eq!(
+            check(&checker, &format!("{ts}.getHours(1)")),
+            Err(
+                "ERROR: found no matching overload for 'getHours' applied to 'google.protobuf.Timestamp.(int)'"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn aggregate_literals() {
+        let checker = Checker::new();
+        for (expr, expected) in [
+            ("[1, 2]", "list(int)"),
+            ("[1, 'a']", "list(dyn)"),
+            ("[]", "list(dyn)"),
+            ("[[], [1]]", "list(list(int))"),
+            ("{'a': 1}", "map(string, int)"),
+            ("{'a': 1}['a']", "int"),
+            ("{'a': [1]}.a", "list(int)"),
+            ("[1, 2][0]", "int"),
+            ("[1] + []", "list(int)"),
+            ("1 in [1, 2]", "bool"),
+            ("[1] == []", "bool"),
+        ] {
+            assert_eq!(check(&checker, expr), Ok(expected.to_string()), "{expr}");
+        }
+    }
+
+    #[test]
+    fn variables() {
+        let mut checker = Checker::new();
+        checker.add_variable("x", INT_TYPE);
+        checker.add_variable("d", DYN_TYPE);
+        checker.add_variable("m", Type::new_map(&STRING_TYPE, &Type::new_list(&INT_TYPE)));
+        checker.add_variable("a.b.c", STRING_TYPE);
+
+        assert_eq!(check(&checker, "x + 1"), Ok("int".to_string()));
+        assert_eq!(check(&checker, "d.foo + 1"), Ok("int".to_string()));
+        assert_eq!(check(&checker, "d.foo"), Ok("dyn".to_string()));
+        assert_eq!(check(&checker, "m.foo[0]"), Ok("int".to_string()));
+        assert_eq!(check(&checker, "has(m.foo)"), Ok("bool".to_string()));
+        assert_eq!(check(&checker, "a.b.c"), Ok("string".to_string()));
+        assert_eq!(
+            check(&checker, "y"),
+            Err("ERROR: undeclared reference to 'y'".to_string())
+        );
+    }
+
+    #[test]
+    fn comprehensions() {
+        let mut checker = Checker::new();
+        checker.add_variable("x", STRING_TYPE);
+        for (expr, expected) in [
+            ("[1, 2].map(x, x * 2)", "list(int)"),
+            ("[1, 2].map(x, x > 1, 'a')", "list(string)"),
+            ("[1, 2].filter(x, x > 1)", "list(int)"),
+            ("[1, 2].all(x, x > 1)", "bool"),
+            ("{'a': 1}.exists(k, k == 'a')", "bool"),
+            ("[1].exists_one(x, x == 1)", "bool"),
+            // The iteration variable shadows the declared one.
+            ("[1].map(x, x + 1)", "list(int)"),
+            ("[].map(x, x)", "list(dyn)"),
+            ("['a'].all(i, v, i > 0 && v == 'a')", "bool"),
+            ("{'a': 1}.exists(k, v, k == 'a' && v > 0)", "bool"),
+            ("['a'].transformList(i, v, v + string(i))", "list(string)"),
+            (
+                "{'a': 1}.transformMap(k, v, v > 0, double(v))",
+                "map(string, double)",
+            ),
+            ("['a'].transformMapEntry(i, v, {v: i})", "map(string, int)"),
+            ("cel.bind(y, x + 'a', [y, y])", "list(string)"),
+            ("cel.block([1, cel.index(0) > 0], cel.index(1))", "bool"),
+        ] {
+            assert_eq!(check(&checker, expr), Ok(expected.to_string()), "{expr}");
+        }
+
+        assert_eq!(
+            check(&checker, "1.all(x, x > 0)"),
+            Err("ERROR: expression of type 'int' cannot be the range of a comprehension (must be list, map, or dynamic)".to_string())
+        );
+        assert_eq!(
+            check(&checker, "[1].all(x, x)"),
+            Err(
+                "ERROR: found no matching overload for '_&&_' applied to '(bool, int)'".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn no_matching_overload() {
+        let checker = Checker::new();
+        assert_eq!(
+            check(&checker, "1 + 2u"),
+            Err("ERROR: found no matching overload for '_+_' applied to '(int, uint)'".to_string())
+        );
+        assert_eq!(
+            check(&checker, "'a'.startsWith(1)"),
+            Err(
+                "ERROR: found no matching overload for 'startsWith' applied to 'string.(int)'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            check(&checker, "true ? 1 : 'a'"),
+            Err(
+                "ERROR: found no matching overload for '_?_:_' applied to '(bool, int, string)'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            check(&checker, "foo(1)"),
+            Err("ERROR: undeclared reference to 'foo'".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_all_errors() {
+        let checker = Checker::new();
+        let ast = Parser::new().parse("a + b").unwrap();
+        let errors = checker.check(&ast).unwrap_err();
+        assert_eq!(errors.errors.len(), 2);
+        assert_eq!(
+            errors.to_string(),
+            "ERROR: undeclared reference to 'a'\nERROR: undeclared reference to 'b'"
+        );
+    }
+
+    #[test]
+    fn custom_functions() {
+        let mut checker = Checker::new();
+        checker.add_function(FunctionDecl::new("first").overload(OverloadDecl::member(
+            "list_first",
+            &[Type::new_list(&Type::new_param("T"))],
+            Type::new_param("T"),
+        )));
+        checker.add_function(FunctionDecl::new("size").overload(OverloadDecl::global(
+            "size_int",
+            &[INT_TYPE],
+            INT_TYPE,
+        )));
+
+        assert_eq!(check(&checker, "['a'].first()"), Ok("string".to_string()));
+        assert_eq!(check(&checker, "[[1]].first()[0]"), Ok("int".to_string()));
+        assert_eq!(check(&checker, "size(1)"), Ok("int".to_string()));
+        assert_eq!(check(&checker, "size('a')"), Ok("int".to_string()));
+    }
+
+    #[test]
+    fn struct_types() {
+        let mut types = StructRegistry::default();
+        types.add(
+            StructType::new("Account")
+                .field("user_id", INT_TYPE)
+                .field("owner", Type::new_object("Account")),
+        );
+        let mut checker = Checker::new();
+        checker.set_type_provider(types);
+
+        for (expr, expected) in [
+            ("Account{user_id: 1}", "Account"),
+            ("Account{}.user_id", "int"),
+            ("Account{owner: null}.owner.owner", "Account"),
+            ("has(Account{}.owner)", "bool"),
+        ] {
+            assert_eq!(check(&checker, expr), Ok(expected.to_string()), "{expr}");
+        }
+        assert_eq!(
+            check(&checker, "Account{user_id: 'a'}"),
+            Err(
+                "ERROR: expected type of field 'user_id' is 'int' but provided type is 'string'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            check(&checker, "Account{}.id"),
+            Err("ERROR: undefined field 'id'".to_string())
+        );
+        assert_eq!(
+            check(&checker, "Unknown{}"),
+            Err("ERROR: undeclared reference to 'Unknown'".to_string())
+        );
+    }
+
+    #[test]
+    fn optionals() {
+        let mut checker = Checker::new();
+        checker.add_variable("m", Type::new_map(&STRING_TYPE, &INT_TYPE));
+        for (expr, expected) in [
+            ("optional.of(1)", "optional_type(int)"),
+            ("optional.none()", "optional_type(dyn)"),
+            ("optional.of('a').orValue('b')", "string"),
+            ("optional.none().orValue(1)", "int"),
+            ("m.?a", "optional_type(int)"),
+            ("m[?'a'].value()", "int"),
+            ("optional.of(m).a", "optional_type(int)"),
+            ("optional.of(m).?a", "optional_type(int)"),
+            ("[?m.?a, 1]", "list(int)"),
+            ("{?'a': m.?b}", "map(string, int)"),
+        ] {
+            assert_eq!(check(&checker, expr), Ok(expected.to_string()), "{expr}");
+        }
+        assert_eq!(
+            check(&checker, "[?1]"),
+            Err("ERROR: expected type 'optional_type' but found 'int'".to_string())
+        );
+        assert_eq!(
+            check(&checker, "optional.of(1).orValue('a')"),
+            Err("ERROR: found no matching overload for 'orValue' applied to 'optional_type(int).(string)'".to_string())
+        );
+    }
+
+    #[test]
+    fn type_of_subexpressions() {
+        let ast = Parser::new().parse("[1, 2].map(x, x * 2.0)").unwrap();
+        assert!(Checker::new().check(&ast).is_err());
+
+        let ast = Parser::new().parse("{'a': [1]}").unwrap();
+        let checked = Checker::new().check(&ast).unwrap();
+        let Expr::Map(map) = &ast.expr else {
+            unreachable!()
+        };
+        let EntryExpr::MapEntry(entry) = &map.entries[0].expr else {
+            unreachable!()
+        };
+        assert_eq!(checked.type_of(entry.key.id), Some(&STRING_TYPE));
+        assert_eq!(
+            checked.type_of(entry.value.id),
+            Some(&Type::new_list(&INT_TYPE))
+        );
+    }
+
+    #[test]
+    fn type_variables_are_not_interned() {
+        // Every `[]` introduces a type variable, which would fill the interner if it outlived
+        // the check.
+        let checker = Checker::new();
+        let expr = format!("[{}]", vec!["[]"; 40_000].join(", "));
+        assert_eq!(check(&checker, &expr), Ok("list(list(dyn))".to_string()));
+        assert_eq!(
+            check(&checker, "[{}, {'a': [1]}]"),
+            Ok("list(map(string, list(int)))".to_string())
+        );
+    }
+}
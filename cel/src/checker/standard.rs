@@ -0,0 +1,445 @@
+use crate::checker::decls::{FunctionDecl, OverloadDecl};
+use crate::common::ast::operators;
+use crate::common::types::{
+    Type, BOOL_TYPE, BYTES_TYPE, DOUBLE_TYPE, DURATION_TYPE, INT_TYPE, STRING_TYPE, TIMESTAMP_TYPE,
+    UINT_TYPE,
+};
+
+const A: Type = Type::new_type_param("A");
+const K: Type = Type::new_type_param("K");
+const V: Type = Type::new_type_param("V");
+const LIST_A: Type = Type::new_list_type(&[&A]);
+const MAP_KV: Type = Type::new_map_type(&[&K, &V]);
+
+/// Returns the declarations of the operators and functions registered by [`crate::Context::default`].
+pub(crate) fn declarations() -> Vec<FunctionDecl> {
+    let optional_a = Type::new_optional(&A);
+    let optional_v = Type::new_optional(&V);
+    let optional_list_a = Type::new_optional(&LIST_A);
+    let optional_map_kv = Type::new_optional(&MAP_KV);
+
+    // Only the declarations of optional features are added to afterwards.
+    #[cfg_attr(
+        not(any(feature = "encoders", feature = "regex", feature = "chrono")),
+        allow(unused_mut)
+    )]
+    let mut decls = vec![
+        FunctionDecl::new(operators::ADD)
+            .overload(global("add_int64", &[INT_TYPE, INT_TYPE], INT_TYPE))
+            .overload(global("add_uint64", &[UINT_TYPE, UINT_TYPE], UINT_TYPE))
+            .overload(global(
+                "add_double",
+                &[DOUBLE_TYPE, DOUBLE_TYPE],
+                DOUBLE_TYPE,
+            ))
+            .overload(global(
+                "add_string",
+                &[STRING_TYPE, STRING_TYPE],
+                STRING_TYPE,
+            ))
+            .overload(global("add_bytes", &[BYTES_TYPE, BYTES_TYPE], BYTES_TYPE))
+            .overload(global("add_list", &[LIST_A, LIST_A], LIST_A))
+            .overload(global(
+                "add_duration_duration",
+                &[DURATION_TYPE, DURATION_TYPE],
+                DURATION_TYPE,
+            ))
+            .overload(global(
+                "add_timestamp_duration",
+                &[TIMESTAMP_TYPE, DURATION_TYPE],
+                TIMESTAMP_TYPE,
+            ))
+            .overload(global(
+                "add_duration_timestamp",
+                &[DURATION_TYPE, TIMESTAMP_TYPE],
+                TIMESTAMP_TYPE,
+            )),
+        FunctionDecl::new(operators::SUBSTRACT)
+            .overload(global("subtract_int64", &[INT_TYPE, INT_TYPE], INT_TYPE))
+            .overload(global(
+                "subtract_uint64",
+                &[UINT_TYPE, UINT_TYPE],
+                UINT_TYPE,
+            ))
+            .overload(global(
+                "subtract_double",
+                &[DOUBLE_TYPE, DOUBLE_TYPE],
+                DOUBLE_TYPE,
+            ))
+            .overload(global(
+                "subtract_duration_duration",
+                &[DURATION_TYPE, DURATION_TYPE],
+                DURATION_TYPE,
+            ))
+            .overload(global(
+                "subtract_timestamp_duration",
+                &[TIMESTAMP_TYPE, DURATION_TYPE],
+                TIMESTAMP_TYPE,
+            ))
+            .overload(global(
+                "subtract_timestamp_timestamp",
+                &[TIMESTAMP_TYPE, TIMESTAMP_TYPE],
+                DURATION_TYPE,
+            )),
+        arithmetic(operators::MULTIPLY, "multiply", true),
+        arithmetic(operators::DIVIDE, "divide", true),
+        arithmetic(operators::MODULO, "modulo", false),
+        FunctionDecl::new(operators::NEGATE)
+            .overload(global("negate_int64", &[INT_TYPE], INT_TYPE))
+            .overload(global("negate_double", &[DOUBLE_TYPE], DOUBLE_TYPE)),
+        FunctionDecl::new(operators::LOGICAL_NOT).overload(global(
+            "logical_not",
+            &[BOOL_TYPE],
+            BOOL_TYPE,
+        )),
+        FunctionDecl::new(operators::NOT_STRICTLY_FALSE).overload(global(
+            "not_strictly_false",
+            &[BOOL_TYPE],
+            BOOL_TYPE,
+        )),
+        FunctionDecl::new(operators::MAP_INSERT)
+            .overload(global("map_insert_key_value", &[MAP_KV, K, V], MAP_KV))
+            .overload(global("map_insert_map", &[MAP_KV, MAP_KV], MAP_KV)),
+        FunctionDecl::new(operators::LOGICAL_AND).overload(global(
+            "logical_and",
+            &[BOOL_TYPE, BOOL_TYPE],
+            BOOL_TYPE,
+        )),
+        FunctionDecl::new(operators::LOGICAL_OR).overload(global(
+            "logical_or",
+            &[BOOL_TYPE, BOOL_TYPE],
+            BOOL_TYPE,
+        )),
+        FunctionDecl::new(operators::CONDITIONAL).overload(global(
+            "conditional",
+            &[BOOL_TYPE, A, A],
+            A,
+        )),
+        FunctionDecl::new(operators::EQUALS).overload(global("equals", &[A, A], BOOL_TYPE)),
+        FunctionDecl::new(operators::NOT_EQUALS).overload(global("not_equals", &[A, A], BOOL_TYPE)),
+        comparison(operators::LESS, "less"),
+        comparison(operators::LESS_EQUALS, "less_equals"),
+        comparison(operators::GREATER, "greater"),
+        comparison(operators::GREATER_EQUALS, "greater_equals"),
+        FunctionDecl::new(operators::INDEX)
+            .overload(global("index_list_int64", &[LIST_A, INT_TYPE], A))
+            .overload(global("index_list_uint64", &[LIST_A, UINT_TYPE], A))
+            .overload(global("index_map", &[MAP_KV, K], V))
+            .overload(global(
+                "optional_list_index_int",
+                &[optional_list_a.clone(), INT_TYPE],
+                optional_a.clone(),
+            ))
+            .overload(global(
+                "optional_map_index_value",
+                &[optional_map_kv.clone(), K],
+                optional_v.clone(),
+            )),
+        FunctionDecl::new(operators::OPT_INDEX)
+            .overload(global(
+                "list_optindex_optional_int",
+                &[LIST_A, INT_TYPE],
+                optional_a.clone(),
+            ))
+            .overload(global(
+                "optional_list_optindex_optional_int",
+                &[optional_list_a, INT_TYPE],
+                optional_a.clone(),
+            ))
+            .overload(global(
+                "map_optindex_optional_value",
+                &[MAP_KV, K],
+                optional_v.clone(),
+            ))
+            .overload(global(
+                "optional_map_optindex_optional_value",
+                &[optional_map_kv, K],
+                optional_v,
+            )),
+        FunctionDecl::new("optional.of").overload(global("optional_of", &[A], optional_a.clone())),
+        FunctionDecl::new("optional.ofNonZeroValue").overload(global(
+            "optional_ofNonZeroValue",
+            &[A],
+            optional_a.clone(),
+        )),
+        FunctionDecl::new("optional.none").overload(global(
+            "optional_none",
+            &[],
+            optional_a.clone(),
+        )),
+        FunctionDecl::new("hasValue").overload(member(
+            "optional_hasValue",
+            std::slice::from_ref(&optional_a),
+            BOOL_TYPE,
+        )),
+        FunctionDecl::new("value").overload(member(
+            "optional_value",
+            std::slice::from_ref(&optional_a),
+            A,
+        )),
+        FunctionDecl::new("orValue").overload(member(
+            "optional_orValue_value",
+            &[optional_a.clone(), A],
+            A,
+        )),
+        FunctionDecl::new("or").overload(member(
+            "optional_or_optional",
+            &[optional_a.clone(), optional_a.clone()],
+            optional_a,
+        )),
+        FunctionDecl::new(operators::IN)
+            .overload(global("in_list", &[A, LIST_A], BOOL_TYPE))
+            .overload(global("in_map", &[K, MAP_KV], BOOL_TYPE))
+            .overload(global("in_string", &[STRING_TYPE, STRING_TYPE], BOOL_TYPE)),
+        FunctionDecl::new("size")
+            .overload(global("size_string", &[STRING_TYPE], INT_TYPE))
+            .overload(global("size_bytes", &[BYTES_TYPE], INT_TYPE))
+            .overload(global("size_list", &[LIST_A], INT_TYPE))
+            .overload(global("size_map", &[MAP_KV], INT_TYPE))
+            .overload(member("string_size", &[STRING_TYPE], INT_TYPE))
+            .overload(member("bytes_size", &[BYTES_TYPE], INT_TYPE))
+            .overload(member("list_size", &[LIST_A], INT_TYPE))
+            .overload(member("map_size", &[MAP_KV], INT_TYPE)),
+        FunctionDecl::new("contains")
+            .overload(member(
+                "contains_string",
+                &[STRING_TYPE, STRING_TYPE],
+                BOOL_TYPE,
+            ))
+            .overload(member(
+                "contains_bytes",
+                &[BYTES_TYPE, BYTES_TYPE],
+                BOOL_TYPE,
+            ))
+            .overload(member("contains_list", &[LIST_A, A], BOOL_TYPE))
+            .overload(member("contains_map", &[MAP_KV, K], BOOL_TYPE)),
+        FunctionDecl::new("startsWith").overload(member(
+            "starts_with_string",
+            &[STRING_TYPE, STRING_TYPE],
+            BOOL_TYPE,
+        )),
+        FunctionDecl::new("endsWith").overload(member(
+            "ends_with_string",
+            &[STRING_TYPE, STRING_TYPE],
+            BOOL_TYPE,
+        )),
+        conversion(
+            "string",
+            STRING_TYPE,
+            &[
+                ("string", STRING_TYPE),
+                ("int64", INT_TYPE),
+                ("uint64", UINT_TYPE),
+                ("double", DOUBLE_TYPE),
+                ("bytes", BYTES_TYPE),
+                ("timestamp", TIMESTAMP_TYPE),
+                ("duration", DURATION_TYPE),
+            ],
+        ),
+        conversion(
+            "double",
+            DOUBLE_TYPE,
+            &[
+                ("double", DOUBLE_TYPE),
+                ("int64", INT_TYPE),
+                ("uint64", UINT_TYPE),
+                ("string", STRING_TYPE),
+            ],
+        ),
+        conversion(
+            "int",
+            INT_TYPE,
+            &[
+                ("int64", INT_TYPE),
+                ("uint64", UINT_TYPE),
+                ("double", DOUBLE_TYPE),
+                ("string", STRING_TYPE),
+            ],
+        ),
+        conversion(
+            "uint",
+            UINT_TYPE,
+            &[
+                ("uint64", UINT_TYPE),
+                ("int64", INT_TYPE),
+                ("double", DOUBLE_TYPE),
+                ("string", STRING_TYPE),
+            ],
+        ),
+        FunctionDecl::new("bytes").overload(global("string_to_bytes", &[STRING_TYPE], BYTES_TYPE)),
+        extremum("max"),
+        extremum("min"),
+    ];
+
+    #[cfg(feature = "encoders")]
+    decls.extend([
+        FunctionDecl::new("base64.encode").overload(global(
+            "base64_encode_bytes",
+            &[BYTES_TYPE],
+            STRING_TYPE,
+        )),
+        FunctionDecl::new("base64.decode").overload(global(
+            "base64_decode_string",
+            &[STRING_TYPE],
+            BYTES_TYPE,
+        )),
+        FunctionDecl::new("base64.urlEncode").overload(global(
+            "base64_url_encode_bytes",
+            &[BYTES_TYPE],
+            STRING_TYPE,
+        )),
+        FunctionDecl::new("base64.urlDecode").overload(global(
+            "base64_url_decode_string",
+            &[STRING_TYPE],
+            BYTES_TYPE,
+        )),
+    ]);
+
+    #[cfg(feature = "regex")]
+    decls.push(
+        FunctionDecl::new("matches")
+            .overload(global("matches", &[STRING_TYPE, STRING_TYPE], BOOL_TYPE))
+            .overload(member(
+                "matches_string",
+                &[STRING_TYPE, STRING_TYPE],
+                BOOL_TYPE,
+            )),
+    );
+
+    #[cfg(feature = "chrono")]
+    {
+        decls.push(FunctionDecl::new("duration").overload(global(
+            "string_to_duration",
+            &[STRING_TYPE],
+            DURATION_TYPE,
+        )));
+        decls.push(FunctionDecl::new("timestamp").overload(global(
+            "string_to_timestamp",
+            &[STRING_TYPE],
+            TIMESTAMP_TYPE,
+        )));
+        for (name, id) in [
+            ("getFullYear", "timestamp_to_year"),
+            ("getMonth", "timestamp_to_month"),
+            ("getDayOfYear", "timestamp_to_day_of_year"),
+            ("getDayOfMonth", "timestamp_to_day_of_month"),
+            ("getDate", "timestamp_to_day_of_month_1_based"),
+            ("getDayOfWeek", "timestamp_to_day_of_week"),
+            ("getHours", "timestamp_to_hours"),
+            ("getMinutes", "timestamp_to_minutes"),
+            ("getSeconds", "timestamp_to_seconds"),
+            ("getMilliseconds", "timestamp_to_milliseconds"),
+        ] {
+            decls.push(
+                FunctionDecl::new(name)
+                    .overload(member(id, &[TIMESTAMP_TYPE], INT_TYPE))
+                    .overload(member(
+                        format!("{id}_with_tz"),
+                        &[TIMESTAMP_TYPE, STRING_TYPE],
+                        INT_TYPE,
+                    )),
+            );
+        }
+    }
+
+    decls
+}
+
+fn global<S: Into<String>>(id: S, args: &[Type<'static>], result: Type<'static>) -> OverloadDecl {
+    OverloadDecl::global(id, args, result)
+}
+
+fn member<S: Into<String>>(id: S, args: &[Type<'static>], result: Type<'static>) -> OverloadDecl {
+    OverloadDecl::member(id, args, result)
+}
+
+fn arithmetic(name: &str, id: &str, double: bool) -> FunctionDecl {
+    let decl = FunctionDecl::new(name)
+        .overload(global(
+            format!("{id}_int64"),
+            &[INT_TYPE, INT_TYPE],
+            INT_TYPE,
+        ))
+        .overload(global(
+            format!("{id}_uint64"),
+            &[UINT_TYPE, UINT_TYPE],
+            UINT_TYPE,
+        ));
+    if double {
+        decl.overload(global(
+            format!("{id}_double"),
+            &[DOUBLE_TYPE, DOUBLE_TYPE],
+            DOUBLE_TYPE,
+        ))
+    } else {
+        decl
+    }
+}
+
+fn comparison(name: &str, id: &str) -> FunctionDecl {
+    let mut decl = FunctionDecl::new(name);
+    for (suffix, ty) in [
+        ("bool", BOOL_TYPE),
+        ("string", STRING_TYPE),
+        ("bytes", BYTES_TYPE),
+        ("timestamp", TIMESTAMP_TYPE),
+        ("duration", DURATION_TYPE),
+    ] {
+        decl = decl.overload(global(
+            format!("{id}_{suffix}"),
+            &[ty.clone(), ty],
+            BOOL_TYPE,
+        ));
+    }
+    // Ordering is defined across all numeric types.
+    let numerics = [
+        ("int64", INT_TYPE),
+        ("uint64", UINT_TYPE),
+        ("double", DOUBLE_TYPE),
+    ];
+    for (lhs_suffix, lhs) in &numerics {
+        for (rhs_suffix, rhs) in &numerics {
+            let id = if lhs_suffix == rhs_suffix {
+                format!("{id}_{lhs_suffix}")
+            } else {
+                format!("{id}_{lhs_suffix}_{rhs_suffix}")
+            };
+            decl = decl.overload(global(id, &[lhs.clone(), rhs.clone()], BOOL_TYPE));
+        }
+    }
+    decl
+}
+
+/// The number of arguments up to which `max` and `min` are declared, as overloads can't be
+/// variadic although the functions are.
+const EXTREMUM_ARITY: usize = 8;
+
+/// `max` and `min` take either a list, `max([1, 2])`, or their values as arguments,
+/// `max(1, 2)`. A single argument is declared as a list only, as `max(x)` would otherwise be
+/// ambiguous for a list `x`.
+fn extremum(name: &str) -> FunctionDecl {
+    let mut decl = FunctionDecl::new(name).overload(global(format!("{name}_list"), &[LIST_A], A));
+    for arity in 2..=EXTREMUM_ARITY {
+        decl = decl.overload(global(format!("{name}_{arity}"), &vec![A; arity], A));
+    }
+    decl
+}
+
+/// Type conversions can be called both as a function, `int('1')`, and as a method, `'1'.int()`.
+fn conversion(name: &str, result: Type<'static>, from: &[(&str, Type<'static>)]) -> FunctionDecl {
+    let mut decl = FunctionDecl::new(name);
+    for (suffix, ty) in from {
+        decl = decl
+            .overload(global(
+                format!("{suffix}_to_{name}"),
+                std::slice::from_ref(ty),
+                result.clone(),
+            ))
+            .overload(member(
+                format!("{suffix}_{name}"),
+                std::slice::from_ref(ty),
+                result.clone(),
+            ));
+    }
+    decl
+}
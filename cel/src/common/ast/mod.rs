@@ -50,6 +50,21 @@ pub struct IdedExpr {
     pub expr: Expr,
 }
 
+impl IdedExpr {
+    /// Returns the dotted name spelled out by a chain of identifiers and field selections,
+    /// e.g. `a.b.c`. This is how namespaced functions such as `optional.of` are referenced.
+    pub(crate) fn to_qualified_name(&self) -> Option<String> {
+        match &self.expr {
+            Expr::Ident(name) => Some(name.clone()),
+            Expr::Select(select) if !select.test => {
+                let operand = select.operand.to_qualified_name()?;
+                Some(format!("{operand}.{}", select.field))
+            }
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct IdedEntryExpr {
     pub id: u64,
@@ -84,6 +99,9 @@ pub struct MapExpr {
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct ListExpr {
     pub elements: Vec<IdedExpr>,
+    /// The indices of the elements marked as optional, e.g. `[?a, b]`, which are only added to
+    /// the list if the optional value they evaluate to is present.
+    pub optional_indices: Vec<usize>,
 }
 
 #[derive(Clone, Debug, Default, PartialEq)]
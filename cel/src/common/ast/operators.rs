@@ -33,9 +33,21 @@ pub const ALL: &str = "all";
 pub const EXISTS: &str = "exists";
 pub const MAP: &str = "map";
 pub const FILTER: &str = "filter";
+pub const TRANSFORM_LIST: &str = "transformList";
+pub const TRANSFORM_MAP: &str = "transformMap";
+pub const TRANSFORM_MAP_ENTRY: &str = "transformMapEntry";
+/// `cel.bind(var, init, expr)`, `cel.block([init, ...], expr)` and `cel.index(n)`, in the `cel`
+/// namespace.
+pub const CEL_NAMESPACE: &str = "cel";
+pub const BIND: &str = "bind";
+pub const BLOCK: &str = "block";
+pub const BLOCK_INDEX: &str = "index";
 
 pub const NOT_STRICTLY_FALSE: &str = "@not_strictly_false";
 pub const IN: &str = "@in";
+/// Inserts into the map accumulated by `transformMap` and `transformMapEntry`, either a key and
+/// its value or the entries of another map.
+pub const MAP_INSERT: &str = "cel.@mapInsert";
 
 const OPERATORS: [(&str, &str); 12] = [
     ("-", SUBSTRACT),
@@ -1,4 +1,8 @@
 use crate::common::traits;
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::sync::RwLock;
 
 mod bool;
 mod bytes;
@@ -21,7 +25,7 @@ pub use string::String;
 pub use timestamp::Timestamp;
 pub use uint::UInt;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum Kind {
     Unspecified,
     Error,
@@ -45,7 +49,7 @@ pub enum Kind {
     Unknown,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Type<'a> {
     kind: Kind,
     parameters: &'a [&'a Type<'a>],
@@ -210,13 +214,221 @@ impl<'a> Type<'a> {
         }
     }
 
+    pub const fn new_type_param(name: &str) -> Type<'_> {
+        Type {
+            kind: Kind::TypeParam,
+            parameters: &[],
+            runtime_type_name: name,
+            trait_mask: 0,
+        }
+    }
+
     pub fn name(&self) -> &'a str {
         self.runtime_type_name
     }
 
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    pub fn parameters(&self) -> &'a [&'a Type<'a>] {
+        self.parameters
+    }
+
     pub fn has_trait(&self, t: u16) -> bool {
         self.trait_mask & t == t
     }
+
+    /// Returns true for `dyn` and `error`, the types which are assignable to and from any other type.
+    pub fn is_dyn_or_error(&self) -> bool {
+        matches!(self.kind, Kind::Dyn | Kind::Error)
+    }
+}
+
+/// Constructors for types only known at runtime, e.g. the opaque types declared by a host.
+///
+/// The resulting types borrow from a process-wide interner, which allocates every distinct
+/// type and name once and never frees them. The memory it holds is thus proportional to the
+/// number of distinct types ever constructed, which hosts shouldn't derive from untrusted
+/// input, and it is capped at [`MAX_INTERNED`] entries, past which these constructors panic.
+/// The [`crate::checker::Checker`] only interns the types it infers once a check succeeds,
+/// and reports an error rather than panicking when the interner is full.
+impl Type<'static> {
+    pub fn new_list(elem: &Type<'static>) -> Type<'static> {
+        Type::new_list_type(interned(intern_parameters(&[elem])).try_into().unwrap())
+    }
+
+    pub fn new_map(key: &Type<'static>, value: &Type<'static>) -> Type<'static> {
+        Type::new_map_type(
+            interned(intern_parameters(&[key, value]))
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    pub fn new_opaque(name: &str, parameters: &[&Type<'static>]) -> Type<'static> {
+        Type {
+            kind: Kind::Opaque,
+            parameters: interned(intern_parameters(parameters)),
+            runtime_type_name: interned(intern_name(name)),
+            trait_mask: 0,
+        }
+    }
+
+    pub fn new_object(name: &str) -> Type<'static> {
+        interned(Type::try_new_object(name))
+    }
+
+    /// The type of optional values, e.g. `optional.of(1)` is an `optional_type(int)`.
+    pub fn new_optional(elem: &Type<'static>) -> Type<'static> {
+        Type::new_opaque("optional_type", &[elem])
+    }
+
+    pub fn new_param(name: &str) -> Type<'static> {
+        Type::new_type_param(interned(intern_name(name)))
+    }
+
+    /// Returns a copy of this type with its parameters replaced.
+    pub fn with_parameters(&self, parameters: &[&Type<'static>]) -> Type<'static> {
+        interned(self.try_with_parameters(parameters))
+    }
+
+    /// Like [`Type::new_object`], but returns `None` once the interner is full.
+    pub(crate) fn try_new_object(name: &str) -> Option<Type<'static>> {
+        Some(Type {
+            kind: Kind::Struct,
+            parameters: &[],
+            runtime_type_name: intern_name(name)?,
+            trait_mask: traits::FIELD_TESTER_TYPE | traits::INDEXER_TYPE,
+        })
+    }
+
+    /// Like [`Type::with_parameters`], but returns `None` once the interner is full.
+    pub(crate) fn try_with_parameters(
+        &self,
+        parameters: &[&Type<'static>],
+    ) -> Option<Type<'static>> {
+        Some(Type {
+            parameters: intern_parameters(parameters)?,
+            ..self.clone()
+        })
+    }
+}
+
+impl Display for Type<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.runtime_type_name)?;
+        if !self.parameters.is_empty() {
+            f.write_str("(")?;
+            for (i, param) in self.parameters.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{param}")?;
+            }
+            f.write_str(")")?;
+        }
+        Ok(())
+    }
+}
+
+/// The maximum number of types, lists of type parameters and names the interner of the types
+/// constructed at runtime holds.
+pub const MAX_INTERNED: usize = 1 << 16;
+
+lazy_static! {
+    static ref INTERNER: RwLock<Interner> = RwLock::new(Interner::default());
+}
+
+/// Unwraps the result of interning. This panics once the interner is full, but never while
+/// holding its lock, so that the interner remains usable afterwards.
+fn interned<T>(interned: Option<T>) -> T {
+    interned.unwrap_or_else(|| {
+        panic!("more than {MAX_INTERNED} distinct types were constructed at runtime")
+    })
+}
+
+/// Interns `name`, only taking the write lock the first time it is seen.
+fn intern_name(name: &str) -> Option<&'static str> {
+    if let Some(name) = INTERNER.read().unwrap().names.get(name) {
+        return Some(name);
+    }
+    INTERNER.write().unwrap().name(name)
+}
+
+fn intern_parameters(parameters: &[&Type<'static>]) -> Option<&'static [&'static Type<'static>]> {
+    if let Some(parameters) = INTERNER.read().unwrap().find_parameters(parameters) {
+        return Some(parameters);
+    }
+    INTERNER.write().unwrap().parameters(parameters)
+}
+
+#[derive(Default)]
+struct Interner {
+    types: HashSet<&'static Type<'static>>,
+    parameters: HashSet<&'static [&'static Type<'static>]>,
+    names: HashSet<&'static str>,
+}
+
+impl Interner {
+    fn is_full(&self) -> bool {
+        self.types.len() + self.parameters.len() + self.names.len() >= MAX_INTERNED
+    }
+
+    fn name(&mut self, name: &str) -> Option<&'static str> {
+        if let Some(name) = self.names.get(name) {
+            return Some(name);
+        }
+        if self.is_full() {
+            return None;
+        }
+        let name: &'static str = Box::leak(name.into());
+        self.names.insert(name);
+        Some(name)
+    }
+
+    fn ty(&mut self, ty: &Type<'static>) -> Option<&'static Type<'static>> {
+        if let Some(ty) = self.types.get(ty) {
+            return Some(ty);
+        }
+        if self.is_full() {
+            return None;
+        }
+        let ty: &'static Type<'static> = Box::leak(Box::new(ty.clone()));
+        self.types.insert(ty);
+        Some(ty)
+    }
+
+    /// Finds interned parameters without interning anything, so with a shared borrow.
+    fn find_parameters(
+        &self,
+        parameters: &[&Type<'static>],
+    ) -> Option<&'static [&'static Type<'static>]> {
+        let parameters = parameters
+            .iter()
+            .map(|ty| self.types.get(*ty).copied())
+            .collect::<Option<Vec<_>>>()?;
+        self.parameters.get(parameters.as_slice()).copied()
+    }
+
+    fn parameters(
+        &mut self,
+        parameters: &[&Type<'static>],
+    ) -> Option<&'static [&'static Type<'static>]> {
+        let parameters = parameters
+            .iter()
+            .map(|ty| self.ty(ty))
+            .collect::<Option<Vec<_>>>()?;
+        if let Some(parameters) = self.parameters.get(parameters.as_slice()) {
+            return Some(parameters);
+        }
+        if self.is_full() {
+            return None;
+        }
+        let parameters: &'static [&'static Type<'static>] = Box::leak(parameters.into());
+        self.parameters.insert(parameters);
+        Some(parameters)
+    }
 }
 
 #[cfg(test)]
@@ -252,4 +464,20 @@ mod tests {
         assert_eq!(&param, map.parameters[1]);
         assert_eq!(2, map.parameters.len());
     }
+
+    #[test]
+    fn runtime_types() {
+        let list = Type::new_list(&INT_TYPE);
+        assert_eq!(list, Type::new_list(&INT_TYPE));
+        assert_eq!(list.kind(), Kind::List);
+        assert_eq!(list.to_string(), "list(int)");
+
+        let map = Type::new_map(&STRING_TYPE, &list);
+        assert_eq!(map.parameters(), &[&STRING_TYPE, &list]);
+        assert_eq!(map.to_string(), "map(string, list(int))");
+
+        let opaque = Type::new_opaque("my.Type", &[&map]);
+        assert_eq!(opaque.to_string(), "my.Type(map(string, list(int)))");
+        assert_eq!(opaque.with_parameters(&[]).to_string(), "my.Type");
+    }
 }
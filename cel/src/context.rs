@@ -1,9 +1,13 @@
+use crate::cost::CostTracker;
+use crate::locate::ErrorLocations;
 use crate::magic::{Function, FunctionRegistry, IntoFunction};
-use crate::objects::{TryIntoValue, Value};
+use crate::objects::{Key, TryIntoValue, Value};
 use crate::parser::Expression;
+use crate::structs::{StructType, TypeProvider};
+use crate::unknown::AttributePattern;
 use crate::{functions, ExecutionError};
 use std::collections::BTreeMap;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 /// Context is a collection of variables and functions that can be used
 /// by the interpreter to resolve expressions.
@@ -35,14 +39,27 @@ pub enum Context<'a> {
         functions: FunctionRegistry,
         variables: BTreeMap<String, Value>,
         resolver: Option<&'a dyn VariableResolver>,
+        types: Option<Arc<dyn TypeProvider>>,
+        unknowns: Vec<AttributePattern>,
     },
     Child {
         parent: &'a Context<'a>,
         variables: BTreeMap<String, Value>,
         resolver: Option<&'a dyn VariableResolver>,
+        cost: Option<&'a CostTracker>,
+        errors: Option<&'a ErrorLocations>,
+        lazy: Option<LazyVariable<'a>>,
     },
 }
 
+/// A variable bound to an expression which is only evaluated, in the scope of the parent
+/// context, once the variable is first resolved, e.g. the indexes of `cel.block`.
+pub struct LazyVariable<'a> {
+    name: String,
+    expr: &'a Expression,
+    value: OnceLock<Result<Value, ExecutionError>>,
+}
+
 impl<'a> Context<'a> {
     pub fn add_variable<S, V>(
         &mut self,
@@ -100,15 +117,21 @@ impl<'a> Context<'a> {
                 variables,
                 parent,
                 resolver,
-            } => resolver
-                .and_then(|r| r.resolve(name))
-                .or_else(|| {
-                    variables
-                        .get(name)
-                        .cloned()
-                        .or_else(|| parent.get_variable(name).ok())
-                })
-                .ok_or_else(|| ExecutionError::UndeclaredReference(name.to_string().into())),
+                lazy,
+                ..
+            } => {
+                let value = resolver
+                    .and_then(|r| r.resolve(name))
+                    .or_else(|| variables.get(name).cloned());
+                match (value, lazy) {
+                    (Some(value), _) => Ok(value),
+                    (None, Some(lazy)) if lazy.name == name => lazy
+                        .value
+                        .get_or_init(|| Value::resolve(lazy.expr, parent))
+                        .clone(),
+                    (None, _) => parent.get_variable(name),
+                }
+            }
             Context::Root {
                 variables,
                 resolver,
@@ -120,6 +143,37 @@ impl<'a> Context<'a> {
         }
     }
 
+    /// Resolves the attribute `variable`, qualified by `qualifiers`, with the
+    /// [`VariableResolver::resolve_attribute`] of the resolvers, in the same order as
+    /// [`Context::get_variable`]. Returns `None` if it has to be resolved from the variable.
+    pub(crate) fn resolve_attribute(&self, variable: &str, qualifiers: &[Key]) -> Option<Value> {
+        match self {
+            Context::Child {
+                parent, resolver, ..
+            } => resolver
+                .and_then(|r| r.resolve_attribute(variable, qualifiers))
+                .or_else(|| {
+                    if self.binds(variable) {
+                        None
+                    } else {
+                        parent.resolve_attribute(variable, qualifiers)
+                    }
+                }),
+            Context::Root { resolver, .. } => {
+                resolver.and_then(|r| r.resolve_attribute(variable, qualifiers))
+            }
+        }
+    }
+
+    pub(crate) fn has_variable_resolver(&self) -> bool {
+        match self {
+            Context::Root { resolver, .. } => resolver.is_some(),
+            Context::Child {
+                parent, resolver, ..
+            } => resolver.is_some() || parent.has_variable_resolver(),
+        }
+    }
+
     pub(crate) fn get_function(&self, name: &str) -> Option<&Function> {
         match self {
             Context::Root { functions, .. } => functions.get(name),
@@ -136,6 +190,82 @@ impl<'a> Context<'a> {
         };
     }
 
+    /// Sets the provider of the struct types which expressions can construct, e.g.
+    /// `Account{user_id: 123}`. See [`crate::structs::StructType`].
+    pub fn set_type_provider<P: TypeProvider + 'static>(&mut self, provider: P) {
+        if let Context::Root { types, .. } = self {
+            *types = Some(Arc::new(provider));
+        };
+    }
+
+    pub(crate) fn find_struct_type(&self, name: &str) -> Option<Arc<StructType>> {
+        match self {
+            Context::Root { types, .. } => types.as_ref()?.find_struct_type(name),
+            Context::Child { parent, .. } => parent.find_struct_type(name),
+        }
+    }
+
+    /// Marks the attributes matching `pattern` as unknown.
+    ///
+    /// Expressions depending on unknown attributes evaluate to a [`Value::Unknown`] holding
+    /// the ids of the attribute expressions, unless their result doesn't depend on them, e.g.
+    /// `request.auth.admin || true`. This allows evaluating programs in stages, as attributes
+    /// become known.
+    ///
+    /// # Example
+    /// ```
+    /// use cel::unknown::AttributePattern;
+    /// use cel::{Context, Program, Value};
+    ///
+    /// let mut context = Context::default();
+    /// context.add_variable_from_value("path", "/admin");
+    /// context.add_unknown(AttributePattern::new("request").field("auth").wildcard());
+    ///
+    /// let program = Program::compile("path == '/' || request.auth.admin").unwrap();
+    /// assert!(matches!(program.execute(&context), Ok(Value::Unknown(_))));
+    ///
+    /// let program = Program::compile("path == '/' && request.auth.admin").unwrap();
+    /// assert_eq!(program.execute(&context), Ok(false.into()));
+    /// ```
+    pub fn add_unknown(&mut self, pattern: AttributePattern) {
+        if let Context::Root { unknowns, .. } = self {
+            unknowns.push(pattern);
+        };
+    }
+
+    pub(crate) fn has_unknowns(&self) -> bool {
+        match self {
+            Context::Root { unknowns, .. } => !unknowns.is_empty(),
+            Context::Child { parent, .. } => parent.has_unknowns(),
+        }
+    }
+
+    /// Returns whether the attribute `variable`, qualified by `qualifiers`, matches one of the
+    /// unknown patterns. Variables of inner scopes, e.g. comprehension variables, are known.
+    pub(crate) fn is_unknown(&self, variable: &str, qualifiers: &[Key]) -> bool {
+        match self {
+            Context::Root { unknowns, .. } => unknowns
+                .iter()
+                .any(|pattern| pattern.matches(variable, qualifiers)),
+            Context::Child { parent, .. } => {
+                !self.binds(variable) && parent.is_unknown(variable, qualifiers)
+            }
+        }
+    }
+
+    /// Whether `variable` is bound by this scope itself, rather than by its parents.
+    fn binds(&self, variable: &str) -> bool {
+        match self {
+            Context::Root { .. } => false,
+            Context::Child {
+                variables, lazy, ..
+            } => {
+                variables.contains_key(variable)
+                    || lazy.as_ref().is_some_and(|lazy| lazy.name == variable)
+            }
+        }
+    }
+
     pub fn resolve(&self, expr: &Expression) -> Result<Value, ExecutionError> {
         Value::resolve(expr, self)
     }
@@ -149,9 +279,197 @@ impl<'a> Context<'a> {
             parent: self,
             variables: Default::default(),
             resolver: None,
+            cost: None,
+            errors: None,
+            lazy: None,
+        }
+    }
+
+    /// Creates an inner scope binding `name` to the value of `expr`, which is evaluated in this
+    /// scope once `name` is first resolved.
+    pub(crate) fn new_lazy_scope<'b>(&'b self, name: &str, expr: &'b Expression) -> Context<'b> {
+        Context::Child {
+            parent: self,
+            variables: Default::default(),
+            resolver: None,
+            cost: None,
+            errors: None,
+            lazy: Some(LazyVariable {
+                name: name.to_string(),
+                expr,
+                value: OnceLock::new(),
+            }),
+        }
+    }
+
+    /// Creates an inner scope charging the cost of the expressions evaluated in it, or in its
+    /// own inner scopes, to `tracker`.
+    pub(crate) fn new_tracked_scope<'b>(&'b self, tracker: &'b CostTracker) -> Context<'b> {
+        Context::Child {
+            parent: self,
+            variables: Default::default(),
+            resolver: None,
+            cost: Some(tracker),
+            errors: None,
+            lazy: None,
+        }
+    }
+
+    /// Creates an inner scope recording where the errors raised by the expressions evaluated
+    /// in it, or in its own inner scopes, are raised.
+    pub(crate) fn new_located_scope<'b>(&'b self, errors: &'b ErrorLocations) -> Context<'b> {
+        Context::Child {
+            parent: self,
+            variables: Default::default(),
+            resolver: None,
+            cost: None,
+            errors: Some(errors),
+            lazy: None,
+        }
+    }
+
+    /// Returns the error locations of the closest scope recording them.
+    pub(crate) fn error_locations(&self) -> Option<&ErrorLocations> {
+        match self {
+            Context::Root { .. } => None,
+            Context::Child {
+                errors: Some(errors),
+                ..
+            } => Some(errors),
+            Context::Child { parent, .. } => parent.error_locations(),
         }
     }
 
+    /// Charges `cost` to the tracker of the closest scope having one, failing if its limit is
+    /// exceeded.
+    pub(crate) fn charge(&self, cost: u64) -> Result<(), ExecutionError> {
+        match self {
+            Context::Root { .. } => Ok(()),
+            Context::Child {
+                cost: Some(tracker),
+                ..
+            } => tracker.charge(cost),
+            Context::Child { parent, .. } => parent.charge(cost),
+        }
+    }
+
+    /// Adds the functions of the [strings extension](crate::extensions::strings): `charAt`,
+    /// `indexOf`, `lastIndexOf`, `lowerAscii`, `upperAscii`, `replace`, `split`, `substring`,
+    /// `trim`, `join`, `reverse`, `strings.quote` and `format`.
+    ///
+    /// # Example
+    /// ```
+    /// use cel::{Context, Program};
+    /// let mut context = Context::default();
+    /// context.add_strings_extension();
+    /// let program = Program::compile("'a,b'.split(',').join('-')").unwrap();
+    /// assert_eq!(program.execute(&context), Ok("a-b".into()));
+    /// ```
+    pub fn add_strings_extension(&mut self) {
+        use crate::extensions::strings;
+        self.add_function("charAt", strings::char_at);
+        self.add_function("indexOf", strings::index_of);
+        self.add_function("lastIndexOf", strings::last_index_of);
+        self.add_function("lowerAscii", strings::lower_ascii);
+        self.add_function("upperAscii", strings::upper_ascii);
+        self.add_function("replace", strings::replace);
+        self.add_function("split", strings::split);
+        self.add_function("substring", strings::substring);
+        self.add_function("trim", strings::trim);
+        self.add_function("join", strings::join);
+        self.add_method_overload(
+            "reverse",
+            |this| matches!(this, Value::String(_)),
+            strings::reverse,
+        );
+        self.add_function("strings.quote", strings::quote);
+        self.add_function("format", strings::format);
+    }
+
+    /// Adds the functions of the [math extension](crate::extensions::math): `math.greatest`,
+    /// `math.least`, `math.ceil`, `math.floor`, `math.round`, `math.trunc`, `math.abs`,
+    /// `math.sign`, `math.isNaN`, `math.isInf`, `math.isFinite`, `math.bitAnd`, `math.bitOr`,
+    /// `math.bitXor`, `math.bitNot`, `math.bitShiftLeft` and `math.bitShiftRight`.
+    pub fn add_math_extension(&mut self) {
+        use crate::extensions::math;
+        self.add_function("math.greatest", math::greatest);
+        self.add_function("math.least", math::least);
+        self.add_function("math.ceil", math::ceil);
+        self.add_function("math.floor", math::floor);
+        self.add_function("math.round", math::round);
+        self.add_function("math.trunc", math::trunc);
+        self.add_function("math.abs", math::abs);
+        self.add_function("math.sign", math::sign);
+        self.add_function("math.isNaN", math::is_nan);
+        self.add_function("math.isInf", math::is_inf);
+        self.add_function("math.isFinite", math::is_finite);
+        self.add_function("math.bitAnd", math::bit_and);
+        self.add_function("math.bitOr", math::bit_or);
+        self.add_function("math.bitXor", math::bit_xor);
+        self.add_function("math.bitNot", math::bit_not);
+        self.add_function("math.bitShiftLeft", math::bit_shift_left);
+        self.add_function("math.bitShiftRight", math::bit_shift_right);
+    }
+
+    /// Adds the functions of the [lists extension](crate::extensions::lists): `lists.range`,
+    /// `slice`, `flatten`, `sort`, `sortBy`, `distinct`, `reverse`, `join`, `first` and `last`.
+    /// `join` is the same function as in the strings extension.
+    pub fn add_lists_extension(&mut self) {
+        use crate::extensions::{lists, strings};
+        self.add_function("lists.range", lists::range);
+        self.add_function("slice", lists::slice);
+        self.add_function("flatten", lists::flatten);
+        self.add_function("sort", lists::sort);
+        self.add_function("sortBy", lists::sort_by);
+        self.add_function(
+            crate::parser::SORT_BY_ASSOCIATED_KEYS,
+            lists::sort_by_associated_keys,
+        );
+        self.add_function("distinct", lists::distinct);
+        self.add_method_overload(
+            "reverse",
+            |this| matches!(this, Value::List(_)),
+            lists::reverse,
+        );
+        self.add_method_overload("join", |this| matches!(this, Value::List(_)), strings::join);
+        self.add_function("first", lists::first);
+        self.add_function("last", lists::last);
+    }
+
+    /// Adds `function` as the implementation of the method `name` for the targets accepted by
+    /// `accepts`, keeping the function previously added as `name`, if any, for the other
+    /// targets, e.g. `reverse`, which the strings extension adds for strings and the lists one
+    /// for lists.
+    fn add_method_overload<T: 'static, F>(
+        &mut self,
+        name: &str,
+        accepts: fn(&Value) -> bool,
+        function: F,
+    ) where
+        F: IntoFunction<T> + 'static + Send + Sync,
+    {
+        let Context::Root { functions, .. } = self else {
+            return;
+        };
+        let function = function.into_function();
+        let previous = functions.get_shared(name);
+        let overload: Function = Box::new(move |ftx| match (&previous, &ftx.this) {
+            (Some(previous), Some(this)) if !accepts(this) => previous(ftx),
+            (None, Some(this)) if !accepts(this) => Err(ExecutionError::NoSuchOverload),
+            _ => function(ftx),
+        });
+        functions.insert(name, Arc::new(overload));
+    }
+
+    /// Adds the functions of the [sets extension](crate::extensions::sets): `sets.contains`,
+    /// `sets.equivalent` and `sets.intersects`.
+    pub fn add_sets_extension(&mut self) {
+        use crate::extensions::sets;
+        self.add_function("sets.contains", sets::contains);
+        self.add_function("sets.equivalent", sets::equivalent);
+        self.add_function("sets.intersects", sets::intersects);
+    }
+
     /// Constructs a new empty context with no variables or functions.
     ///
     /// If you're looking for a context that has all the standard methods, functions
@@ -168,6 +486,8 @@ impl<'a> Context<'a> {
             variables: Default::default(),
             functions: Default::default(),
             resolver: None,
+            types: None,
+            unknowns: Vec::new(),
         }
     }
 }
@@ -178,6 +498,8 @@ impl Default for Context<'_> {
             variables: Default::default(),
             functions: Default::default(),
             resolver: None,
+            types: None,
+            unknowns: Vec::new(),
         };
 
         ctx.add_function("contains", functions::contains);
@@ -191,10 +513,29 @@ impl Default for Context<'_> {
         ctx.add_function("double", functions::double);
         ctx.add_function("int", functions::int);
         ctx.add_function("uint", functions::uint);
+        ctx.add_function("optional.of", functions::optional::of);
+        ctx.add_function("optional.none", functions::optional::none);
+        ctx.add_function(
+            "optional.ofNonZeroValue",
+            functions::optional::of_non_zero_value,
+        );
+        ctx.add_function("hasValue", functions::optional::has_value);
+        ctx.add_function("value", functions::optional::value);
+        ctx.add_function("orValue", functions::optional::or_value);
+        ctx.add_function("or", functions::optional::or);
 
         #[cfg(feature = "regex")]
         ctx.add_function("matches", functions::matches);
 
+        #[cfg(feature = "encoders")]
+        {
+            use crate::extensions::encoders;
+            ctx.add_function("base64.encode", encoders::encode);
+            ctx.add_function("base64.decode", encoders::decode);
+            ctx.add_function("base64.urlEncode", encoders::url_encode);
+            ctx.add_function("base64.urlDecode", encoders::url_decode);
+        }
+
         #[cfg(feature = "chrono")]
         {
             ctx.add_function("duration", functions::duration);
@@ -238,22 +579,171 @@ impl Default for Context<'_> {
 /// ```
 pub trait VariableResolver: Send + Sync {
     fn resolve(&self, variable: &str) -> Option<Value>;
+
+    /// Resolves an attribute, i.e. a variable followed by the fields selected or the indices
+    /// taken from it with literal keys, without resolving the whole variable. For example
+    /// `request.headers['x']` is resolved as `("request", ["headers", "x"])`; as for maps, a
+    /// field `a.name` is qualified by the same key as `a['name']`.
+    ///
+    /// Returning `None` falls back to resolving the attribute without its last qualifier,
+    /// and eventually the variable itself with [`VariableResolver::resolve`]. The default
+    /// implementation always does.
+    ///
+    /// # Example
+    /// ```
+    /// use cel::objects::Key;
+    /// use cel::{Context, Program, Value};
+    /// use std::collections::HashMap;
+    ///
+    /// struct Request {
+    ///     headers: HashMap<String, String>,
+    /// }
+    ///
+    /// impl cel::context::VariableResolver for Request {
+    ///     fn resolve(&self, variable: &str) -> Option<Value> {
+    ///         match variable {
+    ///             "request" => Some(HashMap::from([("headers", self.headers.clone())]).into()),
+    ///             _ => None,
+    ///         }
+    ///     }
+    ///
+    ///     fn resolve_attribute(&self, variable: &str, qualifiers: &[Key]) -> Option<Value> {
+    ///         match (variable, qualifiers) {
+    ///             ("request", [Key::String(field), Key::String(name)]) if **field == "headers" => {
+    ///                 self.headers.get(name.as_str()).map(|value| value.as_str().into())
+    ///             }
+    ///             _ => None,
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let request = Request {
+    ///     headers: HashMap::from([("x".to_string(), "1".to_string())]),
+    /// };
+    /// let mut context = Context::default();
+    /// context.set_variable_resolver(&request);
+    ///
+    /// let program = Program::compile("request.headers['x'] == '1'").unwrap();
+    /// assert_eq!(program.execute(&context), Ok(true.into()));
+    /// ```
+    fn resolve_attribute(&self, _variable: &str, _qualifiers: &[Key]) -> Option<Value> {
+        None
+    }
 }
 
 impl<T: VariableResolver> VariableResolver for Box<T> {
     fn resolve(&self, variable: &str) -> Option<Value> {
         (**self).resolve(variable)
     }
+
+    fn resolve_attribute(&self, variable: &str, qualifiers: &[Key]) -> Option<Value> {
+        (**self).resolve_attribute(variable, qualifiers)
+    }
 }
 
 impl<T: VariableResolver> VariableResolver for Arc<T> {
     fn resolve(&self, variable: &str) -> Option<Value> {
         (**self).resolve(variable)
     }
+
+    fn resolve_attribute(&self, variable: &str, qualifiers: &[Key]) -> Option<Value> {
+        (**self).resolve_attribute(variable, qualifiers)
+    }
 }
 
 impl<T: VariableResolver> VariableResolver for &T {
     fn resolve(&self, variable: &str) -> Option<Value> {
         (**self).resolve(variable)
     }
+
+    fn resolve_attribute(&self, variable: &str, qualifiers: &[Key]) -> Option<Value> {
+        (**self).resolve_attribute(variable, qualifiers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Program;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Resolves `request.headers[name]` on its own, recording the attributes asked for.
+    #[derive(Default)]
+    struct Request {
+        resolved: Mutex<Vec<String>>,
+    }
+
+    impl VariableResolver for Request {
+        fn resolve(&self, variable: &str) -> Option<Value> {
+            self.resolved.lock().unwrap().push(variable.to_string());
+            match variable {
+                "request" => Some(
+                    HashMap::from([
+                        ("headers", Value::from(HashMap::from([("x", "full")]))),
+                        ("path", Value::from("/")),
+                    ])
+                    .into(),
+                ),
+                _ => None,
+            }
+        }
+
+        fn resolve_attribute(&self, variable: &str, qualifiers: &[Key]) -> Option<Value> {
+            match (variable, qualifiers) {
+                ("request", [Key::String(field), Key::String(name)]) if **field == "headers" => {
+                    self.resolved
+                        .lock()
+                        .unwrap()
+                        .push(format!("request.headers.{name}"));
+                    Some(format!("lazy {name}").into())
+                }
+                _ => None,
+            }
+        }
+    }
+
+    fn execute(request: &Request, expr: &str) -> Result<Value, ExecutionError> {
+        request.resolved.lock().unwrap().clear();
+        let mut context = Context::default();
+        context.set_variable_resolver(request);
+        Program::compile(expr).unwrap().execute(&context)
+    }
+
+    #[test]
+    fn resolve_attributes() {
+        let request = Request::default();
+        for expr in ["request.headers['x']", "request.headers.x"] {
+            assert_eq!(execute(&request, expr), Ok("lazy x".into()), "{expr}");
+            assert_eq!(
+                *request.resolved.lock().unwrap(),
+                ["request.headers.x"],
+                "{expr}"
+            );
+        }
+    }
+
+    #[test]
+    fn resolve_attributes_fallback() {
+        let request = Request::default();
+        assert_eq!(execute(&request, "request.path"), Ok("/".into()));
+        assert_eq!(*request.resolved.lock().unwrap(), ["request"]);
+
+        // Only literal keys and non-optional qualifiers are resolved as attributes.
+        let expr = "request.headers[string('x')]";
+        assert_eq!(execute(&request, expr), Ok("full".into()));
+        let expr = "request.?headers.x";
+        assert_eq!(
+            execute(&request, expr),
+            Ok(Value::Optional(Some(Box::new("full".into()))))
+        );
+    }
+
+    #[test]
+    fn resolve_attributes_shadowed() {
+        let request = Request::default();
+        let expr = "[{'headers': {'x': 'local'}}].map(request, request.headers.x)";
+        assert_eq!(execute(&request, expr), Ok(vec!["local"].into()));
+        assert!(request.resolved.lock().unwrap().is_empty());
+    }
 }
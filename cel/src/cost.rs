@@ -0,0 +1,680 @@
+use crate::common::ast::{operators, CallExpr, ComprehensionExpr, EntryExpr, Expr};
+use crate::common::value::CelVal;
+use crate::objects::Value;
+use crate::parser::{is_block_binding, Expression};
+use crate::{Context, ExecutionError};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The cost of building a list, e.g. `[a, b]`.
+const LIST_CREATE_COST: u64 = 10;
+/// The cost of building a map, e.g. `{'a': b}`.
+const MAP_CREATE_COST: u64 = 30;
+/// The cost of building a struct, e.g. `Account{id: 1}`.
+const STRUCT_CREATE_COST: u64 = 40;
+/// The cost of each element of strings, bytes, lists and maps traversed by an operation, e.g.
+/// the characters compared by `a == b` on strings.
+const TRAVERSAL_COST_FACTOR: f64 = 0.1;
+
+/// Accounts for the cost of executing a program and aborts it once the cost exceeds a limit,
+/// see [`crate::Program::execute_with_cost`].
+///
+/// Every variable, field selection and function call costs 1, as does each iteration of a
+/// comprehension; building lists, maps and structs costs 10, 30 and 40. Operations traversing
+/// strings, bytes or lists, e.g. `a + b` or `a.startsWith(b)`, additionally cost 1 per 10
+/// elements traversed, and function calls 1 per 10 elements of the string, bytes, list or map
+/// they return, e.g. `lists.range(n)`. Literals are free.
+///
+/// # Example
+/// ```
+/// use cel::cost::CostTracker;
+/// use cel::{Context, ExecutionError, Program};
+///
+/// let program = Program::compile("[1, 2, 3].map(x, x * 2)").unwrap();
+/// let tracker = CostTracker::new();
+/// program.execute_with_cost(&Context::default(), &tracker).unwrap();
+/// assert!(tracker.cost() > 0);
+///
+/// let tracker = CostTracker::with_limit(10);
+/// let result = program.execute_with_cost(&Context::default(), &tracker);
+/// assert_eq!(result, Err(ExecutionError::CostLimitExceeded(10)));
+/// ```
+#[derive(Debug, Default)]
+pub struct CostTracker {
+    limit: Option<u64>,
+    cost: AtomicU64,
+}
+
+impl CostTracker {
+    /// Creates a tracker without limit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a tracker aborting the execution with [`ExecutionError::CostLimitExceeded`] once
+    /// the cost exceeds `limit`.
+    pub fn with_limit(limit: u64) -> Self {
+        CostTracker {
+            limit: Some(limit),
+            cost: AtomicU64::new(0),
+        }
+    }
+
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// Returns the cost accumulated so far by the executions using this tracker.
+    pub fn cost(&self) -> u64 {
+        self.cost.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn charge(&self, cost: u64) -> Result<(), ExecutionError> {
+        let total = self.cost.fetch_add(cost, Ordering::Relaxed) + cost;
+        match self.limit {
+            Some(limit) if total > limit => Err(ExecutionError::CostLimitExceeded(limit)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The cost of evaluating `expr` itself, excluding its subexpressions.
+pub(crate) fn expr_cost(expr: &Expr) -> u64 {
+    match expr {
+        Expr::Literal(_) | Expr::Comprehension(_) | Expr::Unspecified => 0,
+        Expr::Ident(_) | Expr::Select(_) | Expr::Call(_) => 1,
+        Expr::List(_) => LIST_CREATE_COST,
+        Expr::Map(_) => MAP_CREATE_COST,
+        Expr::Struct(_) => STRUCT_CREATE_COST,
+    }
+}
+
+/// The cost of traversing the elements of `value`.
+pub(crate) fn traversal_cost(value: &Value) -> u64 {
+    let size = match value {
+        Value::String(s) => s.len(),
+        Value::Bytes(b) => b.len(),
+        Value::List(l) => l.len(),
+        Value::Map(m) => m.map.len(),
+        _ => return 0,
+    };
+    traversal_units(size as u64)
+}
+
+/// Bounds of the cost of an expression, or of the size of its value, as computed by
+/// [`CostEstimator`]. A maximum of `u64::MAX` means unbounded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Estimate {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl Estimate {
+    pub const UNBOUNDED: Estimate = Estimate {
+        min: 0,
+        max: u64::MAX,
+    };
+
+    pub fn exact(value: u64) -> Self {
+        Estimate {
+            min: value,
+            max: value,
+        }
+    }
+
+    /// The bounds of either `self` or `other`.
+    fn union(self, other: Estimate) -> Self {
+        Estimate {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+impl std::ops::Add for Estimate {
+    type Output = Estimate;
+
+    fn add(self, other: Estimate) -> Estimate {
+        Estimate {
+            min: self.min.saturating_add(other.min),
+            max: self.max.saturating_add(other.max),
+        }
+    }
+}
+
+impl std::ops::Mul for Estimate {
+    type Output = Estimate;
+
+    fn mul(self, other: Estimate) -> Estimate {
+        Estimate {
+            min: self.min.saturating_mul(other.min),
+            max: self.max.saturating_mul(other.max),
+        }
+    }
+}
+
+/// Estimates the cost of an expression before executing it, according to the costs charged
+/// by [`CostTracker`], so that expressions which could get too expensive can be rejected
+/// upfront.
+///
+/// The cost of comprehensions and of the operations traversing strings, bytes or lists depends
+/// on the size of the values involved, which is given by size hints: the maximum length of a
+/// string or bytes attribute, or the maximum number of entries of a list or map attribute. The
+/// elements of a list, or the keys and values of a map, are referred to with `[]`, e.g.
+/// `request.items[]`. Attributes without
+/// hint may be arbitrarily large, which makes the maximum cost of the operations traversing
+/// them unbounded, so scalar attributes should be declared with a size of 0.
+///
+/// # Example
+/// ```
+/// use cel::cost::CostEstimator;
+/// use cel::{Context, Program};
+///
+/// let program = Program::compile("items.all(item, item.name.startsWith('a'))").unwrap();
+/// let estimator = CostEstimator::new()
+///     .size_hint("items", 100)
+///     .size_hint("items[].name", 64);
+/// let estimate = estimator.estimate(program.expression(), &Context::default());
+/// assert_eq!(estimate.min, 2);
+/// assert_eq!(estimate.max, 1502);
+/// ```
+#[derive(Debug, Default)]
+pub struct CostEstimator {
+    sizes: HashMap<String, u64>,
+    functions: HashMap<String, Estimate>,
+}
+
+impl CostEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares the maximum size of `attribute`, e.g. `request.items` or `request.items[]`.
+    pub fn size_hint<S: Into<String>>(mut self, attribute: S, max_size: u64) -> Self {
+        self.sizes.insert(attribute.into(), max_size);
+        self
+    }
+
+    /// Sets the cost of the calls to the function `name`, excluding the cost of evaluating
+    /// their arguments. Defaults to 1, the cost charged by [`CostTracker`], and is meant for
+    /// expensive functions registered in the [`crate::Context`].
+    pub fn function_cost<S: Into<String>>(mut self, name: S, cost: Estimate) -> Self {
+        self.functions.insert(name.into(), cost);
+        self
+    }
+
+    /// Estimates the cost of executing `expr` with the functions of `context`.
+    pub fn estimate(&self, expr: &Expression, context: &Context) -> Estimate {
+        Estimation {
+            estimator: self,
+            context,
+            scopes: Vec::new(),
+        }
+        .expr(expr)
+        .cost
+    }
+
+    fn size_of(&self, attribute: &str) -> Estimate {
+        match self.sizes.get(attribute) {
+            Some(max) => Estimate { min: 0, max: *max },
+            None => Estimate::UNBOUNDED,
+        }
+    }
+}
+
+/// The estimated cost and size of an expression, along with the attribute it refers to, if any.
+struct Estimated {
+    cost: Estimate,
+    size: Estimate,
+    attribute: Option<String>,
+}
+
+impl Estimated {
+    fn value(cost: Estimate, size: Estimate) -> Self {
+        Estimated {
+            cost,
+            size,
+            attribute: None,
+        }
+    }
+}
+
+struct Estimation<'e> {
+    estimator: &'e CostEstimator,
+    context: &'e Context<'e>,
+    /// The variables of comprehensions in scope, with the attribute they iterate over and
+    /// their size.
+    scopes: Vec<(String, Option<String>, Estimate)>,
+}
+
+impl Estimation<'_> {
+    fn expr(&mut self, expr: &Expression) -> Estimated {
+        let cost = Estimate::exact(expr_cost(&expr.expr));
+        match &expr.expr {
+            Expr::Literal(val) => {
+                let size = match val {
+                    CelVal::String(s) => s.len(),
+                    CelVal::Bytes(b) => b.len(),
+                    _ => 0,
+                };
+                Estimated::value(cost, Estimate::exact(size as u64))
+            }
+            Expr::Ident(name) => match self.scopes.iter().rev().find(|(var, ..)| var == name) {
+                Some((_, attribute, size)) => Estimated {
+                    cost,
+                    size: *size,
+                    attribute: attribute.clone(),
+                },
+                None => self.attribute(cost, Some(name.clone())),
+            },
+            Expr::Select(select) => {
+                let operand = self.expr(&select.operand);
+                if select.test {
+                    return Estimated::value(cost + operand.cost, Estimate::exact(0));
+                }
+                let attribute = operand
+                    .attribute
+                    .map(|attribute| format!("{attribute}.{}", select.field));
+                self.attribute(cost + operand.cost, attribute)
+            }
+            Expr::Call(call) => self.call(call, cost),
+            Expr::Comprehension(comprehension) => self.comprehension(comprehension),
+            Expr::List(list) => {
+                let cost = list
+                    .elements
+                    .iter()
+                    .fold(cost, |cost, element| cost + self.expr(element).cost);
+                let len = list.elements.len() as u64;
+                let size = Estimate {
+                    min: len - list.optional_indices.len() as u64,
+                    max: len,
+                };
+                Estimated::value(cost, size)
+            }
+            Expr::Map(map) => {
+                let mut cost = cost;
+                let mut size = Estimate::exact(0);
+                for entry in &map.entries {
+                    if let EntryExpr::MapEntry(entry) = &entry.expr {
+                        cost = cost + self.expr(&entry.key).cost + self.expr(&entry.value).cost;
+                        size = size
+                            + Estimate {
+                                min: !entry.optional as u64,
+                                max: 1,
+                            };
+                    }
+                }
+                Estimated::value(cost, size)
+            }
+            Expr::Struct(s) => {
+                let cost = s
+                    .entries
+                    .iter()
+                    .fold(cost, |cost, entry| match &entry.expr {
+                        EntryExpr::StructField(field) => cost + self.expr(&field.value).cost,
+                        EntryExpr::MapEntry(_) => cost,
+                    });
+                Estimated::value(cost, Estimate::exact(0))
+            }
+            Expr::Unspecified => Estimated::value(cost, Estimate::exact(0)),
+        }
+    }
+
+    fn attribute(&self, cost: Estimate, attribute: Option<String>) -> Estimated {
+        let size = match &attribute {
+            Some(attribute) => self.estimator.size_of(attribute),
+            None => Estimate::UNBOUNDED,
+        };
+        Estimated {
+            cost,
+            size,
+            attribute,
+        }
+    }
+
+    fn call(&mut self, call: &CallExpr, cost: Estimate) -> Estimated {
+        let name = call.func_name.as_str();
+        match (name, call.args.len()) {
+            (operators::CONDITIONAL, 3) => {
+                let cond = self.expr(&call.args[0]);
+                let left = self.expr(&call.args[1]);
+                let right = self.expr(&call.args[2]);
+                Estimated::value(
+                    cost + cond.cost + left.cost.union(right.cost),
+                    left.size.union(right.size),
+                )
+            }
+            (operators::LOGICAL_AND | operators::LOGICAL_OR, 2) => {
+                let left = self.expr(&call.args[0]);
+                // The right operand isn't evaluated when the left one decides the result.
+                let right = self.expr(&call.args[1]);
+                let right = Estimate {
+                    min: 0,
+                    max: right.cost.max,
+                };
+                Estimated::value(cost + left.cost + right, Estimate::exact(0))
+            }
+            (operators::INDEX | operators::OPT_INDEX, 2) => {
+                let operand = self.expr(&call.args[0]);
+                let index = self.expr(&call.args[1]);
+                let attribute = operand.attribute.map(|attribute| match &call.args[1].expr {
+                    Expr::Literal(CelVal::String(field)) => format!("{attribute}.{field}"),
+                    _ => format!("{attribute}[]"),
+                });
+                self.attribute(cost + operand.cost + index.cost, attribute)
+            }
+            (operators::OPT_SELECT, 2) => {
+                let operand = self.expr(&call.args[0]);
+                let attribute = match &call.args[1].expr {
+                    Expr::Literal(CelVal::String(field)) => operand
+                        .attribute
+                        .map(|attribute| format!("{attribute}.{field}")),
+                    _ => None,
+                };
+                self.attribute(cost + operand.cost, attribute)
+            }
+            (
+                operators::ADD
+                | operators::SUBSTRACT
+                | operators::DIVIDE
+                | operators::MULTIPLY
+                | operators::MODULO
+                | operators::EQUALS
+                | operators::NOT_EQUALS
+                | operators::LESS
+                | operators::LESS_EQUALS
+                | operators::GREATER
+                | operators::GREATER_EQUALS
+                | operators::IN,
+                2,
+            ) => {
+                let left = self.expr(&call.args[0]);
+                let right = self.expr(&call.args[1]);
+                let cost = cost
+                    + left.cost
+                    + right.cost
+                    + traversal_estimate(left.size)
+                    + traversal_estimate(right.size);
+                let size = match name {
+                    operators::ADD => left.size + right.size,
+                    _ => Estimate::exact(0),
+                };
+                Estimated::value(cost, size)
+            }
+            (operators::LOGICAL_NOT | operators::NEGATE | operators::NOT_STRICTLY_FALSE, 1) => {
+                let operand = self.expr(&call.args[0]);
+                Estimated::value(cost + operand.cost, Estimate::exact(0))
+            }
+            _ => self.function(call),
+        }
+    }
+
+    fn function(&mut self, call: &CallExpr) -> Estimated {
+        let name = call.func_name.as_str();
+        // Namespaced functions, e.g. `optional.of(x)`, don't evaluate their target.
+        let namespaced = call
+            .target
+            .as_ref()
+            .and_then(|target| target.to_qualified_name())
+            .map(|namespace| format!("{namespace}.{name}"))
+            .filter(|name| self.context.get_function(name).is_some());
+        let mut cost = match &namespaced {
+            Some(name) => self.estimator.functions.get(name),
+            None => self.estimator.functions.get(name),
+        }
+        .copied()
+        .unwrap_or(Estimate::exact(1));
+        if let (Some(target), None) = (&call.target, &namespaced) {
+            let target = self.expr(target);
+            cost = cost + target.cost;
+            if traverses(name) {
+                cost = cost + traversal_estimate(target.size);
+            }
+        }
+        for arg in &call.args {
+            cost = cost + self.expr(arg).cost;
+        }
+        // The size of the results which are neither strings, bytes, lists nor maps is 0.
+        let size = match namespaced.as_deref().unwrap_or(name) {
+            "size" | "int" | "uint" | "double" | "bool" | "hasValue" => Estimate::exact(0),
+            "timestamp" | "duration" | "optional.of" | "optional.none" => Estimate::exact(0),
+            name if traverses(name) => Estimate::exact(0),
+            _ => Estimate::UNBOUNDED,
+        };
+        // Calls are charged for the size of their result.
+        Estimated::value(cost + traversal_estimate(size), size)
+    }
+
+    fn comprehension(&mut self, comprehension: &ComprehensionExpr) -> Estimated {
+        let range = self.expr(&comprehension.iter_range);
+        let init = self.expr(&comprehension.accu_init);
+        let element = range.attribute.as_ref().map(|range| format!("{range}[]"));
+        let element_size = match &element {
+            Some(element) => self.estimator.size_of(element),
+            None => Estimate::UNBOUNDED,
+        };
+        // List and map accumulators, as built by `map`, `filter` and the `transform` macros,
+        // grow by up to one entry per iteration.
+        let accu_size = match &comprehension.accu_init.expr {
+            Expr::List(_) | Expr::Map(_) => init.size + range.size,
+            _ => init.size,
+        };
+        // With two variables, the first one is the index of a list or the key of a map, and
+        // the second one the element or value, both bounded by the hint of the elements.
+        if let Some(var) = &comprehension.iter_var2 {
+            self.scopes
+                .push((var.clone(), element.clone(), element_size));
+        }
+        self.scopes
+            .push((comprehension.iter_var.clone(), element, element_size));
+        self.scopes
+            .push((comprehension.accu_var.clone(), None, accu_size));
+        let cond = self.expr(&comprehension.loop_cond);
+        let step = self.expr(&comprehension.loop_step);
+        let result = self.expr(&comprehension.result);
+        self.scopes
+            .truncate(self.scopes.len() - 2 - comprehension.iter_var2.is_some() as usize);
+
+        // Unless the loop condition is always true, the loop may stop after the first
+        // iteration.
+        let iterations = match &comprehension.loop_cond.expr {
+            Expr::Literal(CelVal::Boolean(true)) => range.size,
+            _ => Estimate {
+                min: range.size.min.min(1),
+                max: range.size.max,
+            },
+        };
+        // The initializers of `cel.block` are only evaluated if their index is used.
+        if is_block_binding(comprehension) {
+            let init = Estimate {
+                min: 0,
+                max: init.cost.max,
+            };
+            return Estimated::value(init + result.cost, result.size);
+        }
+        let cost = range.cost
+            + init.cost
+            + iterations * (Estimate::exact(1) + cond.cost + step.cost)
+            + result.cost;
+        Estimated::value(cost, result.size)
+    }
+}
+
+/// Whether calling the function `name` on a string, bytes or list traverses it.
+pub(crate) fn traverses(name: &str) -> bool {
+    matches!(name, "contains" | "startsWith" | "endsWith" | "matches")
+}
+
+fn traversal_estimate(size: Estimate) -> Estimate {
+    Estimate {
+        min: traversal_units(size.min),
+        max: traversal_units(size.max),
+    }
+}
+
+pub(crate) fn traversal_units(size: u64) -> u64 {
+    if size == u64::MAX {
+        return u64::MAX;
+    }
+    (size as f64 * TRAVERSAL_COST_FACTOR).ceil() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context, Program};
+
+    fn cost(expr: &str) -> u64 {
+        let mut context = Context::default();
+        context.add_variable_from_value("a", std::collections::HashMap::from([("b", 1)]));
+        let tracker = CostTracker::new();
+        Program::compile(expr)
+            .unwrap()
+            .execute_with_cost(&context, &tracker)
+            .unwrap();
+        tracker.cost()
+    }
+
+    #[test]
+    fn costs() {
+        for (expr, expected) in [
+            ("1", 0),
+            ("a", 1),
+            ("a.b", 2),
+            ("[1, 2]", 10),
+            ("{1: 2}", 30),
+            ("'abc' + 'def'", 3),
+            ("'abcdefghijk'.startsWith('a')", 3),
+            ("size([1, 2, 3])", 11),
+            ("[1, 2, 3].all(x, x > 0)", 32),
+            ("[1, 2, 3].map(x, x * 2)", 71),
+        ] {
+            assert_eq!(cost(expr), expected, "{expr}");
+        }
+    }
+
+    #[test]
+    fn cost_limit() {
+        let mut context = Context::default();
+        context.add_variable_from_value("items", (0..100).collect::<Vec<i64>>());
+        let program = Program::compile("items.map(x, items.map(y, x * y))").unwrap();
+
+        let tracker = CostTracker::with_limit(1000);
+        assert_eq!(
+            program.execute_with_cost(&context, &tracker),
+            Err(ExecutionError::CostLimitExceeded(1000))
+        );
+        assert!(tracker.cost() > 1000);
+        assert!(tracker.cost() < 1100);
+
+        // The cost adds up over executions sharing a tracker.
+        let program = Program::compile("items[0] + 1").unwrap();
+        let tracker = CostTracker::with_limit(9);
+        for _ in 0..3 {
+            program.execute_with_cost(&context, &tracker).unwrap();
+        }
+        assert_eq!(tracker.cost(), 9);
+        assert!(program.execute_with_cost(&context, &tracker).is_err());
+    }
+
+    #[test]
+    fn function_results() {
+        let cost = |expr: &str| {
+            let mut context = Context::default();
+            context.add_strings_extension();
+            context.add_lists_extension();
+            let tracker = CostTracker::new();
+            Program::compile(expr)
+                .unwrap()
+                .execute_with_cost(&context, &tracker)
+                .unwrap();
+            tracker.cost()
+        };
+        for (expr, expected) in [
+            ("lists.range(100)", 11),
+            ("lists.range(100).distinct()", 22),
+            ("lists.range(100).sort()", 22),
+            ("[lists.range(100)].flatten()", 32),
+            ("'a,b,c,d,e,f,g,h,i,j,k'.split(',')", 3),
+            ("['a', 'b'].join(',')", 12),
+            ("'abc'.replace('b', 'bbbbbbbbbbbbbbbbbbbb')", 4),
+            ("'%s-%s'.format(['abcdefghij', 'abcdefghij'])", 14),
+        ] {
+            assert_eq!(cost(expr), expected, "{expr}");
+        }
+    }
+
+    #[test]
+    fn function_results_limit() {
+        let mut context = Context::default();
+        context.add_strings_extension();
+        context.add_lists_extension();
+        for expr in [
+            "size(lists.range(10000000)) > 0",
+            "size('a'.replace('', 'abcdefghij').replace('', 'abcdefghij').replace('', 'abcdefghij')) > 0",
+        ] {
+            let tracker = CostTracker::with_limit(100);
+            assert_eq!(
+                Program::compile(expr)
+                    .unwrap()
+                    .execute_with_cost(&context, &tracker),
+                Err(ExecutionError::CostLimitExceeded(100)),
+                "{expr}"
+            );
+        }
+    }
+
+    #[test]
+    fn estimates() {
+        let estimator = CostEstimator::new()
+            .size_hint("a", 10)
+            .size_hint("items", 100)
+            .size_hint("items[]", 0);
+        for (expr, min, max) in [
+            ("1", 0, 0),
+            ("a", 1, 1),
+            ("a == 'abc'", 3, 4),
+            ("b == 'abc'", 3, u64::MAX),
+            ("true || a == 'abc'", 1, 5),
+            ("a.startsWith('x') ? [1] : {}", 13, 34),
+            ("items.map(x, x * 2)", 12, 2612),
+            ("items.exists(x, x == 2)", 2, 802),
+            ("optional.of(a).hasValue()", 3, 3),
+        ] {
+            let program = Program::compile(expr).unwrap();
+            let estimate = estimator.estimate(program.expression(), &Context::default());
+            assert_eq!(estimate, Estimate { min, max }, "{expr}");
+        }
+    }
+
+    #[test]
+    fn estimates_bound_actual_costs() {
+        let estimator = CostEstimator::new()
+            .size_hint("name", 20)
+            .size_hint("items", 50)
+            .size_hint("items[]", 0)
+            .size_hint("tags", 10)
+            .size_hint("tags[]", 8);
+        let mut context = Context::default();
+        context.add_variable_from_value("name", "abcdefghijklmnopqrst");
+        context.add_variable_from_value("items", (0..50).collect::<Vec<i64>>());
+        context.add_variable_from_value("tags", vec!["abcdefgh"; 10]);
+        for expr in [
+            "name + name == name",
+            "items.filter(x, x % 2 == 0).map(x, x * x)",
+            "items.all(x, x < 25) || tags.exists(t, t.endsWith('h'))",
+            "tags.exists_one(t, t.contains('c')) && size(items) > 0",
+            "{'name': name, 'count': size(tags)}",
+        ] {
+            let program = Program::compile(expr).unwrap();
+            let estimate = estimator.estimate(program.expression(), &context);
+            let tracker = CostTracker::new();
+            program.execute_with_cost(&context, &tracker).unwrap();
+            assert!(
+                estimate.min <= tracker.cost() && tracker.cost() <= estimate.max,
+                "{expr}: {} not within {estimate:?}",
+                tracker.cost()
+            );
+        }
+    }
+}
@@ -0,0 +1,732 @@
+// The inverse of `ser.rs`: a Deserializer for our Value enum, modelled after the one of
+// [serde_json](https://github.com/serde-rs/json/blob/master/src/value/de.rs).
+
+use crate::objects::{Key, Map};
+use crate::Value;
+use serde::de::{
+    self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, Unexpected,
+    VariantAccess, Visitor,
+};
+use serde::Deserialize;
+use std::fmt::{self, Display};
+use std::sync::Arc;
+
+#[cfg(feature = "chrono")]
+use crate::ser::{Duration, Timestamp};
+
+/// Deserializes a value, e.g. the result of a program, into any type implementing
+/// [`serde::Deserialize`].
+///
+/// Maps and structs deserialize into structs and maps, lists into sequences, strings into
+/// enums with unit variants, and single-entry maps into enums with data, as
+/// [`to_value`](crate::to_value) serializes them. Timestamps and durations deserialize into
+/// [`Timestamp`](crate::Timestamp) and [`Duration`](crate::Duration).
+///
+/// # Example
+/// ```
+/// use cel::{from_value, Context, Program};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct User {
+///     name: String,
+///     groups: Vec<String>,
+/// }
+///
+/// let program = Program::compile("{'name': 'alice', 'groups': ['admin']}").unwrap();
+/// let value = program.execute(&Context::default()).unwrap();
+/// let user: User = from_value(value).unwrap();
+/// assert_eq!(user.groups, vec!["admin".to_string()]);
+///
+/// let value = Program::compile("{'name': 'bob', 'groups': [1]}")
+///     .unwrap()
+///     .execute(&Context::default())
+///     .unwrap();
+/// let err = from_value::<User>(value).unwrap_err();
+/// assert_eq!(err.to_string(), "invalid type: integer `1`, expected a string at groups[0]");
+/// ```
+pub fn from_value<'de, T>(value: Value) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize(value)
+}
+
+/// An error deserializing a value, with the path of the value in which it occurred, e.g.
+/// `users[0].name`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeserializationError {
+    message: String,
+    path: Vec<Segment>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl DeserializationError {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The path of the value which failed to deserialize, empty if it's the top-level value.
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+        for segment in &self.path {
+            match segment {
+                Segment::Key(key) if path.is_empty() => path.push_str(key),
+                Segment::Key(key) => {
+                    path.push('.');
+                    path.push_str(key);
+                }
+                Segment::Index(index) => path.push_str(&format!("[{index}]")),
+            }
+        }
+        path
+    }
+
+    fn within(mut self, segment: Segment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+}
+
+impl de::Error for DeserializationError {
+    fn custom<T: Display>(msg: T) -> Self {
+        DeserializationError {
+            message: msg.to_string(),
+            path: Vec::new(),
+        }
+    }
+}
+
+impl Display for DeserializationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "{} at {}", self.message, self.path())
+        }
+    }
+}
+
+impl std::error::Error for DeserializationError {}
+
+pub type Result<T> = std::result::Result<T, DeserializationError>;
+
+impl Value {
+    fn unexpected(&self) -> Unexpected<'_> {
+        match self {
+            Value::Null => Unexpected::Unit,
+            Value::Bool(b) => Unexpected::Bool(*b),
+            Value::Int(i) => Unexpected::Signed(*i),
+            Value::UInt(u) => Unexpected::Unsigned(*u),
+            Value::Float(f) => Unexpected::Float(*f),
+            Value::String(s) => Unexpected::Str(s),
+            Value::Bytes(b) => Unexpected::Bytes(b),
+            Value::List(_) => Unexpected::Seq,
+            Value::Map(_) | Value::Struct(_) => Unexpected::Map,
+            #[cfg(feature = "chrono")]
+            Value::Timestamp(_) => Unexpected::Other("timestamp"),
+            #[cfg(feature = "chrono")]
+            Value::Duration(_) => Unexpected::Other("duration"),
+            Value::Function(..) => Unexpected::Other("function"),
+            Value::Opaque(_) => Unexpected::Other("opaque value"),
+            Value::Optional(_) => Unexpected::Option,
+            Value::Unknown(_) => Unexpected::Other("unknown value"),
+        }
+    }
+
+    fn invalid_type<E: de::Error>(&self, expected: &dyn de::Expected) -> E {
+        E::invalid_type(self.unexpected(), expected)
+    }
+}
+
+fn unwrap_arc<T: Clone>(arc: Arc<T>) -> T {
+    Arc::try_unwrap(arc).unwrap_or_else(|arc| (*arc).clone())
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = DeserializationError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::Int(i) => visitor.visit_i64(i),
+            Value::UInt(u) => visitor.visit_u64(u),
+            Value::Float(f) => visitor.visit_f64(f),
+            Value::String(s) => visitor.visit_string(unwrap_arc(s)),
+            Value::Bytes(b) => visitor.visit_byte_buf(unwrap_arc(b)),
+            Value::List(list) => visitor.visit_seq(SeqDeserializer::new(unwrap_arc(list))),
+            Value::Map(map) => visitor.visit_map(MapDeserializer::new(map)),
+            Value::Struct(s) => {
+                let fields = s
+                    .struct_type()
+                    .fields()
+                    .map(|(name, decl)| {
+                        let value = s.get(name).unwrap_or(&decl.default).clone();
+                        (Value::String(Arc::new(name.to_string())), value)
+                    })
+                    .collect();
+                visitor.visit_map(MapDeserializer::from_entries(fields))
+            }
+            #[cfg(feature = "chrono")]
+            Value::Timestamp(ts) => visitor.visit_string(ts.to_rfc3339()),
+            Value::Optional(Some(value)) => value.deserialize_any(visitor),
+            Value::Optional(None) => visitor.visit_none(),
+            value => Err(value.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Null | Value::Optional(None) => visitor.visit_none(),
+            Value::Optional(Some(value)) => visitor.visit_some(*value),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        match (name, self) {
+            #[cfg(feature = "chrono")]
+            (Timestamp::NAME, Value::Timestamp(ts)) => {
+                visitor.visit_newtype_struct(Value::String(Arc::new(ts.to_rfc3339())))
+            }
+            #[cfg(feature = "chrono")]
+            (Duration::NAME, Value::Duration(d)) => {
+                let (secs, nanos) = (d.num_seconds(), d.subsec_nanos() as i64);
+                let proxy = Map {
+                    map: Arc::new(
+                        [
+                            (Key::from(Duration::SECS_FIELD), Value::Int(secs)),
+                            (Key::from(Duration::NANOS_FIELD), Value::Int(nanos)),
+                        ]
+                        .into_iter()
+                        .collect(),
+                    ),
+                };
+                visitor.visit_newtype_struct(Value::Map(proxy))
+            }
+            (_, value) => visitor.visit_newtype_struct(value),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self {
+            Value::String(variant) => visitor.visit_enum(EnumDeserializer {
+                variant: unwrap_arc(variant),
+                value: None,
+            }),
+            Value::Map(map) if map.map.len() == 1 => {
+                let (key, value) = map.map.iter().next().unwrap();
+                let Key::String(variant) = key else {
+                    return Err(de::Error::invalid_type(
+                        Value::from(key).unexpected(),
+                        &"a string variant name",
+                    ));
+                };
+                visitor.visit_enum(EnumDeserializer {
+                    variant: variant.to_string(),
+                    value: Some(value.clone()),
+                })
+            }
+            value => Err(de::Error::invalid_type(
+                value.unexpected(),
+                &"a string or a map with a single key",
+            )),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            Value::Null | Value::Optional(None) => visitor.visit_unit(),
+            value => Err(value.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        seq tuple tuple_struct map struct identifier
+    }
+}
+
+impl IntoDeserializer<'_, DeserializationError> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+struct SeqDeserializer {
+    iter: std::iter::Enumerate<std::vec::IntoIter<Value>>,
+}
+
+impl SeqDeserializer {
+    fn new(values: Vec<Value>) -> Self {
+        SeqDeserializer {
+            iter: values.into_iter().enumerate(),
+        }
+    }
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer {
+    type Error = DeserializationError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        match self.iter.next() {
+            Some((index, value)) => seed
+                .deserialize(value)
+                .map(Some)
+                .map_err(|err| err.within(Segment::Index(index))),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapDeserializer {
+    entries: std::vec::IntoIter<(Value, Value)>,
+    /// The key of the entry being deserialized, for the path of errors.
+    key: Option<String>,
+    value: Option<Value>,
+}
+
+impl MapDeserializer {
+    fn new(map: Map) -> Self {
+        let entries = unwrap_arc(map.map)
+            .into_iter()
+            .map(|(key, value)| (Value::from(key), value))
+            .collect();
+        MapDeserializer::from_entries(entries)
+    }
+
+    fn from_entries(entries: Vec<(Value, Value)>) -> Self {
+        MapDeserializer {
+            entries: entries.into_iter(),
+            key: None,
+            value: None,
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer {
+    type Error = DeserializationError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        let name = match &key {
+            Value::String(s) => s.to_string(),
+            Value::Int(i) => i.to_string(),
+            Value::UInt(u) => u.to_string(),
+            Value::Bool(b) => b.to_string(),
+            key => format!("{key:?}"),
+        };
+        self.value = Some(value);
+        let key = seed
+            .deserialize(key)
+            .map_err(|err| err.within(Segment::Key(name.clone())))?;
+        self.key = Some(name);
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("value is missing"))?;
+        let key = self.key.take().unwrap_or_default();
+        seed.deserialize(value)
+            .map_err(|err| err.within(Segment::Key(key)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: Option<Value>,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = DeserializationError;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant)> {
+        let variant = seed.deserialize(Value::String(Arc::new(self.variant.clone())))?;
+        Ok((
+            variant,
+            VariantDeserializer {
+                variant: self.variant,
+                value: self.value,
+            },
+        ))
+    }
+}
+
+struct VariantDeserializer {
+    variant: String,
+    value: Option<Value>,
+}
+
+impl VariantDeserializer {
+    fn value(self, expected: &str) -> Result<Value> {
+        self.value
+            .ok_or_else(|| de::Error::invalid_type(Unexpected::UnitVariant, &expected))
+    }
+}
+
+impl<'de> VariantAccess<'de> for VariantDeserializer {
+    type Error = DeserializationError;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.value {
+            None | Some(Value::Null) => Ok(()),
+            Some(value) => Err(de::Error::invalid_type(value.unexpected(), &"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        let variant = self.variant.clone();
+        seed.deserialize(self.value("newtype variant")?)
+            .map_err(|err| err.within(Segment::Key(variant)))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        let variant = self.variant.clone();
+        de::Deserializer::deserialize_seq(self.value("tuple variant")?, visitor)
+            .map_err(|err| err.within(Segment::Key(variant)))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let variant = self.variant.clone();
+        de::Deserializer::deserialize_map(self.value("struct variant")?, visitor)
+            .map_err(|err| err.within(Segment::Key(variant)))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct TimestampVisitor;
+
+        impl<'de> Visitor<'de> for TimestampVisitor {
+            type Value = Timestamp;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an RFC 3339 timestamp")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Timestamp, E> {
+                chrono::DateTime::parse_from_rfc3339(v)
+                    .map(Timestamp)
+                    .map_err(E::custom)
+            }
+
+            fn visit_newtype_struct<D: de::Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> std::result::Result<Timestamp, D::Error> {
+                deserializer.deserialize_str(self)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(Timestamp::NAME, TimestampVisitor)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<'de> Deserialize<'de> for Duration {
+    fn deserialize<D: de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        // The `{secs, nanos}` struct in which `ser.rs` serializes durations.
+        struct DurationVisitor;
+
+        impl<'de> Visitor<'de> for DurationVisitor {
+            type Value = Duration;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a duration struct with secs and nanos")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Duration, A::Error> {
+                let (mut secs, mut nanos) = (None, None);
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        Duration::SECS_FIELD => secs = Some(map.next_value::<i64>()?),
+                        Duration::NANOS_FIELD => nanos = Some(map.next_value::<i64>()?),
+                        _ => {
+                            return Err(de::Error::unknown_field(
+                                &key,
+                                &[Duration::SECS_FIELD, Duration::NANOS_FIELD],
+                            ))
+                        }
+                    }
+                }
+                let secs = secs.ok_or_else(|| de::Error::missing_field(Duration::SECS_FIELD))?;
+                let nanos = nanos.unwrap_or_default();
+                chrono::Duration::try_seconds(secs)
+                    .and_then(|d| d.checked_add(&chrono::Duration::nanoseconds(nanos)))
+                    .map(Duration)
+                    .ok_or_else(|| de::Error::custom("duration out of range"))
+            }
+
+            fn visit_newtype_struct<D: de::Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> std::result::Result<Duration, D::Error> {
+                deserializer.deserialize_map(self)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(Duration::NAME, DurationVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::structs::{Struct, StructType};
+    use crate::{from_value, to_value, Context, Program, Value};
+    use serde::{Deserialize, Serialize};
+    use std::collections::{BTreeMap, HashMap};
+    use std::sync::Arc;
+
+    #[cfg(feature = "chrono")]
+    use crate::{Duration, Timestamp};
+
+    fn execute(expr: &str) -> Value {
+        Program::compile(expr)
+            .unwrap()
+            .execute(&Context::default())
+            .unwrap()
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct User {
+        name: String,
+        age: u8,
+        nickname: Option<String>,
+        #[serde(default)]
+        groups: Vec<String>,
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Point(i64, i64),
+        Rect { width: u32, height: u32 },
+    }
+
+    #[test]
+    fn primitives() {
+        assert_eq!(from_value::<i32>(execute("-5")), Ok(-5));
+        assert_eq!(from_value::<u64>(execute("5u")), Ok(5));
+        assert_eq!(from_value::<f64>(execute("1.5")), Ok(1.5));
+        assert_eq!(from_value::<f64>(execute("2")), Ok(2.0));
+        assert_eq!(from_value::<bool>(execute("true")), Ok(true));
+        assert_eq!(from_value::<String>(execute("'a'")), Ok("a".to_string()));
+        assert_eq!(from_value::<()>(execute("null")), Ok(()));
+        assert_eq!(
+            from_value::<serde_bytes::ByteBuf>(execute("b'\\x00\\xff'")).map(|b| b.into_vec()),
+            Ok(vec![0, 255])
+        );
+        assert_eq!(
+            from_value::<Vec<i64>>(execute("[1, 2, 3]")),
+            Ok(vec![1, 2, 3])
+        );
+        assert_eq!(
+            from_value::<HashMap<i64, String>>(execute("{1: 'one'}")),
+            Ok(HashMap::from([(1, "one".to_string())]))
+        );
+        assert_eq!(from_value::<Option<i64>>(execute("null")), Ok(None));
+        assert_eq!(
+            from_value::<Option<i64>>(execute("optional.of(1)")),
+            Ok(Some(1))
+        );
+        assert_eq!(
+            from_value::<Option<i64>>(execute("optional.none()")),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn structs() {
+        let user = User {
+            name: "alice".to_string(),
+            age: 30,
+            nickname: None,
+            groups: vec!["admin".to_string()],
+        };
+        assert_eq!(from_value::<User>(to_value(&user).unwrap()), Ok(user));
+        assert_eq!(
+            from_value::<User>(execute("{'name': 'bob', 'age': 40, 'nickname': 'b'}")),
+            Ok(User {
+                name: "bob".to_string(),
+                age: 40,
+                nickname: Some("b".to_string()),
+                groups: vec![],
+            })
+        );
+
+        // Fields which aren't set deserialize from their default value.
+        let ty = Arc::new(
+            StructType::new("User")
+                .field("name", crate::common::types::STRING_TYPE)
+                .field_with_default("age", crate::common::types::UINT_TYPE, 18u64)
+                .field("nickname", crate::common::types::DYN_TYPE),
+        );
+        let fields = BTreeMap::from([("name".to_string(), Value::from("carol"))]);
+        let value = Value::Struct(Struct::new(ty, fields).unwrap());
+        assert_eq!(
+            from_value::<User>(value),
+            Ok(User {
+                name: "carol".to_string(),
+                age: 18,
+                nickname: None,
+                groups: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn enums() {
+        for shape in [
+            Shape::Empty,
+            Shape::Circle(1.5),
+            Shape::Point(1, -1),
+            Shape::Rect {
+                width: 2,
+                height: 3,
+            },
+        ] {
+            let value = to_value(&shape).unwrap();
+            assert_eq!(from_value::<Shape>(value), Ok(shape));
+        }
+        assert_eq!(from_value::<Shape>(execute("'Empty'")), Ok(Shape::Empty));
+        assert_eq!(
+            from_value::<Shape>(execute("{'Rect': {'width': 1u, 'height': 2}}")),
+            Ok(Shape::Rect {
+                width: 1,
+                height: 2
+            })
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn time() {
+        #[derive(Deserialize, Serialize, Debug, PartialEq)]
+        struct Event {
+            at: Timestamp,
+            lasts: Duration,
+        }
+
+        let event = Event {
+            at: chrono::DateTime::parse_from_rfc3339("2025-01-01T12:00:00+02:00")
+                .unwrap()
+                .into(),
+            lasts: chrono::Duration::milliseconds(-1500).into(),
+        };
+        assert_eq!(from_value::<Event>(to_value(&event).unwrap()), Ok(event));
+        assert_eq!(
+            from_value::<Event>(execute(
+                "{'at': timestamp('2025-01-01T10:00:00Z'), 'lasts': duration('1h')}"
+            )),
+            Ok(Event {
+                at: chrono::DateTime::parse_from_rfc3339("2025-01-01T10:00:00Z")
+                    .unwrap()
+                    .into(),
+                lasts: chrono::Duration::hours(1).into(),
+            })
+        );
+        assert_eq!(
+            from_value::<String>(execute("timestamp('2025-01-01T10:00:00Z')")),
+            Ok("2025-01-01T10:00:00+00:00".to_string())
+        );
+    }
+
+    #[test]
+    fn errors() {
+        #[derive(Deserialize, Debug)]
+        struct Users {
+            #[allow(dead_code)]
+            users: Vec<User>,
+        }
+
+        let err = from_value::<u8>(execute("300")).unwrap_err();
+        assert_eq!(err.message(), "invalid value: integer `300`, expected u8");
+        assert_eq!(err.path(), "");
+
+        let err = from_value::<Vec<u8>>(execute("[1, 'a']")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid type: string \"a\", expected u8 at [1]"
+        );
+
+        for (expr, expected) in [
+            (
+                "{'users': [{'name': 'a', 'age': 1}, {'name': 'b', 'age': -1}]}",
+                "invalid value: integer `-1`, expected u8 at users[1].age",
+            ),
+            (
+                "{'users': [{'age': 1}]}",
+                "missing field `name` at users[0]",
+            ),
+            (
+                "{'users': {1: 2}}",
+                "invalid type: map, expected a sequence at users",
+            ),
+        ] {
+            let err = from_value::<Users>(execute(expr)).unwrap_err();
+            assert_eq!(err.to_string(), expected, "{expr}");
+        }
+
+        let err = from_value::<Shape>(execute("{'Rect': {'width': -1, 'height': 2}}")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value: integer `-1`, expected u32 at Rect.width"
+        );
+    }
+}
@@ -0,0 +1,554 @@
+use crate::checker::{CheckErrors, CheckedExpr, Checker, FunctionDecl, OverloadDecl};
+use crate::common::types::{Kind, Type};
+use crate::magic::{Function, FunctionRegistry, IntoFunction};
+use crate::parser::{Macro, ParseErrors, Parser};
+use crate::resolvers::Argument;
+use crate::structs::TypeProvider;
+use crate::unknown::Unknown;
+use crate::{Context, ExecutionError, FunctionContext, Program, ResolveResult, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Env declares everything a program is allowed to reference: typed variables, and functions
+/// along with their overloads and, optionally, their implementation.
+///
+/// The declarations are used to type check programs at compile time, while the function
+/// implementations are wired into every [`Context`] created from the environment.
+///
+/// # Example
+/// ```
+/// use cel::checker::{FunctionDecl, OverloadDecl};
+/// use cel::common::types::{Type, BOOL_TYPE, DYN_TYPE, STRING_TYPE};
+/// use cel::extractors::This;
+/// use cel::Env;
+/// use std::collections::HashMap;
+/// use std::sync::Arc;
+///
+/// let env = Env::default()
+///     .variable("request", Type::new_map(&STRING_TYPE, &DYN_TYPE))
+///     .variable("groups", Type::new_list(&STRING_TYPE))
+///     .function(
+///         FunctionDecl::new("isAdmin").overload(OverloadDecl::member(
+///             "string_is_admin",
+///             &[STRING_TYPE],
+///             BOOL_TYPE,
+///         )),
+///         |This(user): This<Arc<String>>| user.as_str() == "root",
+///     );
+///
+/// let program = env.compile("request.user.isAdmin() || 'admins' in groups").unwrap();
+///
+/// let mut context = env.context();
+/// context.add_variable("request", HashMap::from([("user", "root")])).unwrap();
+/// context.add_variable("groups", Vec::<String>::new()).unwrap();
+/// assert_eq!(program.execute(&context), Ok(true.into()));
+///
+/// // References to undeclared variables, or mistyped calls, are rejected at compile time.
+/// assert!(env.compile("requests.user == 'root'").is_err());
+/// assert!(env.compile("groups.isAdmin()").is_err());
+/// ```
+#[derive(Clone)]
+pub struct Env {
+    checker: Checker,
+    functions: FunctionRegistry,
+    types: Option<Arc<dyn TypeProvider>>,
+    macros: Vec<Macro>,
+    implementations: HashMap<String, Implementations>,
+}
+
+impl Default for Env {
+    /// Creates an environment with the declarations and implementations of all the standard
+    /// operators and functions.
+    fn default() -> Self {
+        let Context::Root { functions, .. } = Context::default() else {
+            unreachable!("the default context is a root context")
+        };
+        Env {
+            checker: Checker::new(),
+            functions,
+            types: None,
+            macros: Vec::new(),
+            implementations: HashMap::new(),
+        }
+    }
+}
+
+impl Env {
+    /// Creates an environment without any declarations or function implementations.
+    pub fn empty() -> Self {
+        Env {
+            checker: Checker::empty(),
+            functions: FunctionRegistry::default(),
+            types: None,
+            macros: Vec::new(),
+            implementations: HashMap::new(),
+        }
+    }
+
+    /// Declares a variable of the given type.
+    pub fn variable<S: Into<String>>(mut self, name: S, ty: Type<'static>) -> Self {
+        self.checker.add_variable(name, ty);
+        self
+    }
+
+    /// Declares a function and registers its implementation, see [`Context::add_function`] for
+    /// the handlers that are supported.
+    ///
+    /// A function may have several implementations, each registered along with the overloads
+    /// it implements. Calls are then dispatched to the most recently registered implementation
+    /// having an overload which accepts the arguments, checked against the kinds of their
+    /// values, or else to the implementation the function had before, e.g. a standard one.
+    /// Such functions have their arguments evaluated before being dispatched, so they can't
+    /// take identifiers or expressions like `map` does.
+    ///
+    /// # Example
+    /// ```
+    /// use cel::checker::{FunctionDecl, OverloadDecl};
+    /// use cel::common::types::{INT_TYPE, STRING_TYPE};
+    /// use cel::Env;
+    /// use std::sync::Arc;
+    ///
+    /// let env = Env::default()
+    ///     .function(
+    ///         FunctionDecl::new("twice").overload(OverloadDecl::global(
+    ///             "twice_int",
+    ///             &[INT_TYPE],
+    ///             INT_TYPE,
+    ///         )),
+    ///         |x: i64| x * 2,
+    ///     )
+    ///     .function(
+    ///         FunctionDecl::new("twice").overload(OverloadDecl::global(
+    ///             "twice_string",
+    ///             &[STRING_TYPE],
+    ///             STRING_TYPE,
+    ///         )),
+    ///         |s: Arc<String>| s.repeat(2),
+    ///     );
+    ///
+    /// let program = env.compile("twice(2) == 4 && twice('a') == 'aa'").unwrap();
+    /// assert_eq!(program.execute(&env.context()), Ok(true.into()));
+    /// ```
+    pub fn function<T: 'static, F>(mut self, decl: FunctionDecl, handler: F) -> Self
+    where
+        F: IntoFunction<T> + 'static + Send + Sync,
+    {
+        let handler = Arc::new(handler.into_function());
+        let previous = self.functions.get_shared(&decl.name);
+        let implementations = self
+            .implementations
+            .entry(decl.name.clone())
+            .or_insert_with(|| Implementations {
+                overloads: Vec::new(),
+                fallback: previous,
+            });
+        implementations
+            .overloads
+            .push((decl.overloads.clone(), handler.clone()));
+        if implementations.overloads.len() == 1 && implementations.fallback.is_none() {
+            self.functions.insert(&decl.name, handler);
+        } else {
+            let implementations = implementations.clone();
+            let dispatch: Function = Box::new(move |ftx| implementations.call(ftx));
+            self.functions.add(&decl.name, dispatch);
+        }
+        self.checker.add_function(decl);
+        self
+    }
+
+    /// Adds the declarations, functions and macros of a library, e.g. one of the
+    /// [extensions](crate::extensions).
+    ///
+    /// # Example
+    /// ```
+    /// use cel::extensions::lists::Lists;
+    /// use cel::Env;
+    ///
+    /// let env = Env::default().library(Lists);
+    /// let program = env.compile("[3, 1, 2].sort() == [1, 2, 3]").unwrap();
+    /// assert_eq!(program.execute(&env.context()), Ok(true.into()));
+    /// ```
+    pub fn library<L: Library>(mut self, library: L) -> Self {
+        let mut context = Context::Root {
+            functions: std::mem::take(&mut self.functions),
+            variables: Default::default(),
+            resolver: None,
+            types: None,
+            unknowns: Vec::new(),
+        };
+        library.add_functions(&mut context);
+        let Context::Root { functions, .. } = context else {
+            unreachable!("the context is a root context")
+        };
+        self.functions = functions;
+        let decls = library.declarations();
+        // The functions replaced by the library no longer dispatch to the ones registered before.
+        self.implementations
+            .retain(|name, _| !decls.iter().any(|d| &d.name == name));
+        for decl in decls {
+            self.checker.add_function(decl);
+        }
+        self.macros.extend(library.macros());
+        self
+    }
+
+    /// Declares a function without an implementation, e.g. to add overloads to one of the
+    /// standard functions, or for functions which are only implemented by some contexts.
+    pub fn declare_function(mut self, decl: FunctionDecl) -> Self {
+        self.checker.add_function(decl);
+        self
+    }
+
+    /// Sets the provider of the struct types that programs can construct, both for checking
+    /// and at runtime.
+    pub fn type_provider<P: TypeProvider + 'static>(mut self, provider: P) -> Self {
+        let provider: Arc<dyn TypeProvider> = Arc::new(provider);
+        self.checker.set_type_provider(provider.clone());
+        self.types = Some(provider);
+        self
+    }
+
+    /// Registers a macro used when parsing programs, see [`Macro`].
+    pub fn add_macro(mut self, macro_: Macro) -> Self {
+        self.macros.push(macro_);
+        self
+    }
+
+    /// Parses and type checks `source`.
+    pub fn compile(&self, source: &str) -> Result<Program, CompileError> {
+        let parser = Parser::new().macros(self.macros.iter().rev().cloned());
+        let program = Program::compile_with(source, parser)?;
+        self.check(&program)?;
+        Ok(program)
+    }
+
+    /// Type checks an already compiled program against the declarations of this environment.
+    pub fn check(&self, program: &Program) -> Result<CheckedExpr, CheckErrors> {
+        self.checker.check(program.expression())
+    }
+
+    pub fn checker(&self) -> &Checker {
+        &self.checker
+    }
+
+    /// Creates a new context with all the function implementations of this environment, to
+    /// which the values of the declared variables can then be added.
+    pub fn context(&self) -> Context<'static> {
+        Context::Root {
+            functions: self.functions.clone(),
+            variables: Default::default(),
+            resolver: None,
+            types: self.types.clone(),
+            unknowns: Vec::new(),
+        }
+    }
+}
+
+/// A library of functions which can be added to an [`Env`] with [`Env::library`], bringing both
+/// their declarations, to type check programs, and their implementations.
+pub trait Library {
+    /// The declarations of the functions of the library.
+    fn declarations(&self) -> Vec<FunctionDecl>;
+
+    /// Registers the implementations of the functions of the library.
+    fn add_functions(&self, context: &mut Context);
+
+    /// The macros of the library, used when parsing programs.
+    fn macros(&self) -> Vec<Macro> {
+        Vec::new()
+    }
+}
+
+/// The implementations of a function registered with [`Env::function`], along with the
+/// overloads each of them implements.
+#[derive(Clone)]
+struct Implementations {
+    overloads: Vec<(Vec<OverloadDecl>, Arc<Function>)>,
+    /// The implementation of the calls matching none of the overloads.
+    fallback: Option<Arc<Function>>,
+}
+
+impl Implementations {
+    fn call(&self, ftx: &mut FunctionContext) -> ResolveResult {
+        let args: Vec<ResolveResult> = (0..ftx.args.len())
+            .map(|i| ftx.resolve(Argument(i)))
+            .collect();
+        let mut values = Vec::with_capacity(args.len() + 1);
+        values.extend(ftx.this.clone());
+        for arg in &args {
+            values.push(arg.clone()?);
+        }
+        if let Some(unknown) = Unknown::merge(&values) {
+            return Ok(Value::Unknown(unknown));
+        }
+
+        let handler = self
+            .overloads
+            .iter()
+            .rev()
+            .find(|(overloads, _)| overloads.iter().any(|o| accepts(o, &values)))
+            .map(|(_, handler)| handler)
+            .or(self.fallback.as_ref())
+            .ok_or(ExecutionError::NoSuchOverload)?;
+        let mut inner = FunctionContext {
+            arg_idx: 0,
+            resolved_args: Some(args),
+            ..ftx.clone()
+        };
+        let result = handler(&mut inner);
+        ftx.charged.set(inner.charged.get());
+        result
+    }
+}
+
+/// Whether the values of the arguments, including the target of member calls, are of the kinds
+/// of the arguments of `overload`.
+fn accepts(overload: &OverloadDecl, values: &[Value]) -> bool {
+    overload.args.len() == values.len()
+        && overload
+            .args
+            .iter()
+            .zip(values)
+            .all(|(ty, value)| has_type(value, ty))
+}
+
+fn has_type(value: &Value, ty: &Type) -> bool {
+    match (ty.kind(), value) {
+        (Kind::Dyn | Kind::Any | Kind::TypeParam | Kind::Error, _) => true,
+        (Kind::Boolean, Value::Bool(_))
+        | (Kind::Int, Value::Int(_))
+        | (Kind::UInt, Value::UInt(_))
+        | (Kind::Double, Value::Float(_))
+        | (Kind::String, Value::String(_))
+        | (Kind::Bytes, Value::Bytes(_))
+        | (Kind::NullType, Value::Null)
+        | (Kind::List, Value::List(_))
+        | (Kind::Map, Value::Map(_)) => true,
+        #[cfg(feature = "chrono")]
+        (Kind::Duration, Value::Duration(_)) | (Kind::Timestamp, Value::Timestamp(_)) => true,
+        (Kind::Struct, Value::Struct(s)) => s.type_name() == ty.name(),
+        (Kind::Opaque, Value::Optional(_)) => ty.name() == "optional_type",
+        (Kind::Opaque, Value::Opaque(opaque)) => opaque.runtime_type_name() == ty.name(),
+        _ => false,
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CompileError {
+    #[error("{0}")]
+    Parse(#[from] ParseErrors),
+    #[error("{0}")]
+    Check(#[from] CheckErrors),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::OverloadDecl;
+    use crate::common::types::{DYN_TYPE, INT_TYPE, STRING_TYPE};
+    use crate::extractors::This;
+    use crate::structs::{StructRegistry, StructType};
+    use crate::Value;
+
+    #[test]
+    fn variables_are_checked() {
+        let env = Env::default()
+            .variable("x", INT_TYPE)
+            .variable("labels", Type::new_map(&STRING_TYPE, &STRING_TYPE));
+
+        assert!(env.compile("x + 1 > 2").is_ok());
+        assert!(env.compile("labels.app == 'web'").is_ok());
+        assert!(env.compile("size(labels) + x").is_ok());
+        assert!(matches!(env.compile("y + 1"), Err(CompileError::Check(_))));
+        assert!(matches!(
+            env.compile("labels.app + x"),
+            Err(CompileError::Check(_))
+        ));
+        assert!(matches!(env.compile("x +"), Err(CompileError::Parse(_))));
+    }
+
+    #[test]
+    fn functions_are_checked_and_registered() {
+        let env = Env::default().variable("x", INT_TYPE).function(
+            FunctionDecl::new("twice").overload(OverloadDecl::global(
+                "twice_int",
+                &[INT_TYPE],
+                INT_TYPE,
+            )),
+            |x: i64| x * 2,
+        );
+
+        let program = env.compile("twice(x) == 4").unwrap();
+        let mut context = env.context();
+        context.add_variable_from_value("x", 2);
+        assert_eq!(program.execute(&context), Ok(Value::Bool(true)));
+
+        assert!(env.compile("twice(x) == 'a'").is_err());
+        assert!(env.compile("twice('a')").is_err());
+    }
+
+    #[test]
+    fn struct_types() {
+        let mut types = StructRegistry::default();
+        types.add(StructType::new("Account").field("user_id", INT_TYPE));
+        let env = Env::default().type_provider(types);
+
+        let program = env.compile("Account{user_id: 123}.user_id == 123").unwrap();
+        assert_eq!(program.execute(&env.context()), Ok(Value::Bool(true)));
+
+        assert!(env.compile("Account{user_id: 'a'}").is_err());
+        assert!(env.compile("Account{id: 1}").is_err());
+        assert!(env.compile("Account{}.id").is_err());
+        assert!(env.compile("Unknown{}").is_err());
+    }
+
+    #[test]
+    fn declared_only_functions() {
+        let env = Env::empty().declare_function(
+            FunctionDecl::new("now").overload(OverloadDecl::global("now", &[], DYN_TYPE)),
+        );
+        assert!(env.compile("now()").is_ok());
+        // The empty environment doesn't even know about the standard operators.
+        assert!(env.compile("1 + 1").is_err());
+
+        let program = env.compile("now()").unwrap();
+        let mut context = env.context();
+        context.add_function("now", || 42i64);
+        assert_eq!(program.execute(&context), Ok(Value::Int(42)));
+    }
+
+    #[test]
+    fn macros_are_expanded_before_checking() {
+        use crate::common::ast::{CallExpr, Expr, IdedExpr};
+        use crate::common::value::CelVal;
+        use crate::parser::{MacroExprHelper, ParseError};
+
+        // `x.isEmpty()` -> `size(x) == 0`
+        fn is_empty(
+            helper: &mut MacroExprHelper,
+            target: Option<IdedExpr>,
+            _args: Vec<IdedExpr>,
+        ) -> Result<IdedExpr, ParseError> {
+            let size = helper.next_expr(Expr::Call(CallExpr {
+                func_name: "size".to_string(),
+                target: None,
+                args: vec![target.unwrap()],
+            }));
+            let zero = helper.next_expr(Expr::Literal(CelVal::Int(0)));
+            Ok(helper.next_expr(Expr::Call(CallExpr {
+                func_name: crate::common::ast::operators::EQUALS.to_string(),
+                target: None,
+                args: vec![size, zero],
+            })))
+        }
+
+        let env = Env::default()
+            .variable("labels", Type::new_map(&STRING_TYPE, &STRING_TYPE))
+            .add_macro(Macro::receiver("isEmpty", 0, is_empty));
+        let program = env.compile("labels.isEmpty()").unwrap();
+
+        let mut context = env.context();
+        context
+            .add_variable("labels", std::collections::HashMap::<String, String>::new())
+            .unwrap();
+        assert_eq!(program.execute(&context), Ok(true.into()));
+        assert!(matches!(
+            env.compile("labels.isEmpty(1)"),
+            Err(CompileError::Check(_))
+        ));
+    }
+
+    #[test]
+    fn overloads_are_dispatched() {
+        let env = Env::default()
+            .variable("n", DYN_TYPE)
+            .variable("f", DYN_TYPE)
+            .function(
+                FunctionDecl::new("describe").overload(OverloadDecl::member(
+                    "int_describe",
+                    &[INT_TYPE],
+                    STRING_TYPE,
+                )),
+                |This(x): This<i64>| format!("int {x}"),
+            )
+            .function(
+                FunctionDecl::new("describe").overload(OverloadDecl::member(
+                    "string_describe",
+                    &[STRING_TYPE],
+                    STRING_TYPE,
+                )),
+                |This(s): This<Arc<String>>| format!("string {s}"),
+            )
+            // Falls back to the standard implementation for the other overloads.
+            .function(
+                FunctionDecl::new("size").overload(OverloadDecl::global(
+                    "size_int",
+                    &[INT_TYPE],
+                    INT_TYPE,
+                )),
+                |x: i64| x.abs(),
+            );
+
+        let mut context = env.context();
+        context.add_variable_from_value("n", 1);
+        context.add_variable_from_value("f", 1.0);
+        for (expr, expected) in [
+            ("1.describe()", Value::from("int 1")),
+            ("'a'.describe()", "string a".into()),
+            ("n.describe()", "int 1".into()),
+            ("size(-3)", 3.into()),
+            ("size('abc')", 3.into()),
+            ("size([1, 2])", 2.into()),
+        ] {
+            let program = env.compile(expr).unwrap();
+            assert_eq!(program.execute(&context), Ok(expected), "{expr}");
+        }
+        let program = env.compile("f.describe()").unwrap();
+        assert_eq!(
+            program.execute(&context),
+            Err(ExecutionError::NoSuchOverload)
+        );
+    }
+
+    #[test]
+    fn libraries() {
+        use crate::extensions::{lists::Lists, math::Math, sets::Sets, strings::Strings};
+
+        let env = Env::default()
+            .variable("names", Type::new_list(&STRING_TYPE))
+            .library(Strings)
+            .library(Math)
+            .library(Lists)
+            .library(Sets);
+        let mut context = env.context();
+        context
+            .add_variable("names", vec!["bob", "al", "carol"])
+            .unwrap();
+        for expr in [
+            "[1, 2].sort() == [1, 2]",
+            "names.sortBy(n, size(n)) == ['al', 'bob', 'carol']",
+            "names.sortBy(n, -size(n)).first() == optional.of('carol')",
+            "names.join(',').split(',') == names",
+            "'abc'.reverse() == 'cba' && [1, 2].reverse() == [2, 1]",
+            "math.greatest(1, 2.5) == 2.5 && math.abs(-1) == 1",
+            "sets.contains(names, ['al'])",
+        ] {
+            let program = env.compile(expr).unwrap();
+            assert_eq!(program.execute(&context), Ok(true.into()), "{expr}");
+        }
+        for expr in [
+            "names.sortBy(n, n.foo())",
+            "names.join(1)",
+            "math.abs('a')",
+            "sets.contains(names, [1])",
+        ] {
+            assert!(env.compile(expr).is_err(), "{expr}");
+        }
+        assert!(Env::default().compile("[1, 2].sort()").is_err());
+        assert!(Env::default()
+            .library(Lists)
+            .compile("['a', 'b'].join('-')")
+            .is_ok());
+    }
+}
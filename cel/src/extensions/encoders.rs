@@ -0,0 +1,87 @@
+//! The encoders extension, which adds functions in the `base64` namespace to encode bytes to
+//! strings and back.
+//!
+//! The functions are registered by [`Context::default`](crate::Context::default) when the
+//! `encoders` feature is enabled.
+//!
+//! # Example
+//! ```cel
+//! base64.encode(b'hello') == 'aGVsbG8='
+//! base64.decode('aGVsbG8') == b'hello'
+//! base64.urlEncode(b'\xfb\xff') == '-_8='
+//! ```
+
+use crate::{ExecutionError, FunctionContext};
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, Engine, GeneralPurpose, GeneralPurposeConfig};
+use std::sync::Arc;
+
+type Result<T> = std::result::Result<T, ExecutionError>;
+
+// Padding is written when encoding, but optional when decoding.
+const CONFIG: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const STANDARD: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, CONFIG);
+const URL_SAFE: GeneralPurpose = GeneralPurpose::new(&alphabet::URL_SAFE, CONFIG);
+
+/// `base64.encode(bytes)` encodes bytes with the standard base64 alphabet.
+pub fn encode(bytes: Arc<Vec<u8>>) -> String {
+    STANDARD.encode(bytes.as_slice())
+}
+
+/// `base64.decode(string)` decodes a string encoded with the standard base64 alphabet, with or
+/// without padding.
+pub fn decode(ftx: &FunctionContext, encoded: Arc<String>) -> Result<Arc<Vec<u8>>> {
+    STANDARD
+        .decode(encoded.as_str())
+        .map(Arc::new)
+        .map_err(|err| ftx.error(err))
+}
+
+/// `base64.urlEncode(bytes)` encodes bytes with the URL and filename safe base64 alphabet,
+/// which has `-` and `_` instead of `+` and `/`.
+pub fn url_encode(bytes: Arc<Vec<u8>>) -> String {
+    URL_SAFE.encode(bytes.as_slice())
+}
+
+/// `base64.urlDecode(string)` decodes a string encoded with the URL and filename safe base64
+/// alphabet, with or without padding.
+pub fn url_decode(ftx: &FunctionContext, encoded: Arc<String>) -> Result<Arc<Vec<u8>>> {
+    URL_SAFE
+        .decode(encoded.as_str())
+        .map(Arc::new)
+        .map_err(|err| ftx.error(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::test_script;
+    use crate::{ExecutionError, Value};
+
+    #[test]
+    fn encoders() {
+        for expr in [
+            "base64.encode(b'hello') == 'aGVsbG8='",
+            "base64.encode(b'') == ''",
+            "base64.decode('aGVsbG8=') == b'hello'",
+            "base64.decode('aGVsbG8') == b'hello'",
+            "base64.encode(b'\\xfb\\xff') == '+/8='",
+            "base64.urlEncode(b'\\xfb\\xff') == '-_8='",
+            "base64.urlDecode('-_8') == b'\\xfb\\xff'",
+            "base64.decode(base64.encode(b'\\x00\\x01\\x02')) == b'\\x00\\x01\\x02'",
+        ] {
+            assert_eq!(test_script(expr, None), Ok(Value::Bool(true)), "{expr}");
+        }
+    }
+
+    #[test]
+    fn invalid_encoding() {
+        assert_eq!(
+            test_script("base64.decode('-_8=')", None),
+            Err(ExecutionError::function_error(
+                "decode",
+                "Invalid symbol 45, offset 0."
+            ))
+        );
+    }
+}
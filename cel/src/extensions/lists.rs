@@ -0,0 +1,358 @@
+//! The lists extension, which adds functions to build, reorder and extract elements from
+//! lists.
+//!
+//! # Example
+//! ```cel
+//! lists.range(3) == [0, 1, 2]
+//! [1, [2, [3]]].flatten() == [1, 2, [3]]
+//! [3, 1, 2].sort().slice(0, 2) == [1, 2]
+//! ['b', 'aa'].sortBy(s, size(s)) == ['b', 'aa']
+//! [1, 2, 1].distinct().reverse() == [2, 1]
+//! ['a', 'b'].join('-') == 'a-b'
+//! [].first() == optional.none()
+//! ```
+
+use super::method_args;
+use super::sets::ValueSet;
+use crate::checker::{FunctionDecl, OverloadDecl};
+use crate::common::types::{Type, DYN_TYPE, INT_TYPE, STRING_TYPE};
+use crate::magic::{Identifier, This};
+use crate::parser::{sort_by_macro_expander, Expression, Macro, SORT_BY_ASSOCIATED_KEYS};
+use crate::{Context, ExecutionError, FunctionContext, Library, Value};
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+type Result<T> = std::result::Result<T, ExecutionError>;
+
+const A: Type = Type::new_type_param("A");
+const B: Type = Type::new_type_param("B");
+const LIST_A: Type = Type::new_list_type(&[&A]);
+const LIST_B: Type = Type::new_list_type(&[&B]);
+const LIST_DYN: Type = Type::new_list_type(&[&DYN_TYPE]);
+const LIST_STRING: Type = Type::new_list_type(&[&STRING_TYPE]);
+
+/// The lists extension as a [`Library`], to add it to an [`Env`](crate::Env).
+///
+/// `sortBy` is expanded by a macro, so that its key expression is type checked, into a call of
+/// the internal `@sortByAssociatedKeys` function.
+pub struct Lists;
+
+impl Library for Lists {
+    fn declarations(&self) -> Vec<FunctionDecl> {
+        let member = OverloadDecl::member::<&str>;
+        let optional_a = Type::new_optional(&A);
+        vec![
+            FunctionDecl::new("lists.range").overload(OverloadDecl::global(
+                "lists_range",
+                &[INT_TYPE],
+                Type::new_list(&INT_TYPE),
+            )),
+            FunctionDecl::new("slice").overload(member(
+                "list_slice",
+                &[LIST_A, INT_TYPE, INT_TYPE],
+                LIST_A,
+            )),
+            FunctionDecl::new("flatten")
+                .overload(member("list_flatten", &[LIST_DYN], LIST_DYN))
+                .overload(member("list_flatten_int", &[LIST_DYN, INT_TYPE], LIST_DYN)),
+            FunctionDecl::new("sort").overload(member("list_sort", &[LIST_A], LIST_A)),
+            FunctionDecl::new(SORT_BY_ASSOCIATED_KEYS).overload(member(
+                "list_sort_by_associated_keys",
+                &[LIST_A, LIST_B],
+                LIST_A,
+            )),
+            FunctionDecl::new("distinct").overload(member("list_distinct", &[LIST_A], LIST_A)),
+            FunctionDecl::new("reverse").overload(member("list_reverse", &[LIST_A], LIST_A)),
+            FunctionDecl::new("join")
+                .overload(member("list_join", &[LIST_STRING], STRING_TYPE))
+                .overload(member(
+                    "list_join_string",
+                    &[LIST_STRING, STRING_TYPE],
+                    STRING_TYPE,
+                )),
+            FunctionDecl::new("first").overload(member(
+                "list_first",
+                &[LIST_A],
+                optional_a.clone(),
+            )),
+            FunctionDecl::new("last").overload(member("list_last", &[LIST_A], optional_a)),
+        ]
+    }
+
+    fn add_functions(&self, context: &mut Context) {
+        context.add_lists_extension();
+    }
+
+    fn macros(&self) -> Vec<Macro> {
+        vec![Macro::receiver("sortBy", 2, sort_by_macro_expander)]
+    }
+}
+
+/// `lists.range(n)` returns the list of the ints from 0 to `n`, excluded. Its cost is charged
+/// before building the list, so that a [cost limit](crate::cost::CostTracker) bounds `n`, and
+/// it is an error, rather than an abort, if the list can't be allocated.
+pub fn range(ftx: &FunctionContext, n: i64) -> Result<Value> {
+    let n = n.max(0);
+    ftx.charge_size(n as usize)?;
+    let mut list = Vec::new();
+    list.try_reserve_exact(n as usize)
+        .map_err(|_| ftx.error(format!("cannot allocate a list of {n} ints")))?;
+    list.extend((0..n).map(Value::Int));
+    Ok(Value::List(Arc::new(list)))
+}
+
+/// `list.slice(start, end)` returns the elements of `list` from index `start` to `end`,
+/// excluded.
+pub fn slice(
+    ftx: &FunctionContext,
+    This(this): This<Arc<Vec<Value>>>,
+    start: i64,
+    end: i64,
+) -> Result<Value> {
+    if start < 0 || end < 0 {
+        return Err(ftx.error(format!(
+            "negative indexes not supported: slice({start}, {end})"
+        )));
+    }
+    if start > end {
+        return Err(ftx.error(format!(
+            "start index must be less than or equal to end index: slice({start}, {end})"
+        )));
+    }
+    if end as usize > this.len() {
+        return Err(ftx.error(format!(
+            "index out of bounds: slice({start}, {end}) of a list of size {}",
+            this.len()
+        )));
+    }
+    if start == 0 && end as usize == this.len() {
+        return Ok(Value::List(this));
+    }
+    Ok(Value::List(Arc::new(
+        this[start as usize..end as usize].to_vec(),
+    )))
+}
+
+/// `list.flatten()` replaces the lists nested in `list` with their elements, and
+/// `list.flatten(depth)` does it recursively for lists nested up to `depth` levels.
+pub fn flatten(ftx: &FunctionContext, This(this): This<Arc<Vec<Value>>>) -> Result<Value> {
+    let depth = match method_args(ftx)?.as_slice() {
+        [] => 1,
+        [Value::Int(depth)] if *depth >= 0 => *depth,
+        [Value::Int(depth)] => {
+            return Err(ftx.error(format!("depth must be non-negative, got {depth}")))
+        }
+        _ => return Err(ExecutionError::NoSuchOverload),
+    };
+    if depth == 0 || !this.iter().any(|v| matches!(v, Value::List(_))) {
+        return Ok(Value::List(this));
+    }
+    let mut flattened = Vec::with_capacity(this.len());
+    flatten_into(&this, depth, &mut flattened);
+    Ok(Value::List(Arc::new(flattened)))
+}
+
+fn flatten_into(list: &[Value], depth: i64, flattened: &mut Vec<Value>) {
+    for value in list {
+        match value {
+            Value::List(list) if depth > 0 => flatten_into(list, depth - 1, flattened),
+            value => flattened.push(value.clone()),
+        }
+    }
+}
+
+/// `list.sort()` sorts a list of comparable values, e.g. numbers or strings, in ascending
+/// order.
+pub fn sort(This(this): This<Arc<Vec<Value>>>) -> Result<Value> {
+    let mut sorted = this.to_vec();
+    try_sort_by(&mut sorted, compare)?;
+    Ok(Value::List(Arc::new(sorted)))
+}
+
+/// `list.sortBy(x, key)` sorts the elements `x` of the list by the value of the expression
+/// `key`, e.g. `users.sortBy(u, u.age)`. The sort is stable.
+pub fn sort_by(
+    ftx: &FunctionContext,
+    This(this): This<Arc<Vec<Value>>>,
+    ident: Identifier,
+    key: Expression,
+) -> Result<Value> {
+    let mut ptx = ftx.ptx.new_inner_scope();
+    let mut keys = Vec::with_capacity(this.len());
+    for value in this.iter() {
+        ptx.add_variable_from_value(&ident, value.clone());
+        keys.push(ptx.resolve(&key)?);
+    }
+    sort_by_keys(&this, keys)
+}
+
+/// `list.@sortByAssociatedKeys(keys)` sorts `list` by the key at the same index in `keys`,
+/// which is what `sortBy` expands to when parsed with the macros of [`Lists`].
+pub(crate) fn sort_by_associated_keys(
+    ftx: &FunctionContext,
+    This(this): This<Arc<Vec<Value>>>,
+    keys: Arc<Vec<Value>>,
+) -> Result<Value> {
+    if keys.len() != this.len() {
+        return Err(ftx.error(format!("expected {} keys, got {}", this.len(), keys.len())));
+    }
+    sort_by_keys(&this, keys.to_vec())
+}
+
+fn sort_by_keys(list: &[Value], keys: Vec<Value>) -> Result<Value> {
+    let mut keyed: Vec<_> = keys.into_iter().zip(list).collect();
+    try_sort_by(&mut keyed, |(a, _), (b, _)| compare(a, b))?;
+    Ok(Value::List(Arc::new(
+        keyed.into_iter().map(|(_, value)| value.clone()).collect(),
+    )))
+}
+
+/// Sorts `values` with a fallible comparison, returning the first error.
+fn try_sort_by<T>(values: &mut [T], compare: impl Fn(&T, &T) -> Result<Ordering>) -> Result<()> {
+    let mut error = None;
+    values.sort_by(|a, b| {
+        compare(a, b).unwrap_or_else(|err| {
+            error.get_or_insert(err);
+            Ordering::Equal
+        })
+    });
+    error.map_or(Ok(()), Err)
+}
+
+fn compare(a: &Value, b: &Value) -> Result<Ordering> {
+    a.partial_cmp(b)
+        .ok_or_else(|| ExecutionError::ValuesNotComparable(a.clone(), b.clone()))
+}
+
+/// `list.distinct()` removes the elements which are equal to a previous one, e.g. the `1.0` in
+/// `[1, 1.0]`.
+pub fn distinct(This(this): This<Arc<Vec<Value>>>) -> Result<Value> {
+    let mut seen = ValueSet::with_capacity(this.len());
+    let distinct: Vec<Value> = this
+        .iter()
+        .filter(|value| seen.insert(value))
+        .cloned()
+        .collect();
+    if distinct.len() == this.len() {
+        return Ok(Value::List(this));
+    }
+    Ok(Value::List(Arc::new(distinct)))
+}
+
+/// `list.reverse()` reverses the elements of a list. The strings extension adds
+/// [`reverse`](super::strings::reverse) for strings.
+pub fn reverse(This(this): This<Arc<Vec<Value>>>) -> Result<Value> {
+    Ok(Value::List(Arc::new(this.iter().rev().cloned().collect())))
+}
+
+/// `list.first()` returns the first element of the list as an optional, which has no value if
+/// the list is empty.
+pub fn first(This(this): This<Arc<Vec<Value>>>) -> Result<Value> {
+    Ok(Value::Optional(this.first().cloned().map(Box::new)))
+}
+
+/// `list.last()` returns the last element of the list as an optional, which has no value if
+/// the list is empty.
+pub fn last(This(this): This<Arc<Vec<Value>>>) -> Result<Value> {
+    Ok(Value::Optional(this.last().cloned().map(Box::new)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::Context;
+    use crate::{ExecutionError, Program, Value};
+
+    fn execute(expr: &str) -> std::result::Result<Value, ExecutionError> {
+        let mut context = Context::default();
+        context.add_lists_extension();
+        Program::compile(expr).unwrap().execute(&context)
+    }
+
+    #[test]
+    fn lists() {
+        for expr in [
+            "lists.range(3) == [0, 1, 2]",
+            "lists.range(0) == []",
+            "[1, 2, 3, 4].slice(1, 3) == [2, 3]",
+            "[1, 2].slice(0, 2) == [1, 2]",
+            "[1, 2].slice(2, 2) == []",
+            "[1, [2, [3, [4]]]].flatten() == [1, 2, [3, [4]]]",
+            "[1, [2, [3, [4]]]].flatten(2) == [1, 2, 3, [4]]",
+            "[1, [2, [3, [4]]]].flatten(0) == [1, [2, [3, [4]]]]",
+            "[[], [[]]].flatten(10) == []",
+            "[3, 1, 2].sort() == [1, 2, 3]",
+            "['b', 'c', 'a'].sort() == ['a', 'b', 'c']",
+            "[2, 1.5, 1u].sort() == [1u, 1.5, 2]",
+            "['bb', 'a', 'ccc', 'd'].sortBy(s, size(s)) == ['a', 'd', 'bb', 'ccc']",
+            "[{'n': 2}, {'n': 1}].sortBy(m, -m.n) == [{'n': 2}, {'n': 1}]",
+            "[1, 2, 1, 1.0, 3u, 3].distinct() == [1, 2, 3u]",
+            "[1, 2, 3].reverse() == [3, 2, 1]",
+            "['a', 'b'].join('-') == 'a-b'",
+            "[1, 1.0, 1u, 2, 2.0, 'a', 'a', [1], [1.0], null, null].distinct() == [1, 2, 'a', [1], null]",
+            "[1, 2].first() == optional.of(1)",
+            "[1, 2].last() == optional.of(2)",
+            "[].first() == optional.none()",
+            "[].last().orValue(0) == 0",
+        ] {
+            assert_eq!(execute(expr), Ok(Value::Bool(true)), "{expr}");
+        }
+    }
+
+    #[test]
+    fn reverse_with_strings_extension() {
+        let mut strings_first = Context::default();
+        strings_first.add_strings_extension();
+        strings_first.add_lists_extension();
+        let mut lists_first = Context::default();
+        lists_first.add_lists_extension();
+        lists_first.add_strings_extension();
+        for context in [strings_first, lists_first] {
+            let program = Program::compile("[1, 2].reverse() == [2, 1] && 'ab'.reverse() == 'ba'");
+            assert_eq!(program.unwrap().execute(&context), Ok(Value::Bool(true)));
+        }
+        assert_eq!(
+            execute("'ab'.reverse()"),
+            Err(ExecutionError::NoSuchOverload)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        for (expr, expected) in [
+            (
+                "[1, 2].slice(1, 3)",
+                ExecutionError::function_error(
+                    "slice",
+                    "index out of bounds: slice(1, 3) of a list of size 2",
+                ),
+            ),
+            (
+                "[1, 2].slice(2, 1)",
+                ExecutionError::function_error(
+                    "slice",
+                    "start index must be less than or equal to end index: slice(2, 1)",
+                ),
+            ),
+            (
+                "lists.range(9223372036854775807)",
+                ExecutionError::function_error(
+                    "range",
+                    "cannot allocate a list of 9223372036854775807 ints",
+                ),
+            ),
+            (
+                "[1].flatten(-1)",
+                ExecutionError::function_error("flatten", "depth must be non-negative, got -1"),
+            ),
+            (
+                "[1, 'a'].sort()",
+                ExecutionError::ValuesNotComparable(
+                    Value::String("a".to_string().into()),
+                    1.into(),
+                ),
+            ),
+        ] {
+            assert_eq!(execute(expr), Err(expected), "{expr}");
+        }
+    }
+}
@@ -0,0 +1,378 @@
+//! The math extension, which adds functions over numbers in the `math` namespace.
+//!
+//! # Example
+//! ```cel
+//! math.greatest(1, 2.5, 3u) == 3u
+//! math.least([-1, 0]) == -1
+//! math.round(1.5) == 2.0
+//! math.bitShiftLeft(1, 4) == 16
+//! ```
+
+use crate::checker::{FunctionDecl, OverloadDecl};
+use crate::common::types::{Type, BOOL_TYPE, DOUBLE_TYPE, DYN_TYPE, INT_TYPE, UINT_TYPE};
+use crate::magic::Arguments;
+use crate::{Context, ExecutionError, FunctionContext, Library, Value};
+use std::cmp::Ordering;
+
+type Result<T> = std::result::Result<T, ExecutionError>;
+
+/// The highest number of arguments declared for `math.greatest` and `math.least`, beyond which
+/// the numbers must be passed as a list.
+const EXTREMUM_ARITY: usize = 8;
+
+/// The math extension as a [`Library`], to add it to an [`Env`](crate::Env).
+pub struct Math;
+
+impl Library for Math {
+    fn declarations(&self) -> Vec<FunctionDecl> {
+        let global = OverloadDecl::global::<String>;
+        let numbers = [
+            ("int", INT_TYPE),
+            ("uint", UINT_TYPE),
+            ("double", DOUBLE_TYPE),
+        ];
+        let unary = |name: &str,
+                     id: &str,
+                     types: &[(&str, Type<'static>)],
+                     result: Option<Type<'static>>| {
+            types
+                .iter()
+                .fold(FunctionDecl::new(name), |decl, (suffix, ty)| {
+                    decl.overload(global(
+                        format!("math_{id}_{suffix}"),
+                        std::slice::from_ref(ty),
+                        result.clone().unwrap_or_else(|| ty.clone()),
+                    ))
+                })
+        };
+        let double = [("double", DOUBLE_TYPE)];
+        let ints = [("int", INT_TYPE), ("uint", UINT_TYPE)];
+
+        let mut decls = Vec::new();
+        for (name, id) in [("math.greatest", "greatest"), ("math.least", "least")] {
+            // Numbers of different types can be compared, so that calls with several arguments
+            // are only typed as dyn.
+            let mut decl = unary(name, id, &numbers, None).overload(global(
+                format!("math_{id}_list_dyn"),
+                &[Type::new_list(&DYN_TYPE)],
+                DYN_TYPE,
+            ));
+            for arity in 2..=EXTREMUM_ARITY {
+                decl = decl.overload(global(
+                    format!("math_{id}_{arity}"),
+                    &vec![DYN_TYPE; arity],
+                    DYN_TYPE,
+                ));
+            }
+            decls.push(decl);
+        }
+        for (name, id) in [
+            ("math.ceil", "ceil"),
+            ("math.floor", "floor"),
+            ("math.round", "round"),
+            ("math.trunc", "trunc"),
+        ] {
+            decls.push(unary(name, id, &double, None));
+        }
+        decls.push(unary("math.abs", "abs", &numbers, None));
+        decls.push(unary("math.sign", "sign", &numbers, None));
+        for (name, id) in [
+            ("math.isNaN", "is_nan"),
+            ("math.isInf", "is_inf"),
+            ("math.isFinite", "is_finite"),
+        ] {
+            decls.push(unary(name, id, &double, Some(BOOL_TYPE)));
+        }
+        for (name, id) in [
+            ("math.bitAnd", "bit_and"),
+            ("math.bitOr", "bit_or"),
+            ("math.bitXor", "bit_xor"),
+        ] {
+            decls.push(
+                ints.iter()
+                    .fold(FunctionDecl::new(name), |decl, (suffix, ty)| {
+                        decl.overload(global(
+                            format!("math_{id}_{suffix}_{suffix}"),
+                            &[ty.clone(), ty.clone()],
+                            ty.clone(),
+                        ))
+                    }),
+            );
+        }
+        decls.push(unary("math.bitNot", "bit_not", &ints, None));
+        for (name, id) in [
+            ("math.bitShiftLeft", "bit_shift_left"),
+            ("math.bitShiftRight", "bit_shift_right"),
+        ] {
+            decls.push(
+                ints.iter()
+                    .fold(FunctionDecl::new(name), |decl, (suffix, ty)| {
+                        decl.overload(global(
+                            format!("math_{id}_{suffix}_int"),
+                            &[ty.clone(), INT_TYPE],
+                            ty.clone(),
+                        ))
+                    }),
+            );
+        }
+        decls
+    }
+
+    fn add_functions(&self, context: &mut Context) {
+        context.add_math_extension();
+    }
+}
+
+/// `math.greatest(a, b, ...)` and `math.greatest([a, b, ...])` return the greatest of
+/// numbers of any type, e.g. `math.greatest(1, 2.5) == 2.5`.
+pub fn greatest(ftx: &FunctionContext, Arguments(args): Arguments) -> Result<Value> {
+    extremum(ftx, &args, Ordering::Greater)
+}
+
+/// `math.least(a, b, ...)` and `math.least([a, b, ...])` return the least of numbers of any
+/// type, e.g. `math.least(1, 2.5) == 1`.
+pub fn least(ftx: &FunctionContext, Arguments(args): Arguments) -> Result<Value> {
+    extremum(ftx, &args, Ordering::Less)
+}
+
+fn extremum(ftx: &FunctionContext, args: &[Value], wanted: Ordering) -> Result<Value> {
+    let values = match args {
+        [Value::List(values)] => values.as_slice(),
+        args => args,
+    };
+    let mut extremum: Option<&Value> = None;
+    for value in values {
+        if !matches!(value, Value::Int(_) | Value::UInt(_) | Value::Float(_)) {
+            return Err(ftx.error(format!("expected a number, got {value:?}")));
+        }
+        extremum = match extremum {
+            None => Some(value),
+            Some(current) => match value.partial_cmp(current) {
+                Some(ordering) if ordering == wanted => Some(value),
+                Some(_) => Some(current),
+                None => {
+                    return Err(ExecutionError::ValuesNotComparable(
+                        current.clone(),
+                        value.clone(),
+                    ))
+                }
+            },
+        };
+    }
+    extremum
+        .cloned()
+        .ok_or_else(|| ftx.error("expected at least one number"))
+}
+
+/// `math.ceil(x)` rounds `x` up to the closest integral double.
+pub fn ceil(x: f64) -> f64 {
+    x.ceil()
+}
+
+/// `math.floor(x)` rounds `x` down to the closest integral double.
+pub fn floor(x: f64) -> f64 {
+    x.floor()
+}
+
+/// `math.round(x)` rounds `x` to the closest integral double, and half-way cases away from
+/// zero.
+pub fn round(x: f64) -> f64 {
+    x.round()
+}
+
+/// `math.trunc(x)` rounds `x` towards zero to an integral double.
+pub fn trunc(x: f64) -> f64 {
+    x.trunc()
+}
+
+/// `math.abs(x)` returns the absolute value of an int, uint or double, and fails for the
+/// smallest int which has no positive counterpart.
+pub fn abs(ftx: &FunctionContext, x: Value) -> Result<Value> {
+    match x {
+        Value::Int(i) => i
+            .checked_abs()
+            .map(Value::Int)
+            .ok_or_else(|| ftx.error("integer overflow")),
+        Value::UInt(u) => Ok(Value::UInt(u)),
+        Value::Float(f) => Ok(Value::Float(f.abs())),
+        _ => Err(ExecutionError::NoSuchOverload),
+    }
+}
+
+/// `math.sign(x)` returns -1, 0 or 1, of the type of `x`, depending on the sign of `x`, or NaN
+/// if `x` is NaN.
+pub fn sign(x: Value) -> Result<Value> {
+    match x {
+        Value::Int(i) => Ok(Value::Int(i.signum())),
+        Value::UInt(u) => Ok(Value::UInt(u.min(1))),
+        Value::Float(f) if f == 0.0 || f.is_nan() => Ok(Value::Float(f)),
+        Value::Float(f) => Ok(Value::Float(f.signum())),
+        _ => Err(ExecutionError::NoSuchOverload),
+    }
+}
+
+/// `math.isNaN(x)`
+pub fn is_nan(x: f64) -> bool {
+    x.is_nan()
+}
+
+/// `math.isInf(x)` is true if `x` is positive or negative infinity.
+pub fn is_inf(x: f64) -> bool {
+    x.is_infinite()
+}
+
+/// `math.isFinite(x)` is true if `x` is neither infinite nor NaN.
+pub fn is_finite(x: f64) -> bool {
+    x.is_finite()
+}
+
+/// `math.bitAnd(a, b)` of two ints or two uints.
+pub fn bit_and(a: Value, b: Value) -> Result<Value> {
+    bitwise(a, b, |a, b| a & b, |a, b| a & b)
+}
+
+/// `math.bitOr(a, b)` of two ints or two uints.
+pub fn bit_or(a: Value, b: Value) -> Result<Value> {
+    bitwise(a, b, |a, b| a | b, |a, b| a | b)
+}
+
+/// `math.bitXor(a, b)` of two ints or two uints.
+pub fn bit_xor(a: Value, b: Value) -> Result<Value> {
+    bitwise(a, b, |a, b| a ^ b, |a, b| a ^ b)
+}
+
+fn bitwise(
+    a: Value,
+    b: Value,
+    int_op: fn(i64, i64) -> i64,
+    uint_op: fn(u64, u64) -> u64,
+) -> Result<Value> {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => Ok(Value::Int(int_op(a, b))),
+        (Value::UInt(a), Value::UInt(b)) => Ok(Value::UInt(uint_op(a, b))),
+        _ => Err(ExecutionError::NoSuchOverload),
+    }
+}
+
+/// `math.bitNot(a)` flips the bits of an int or a uint.
+pub fn bit_not(a: Value) -> Result<Value> {
+    match a {
+        Value::Int(a) => Ok(Value::Int(!a)),
+        Value::UInt(a) => Ok(Value::UInt(!a)),
+        _ => Err(ExecutionError::NoSuchOverload),
+    }
+}
+
+/// `math.bitShiftLeft(a, n)` shifts the bits of an int or a uint `n` places to the left,
+/// which is 0 if `n` is 64 or more.
+pub fn bit_shift_left(ftx: &FunctionContext, a: Value, n: i64) -> Result<Value> {
+    let n = shift_offset(ftx, n)?;
+    match a {
+        Value::Int(a) => Ok(Value::Int(a.checked_shl(n).unwrap_or(0))),
+        Value::UInt(a) => Ok(Value::UInt(a.checked_shl(n).unwrap_or(0))),
+        _ => Err(ExecutionError::NoSuchOverload),
+    }
+}
+
+/// `math.bitShiftRight(a, n)` shifts the bits of an int or a uint `n` places to the right,
+/// which is 0 if `n` is 64 or more. Ints are shifted as unsigned: the sign bit isn't
+/// extended.
+pub fn bit_shift_right(ftx: &FunctionContext, a: Value, n: i64) -> Result<Value> {
+    let n = shift_offset(ftx, n)?;
+    match a {
+        Value::Int(a) => Ok(Value::Int((a as u64).checked_shr(n).unwrap_or(0) as i64)),
+        Value::UInt(a) => Ok(Value::UInt(a.checked_shr(n).unwrap_or(0))),
+        _ => Err(ExecutionError::NoSuchOverload),
+    }
+}
+
+fn shift_offset(ftx: &FunctionContext, n: i64) -> Result<u32> {
+    if n < 0 {
+        return Err(ftx.error(format!("negative offset: {n}")));
+    }
+    Ok(u32::try_from(n).unwrap_or(u32::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::Context;
+    use crate::{ExecutionError, Program, Value};
+
+    fn execute(expr: &str) -> std::result::Result<Value, ExecutionError> {
+        let mut context = Context::default();
+        context.add_math_extension();
+        Program::compile(expr).unwrap().execute(&context)
+    }
+
+    #[test]
+    fn math() {
+        for expr in [
+            "math.greatest(1) == 1",
+            "math.greatest(1, 2.5, 3u) == 3u",
+            "math.greatest(-1, -2.5) == -1",
+            "math.greatest([1, 5.5, 2u]) == 5.5",
+            "math.least(1, 2.5, 3u) == 1",
+            "math.least([-1, -2.5, 0u]) == -2.5",
+            "math.ceil(1.2) == 2.0",
+            "math.floor(-1.2) == -2.0",
+            "math.round(1.5) == 2.0",
+            "math.round(-1.5) == -2.0",
+            "math.round(1.4) == 1.0",
+            "math.trunc(-1.7) == -1.0",
+            "math.abs(-1) == 1",
+            "math.abs(1u) == 1u",
+            "math.abs(-1.5) == 1.5",
+            "math.sign(-5) == -1",
+            "math.sign(0) == 0",
+            "math.sign(5u) == 1u",
+            "math.sign(-0.5) == -1.0",
+            "math.sign(0.0) == 0.0",
+            "math.isNaN(math.sign(double('NaN')))",
+            "math.isNaN(double('NaN'))",
+            "!math.isNaN(1.0)",
+            "math.isInf(-double('inf'))",
+            "!math.isInf(double('NaN'))",
+            "math.isFinite(1.0)",
+            "!math.isFinite(double('inf'))",
+            "math.bitAnd(3, 5) == 1",
+            "math.bitAnd(3u, 5u) == 1u",
+            "math.bitOr(3, 5) == 7",
+            "math.bitXor(3u, 5u) == 6u",
+            "math.bitNot(1) == -2",
+            "math.bitNot(0u) == 18446744073709551615u",
+            "math.bitShiftLeft(1, 4) == 16",
+            "math.bitShiftLeft(1u, 64) == 0u",
+            "math.bitShiftLeft(-1, 63) == -9223372036854775808",
+            "math.bitShiftRight(16u, 4) == 1u",
+            "math.bitShiftRight(-1024, 3) == 2305843009213693824",
+            "math.bitShiftRight(-1, 200) == 0",
+        ] {
+            assert_eq!(execute(expr), Ok(Value::Bool(true)), "{expr}");
+        }
+    }
+
+    #[test]
+    fn errors() {
+        for (expr, expected) in [
+            (
+                "math.greatest([])",
+                ExecutionError::function_error("greatest", "expected at least one number"),
+            ),
+            (
+                "math.least(1, 'a')",
+                ExecutionError::function_error("least", "expected a number, got String(\"a\")"),
+            ),
+            (
+                "math.abs(-9223372036854775807 - 1)",
+                ExecutionError::function_error("abs", "integer overflow"),
+            ),
+            ("math.bitAnd(1, 1u)", ExecutionError::NoSuchOverload),
+            (
+                "math.bitShiftLeft(1, -1)",
+                ExecutionError::function_error("bitShiftLeft", "negative offset: -1"),
+            ),
+        ] {
+            assert_eq!(execute(expr), Err(expected), "{expr}");
+        }
+    }
+}
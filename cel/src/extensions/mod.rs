@@ -0,0 +1,41 @@
+//! Optional libraries of functions, ported from the extensions of cel-go, which aren't part
+//! of the standard definitions of CEL.
+//!
+//! Each is added to a context by its own function, e.g.
+//! [`Context::add_strings_extension`](crate::Context::add_strings_extension), except for the
+//! encoders, which [`Context::default`](crate::Context::default) registers when the `encoders`
+//! feature is enabled.
+//!
+//! They are also [libraries](crate::Library), e.g. [`lists::Lists`], which add their
+//! declarations along with their functions to an [`Env`](crate::Env):
+//!
+//! ```
+//! use cel::extensions::strings::Strings;
+//! use cel::Env;
+//!
+//! let env = Env::default().library(Strings);
+//! let program = env.compile("'a,b'.split(',').join('-')").unwrap();
+//! assert_eq!(program.execute(&env.context()), Ok("a-b".into()));
+//! ```
+
+use crate::resolvers::Argument;
+use crate::{ExecutionError, FunctionContext, Value};
+
+#[cfg(feature = "encoders")]
+pub mod encoders;
+pub mod lists;
+pub mod math;
+pub mod sets;
+pub mod strings;
+
+/// Resolves the arguments of a method call which follow its target, which is the first
+/// argument when the method is called as a function, e.g. `sep` and `n` in both
+/// `s.split(sep, n)` and `split(s, sep, n)`.
+///
+/// Used by the functions which have overloads with a different number of arguments.
+pub(crate) fn method_args(ftx: &FunctionContext) -> Result<Vec<Value>, ExecutionError> {
+    let first = if ftx.this.is_some() { 0 } else { 1 };
+    (first..ftx.args.len())
+        .map(|i| ftx.resolve(Argument(i)))
+        .collect()
+}
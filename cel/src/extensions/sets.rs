@@ -0,0 +1,180 @@
+//! The sets extension, which adds functions in the `sets` namespace to compare lists as sets.
+//!
+//! Elements are compared as with `==`, so that numbers of different types are equal if they
+//! have the same value, e.g. `sets.contains([1], [1.0, 1u])`.
+//!
+//! # Example
+//! ```cel
+//! sets.contains(['admin', 'dev'], ['dev'])
+//! sets.equivalent([1, 2, 2], [2u, 1.0])
+//! sets.intersects(user.groups, ['admin', 'ops'])
+//! ```
+
+use crate::checker::{FunctionDecl, OverloadDecl};
+use crate::common::types::{Type, BOOL_TYPE};
+use crate::{Context, ExecutionError, Library, Value};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+type Result<T> = std::result::Result<T, ExecutionError>;
+
+const A: Type = Type::new_type_param("A");
+const LIST_A: Type = Type::new_list_type(&[&A]);
+
+/// The sets extension as a [`Library`], to add it to an [`Env`](crate::Env).
+pub struct Sets;
+
+impl Library for Sets {
+    fn declarations(&self) -> Vec<FunctionDecl> {
+        [
+            ("sets.contains", "sets_contains_list"),
+            ("sets.equivalent", "sets_equivalent_list"),
+            ("sets.intersects", "sets_intersects_list"),
+        ]
+        .into_iter()
+        .map(|(name, id)| {
+            FunctionDecl::new(name).overload(OverloadDecl::global(id, &[LIST_A, LIST_A], BOOL_TYPE))
+        })
+        .collect()
+    }
+
+    fn add_functions(&self, context: &mut Context) {
+        context.add_sets_extension();
+    }
+}
+
+/// `sets.contains(list, sublist)` is true if every element of `sublist` is in `list`.
+pub fn contains(list: Arc<Vec<Value>>, sublist: Arc<Vec<Value>>) -> Result<bool> {
+    let set = ValueSet::new(&list);
+    Ok(sublist.iter().all(|value| set.contains(value)))
+}
+
+/// `sets.equivalent(a, b)` is true if every element of `a` is in `b` and every element of `b`
+/// is in `a`, regardless of their order or repetitions.
+pub fn equivalent(a: Arc<Vec<Value>>, b: Arc<Vec<Value>>) -> Result<bool> {
+    let (set_a, set_b) = (ValueSet::new(&a), ValueSet::new(&b));
+    Ok(b.iter().all(|value| set_a.contains(value)) && a.iter().all(|value| set_b.contains(value)))
+}
+
+/// `sets.intersects(a, b)` is true if at least one element of `b` is in `a`.
+pub fn intersects(a: Arc<Vec<Value>>, b: Arc<Vec<Value>>) -> Result<bool> {
+    let (small, large) = if a.len() < b.len() { (a, b) } else { (b, a) };
+    let set = ValueSet::new(&small);
+    Ok(large.iter().any(|value| set.contains(value)))
+}
+
+/// A set of values, in which lookups are hashed unless the value is e.g. a list or a map.
+/// Also used by `distinct` of the lists extension.
+pub(crate) struct ValueSet<'a> {
+    hashed: HashSet<SetKey<'a>>,
+    others: Vec<&'a Value>,
+}
+
+impl<'a> ValueSet<'a> {
+    fn new(values: &'a [Value]) -> Self {
+        let mut set = ValueSet::with_capacity(values.len());
+        for value in values {
+            set.insert(value);
+        }
+        set
+    }
+
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        ValueSet {
+            hashed: HashSet::with_capacity(capacity),
+            others: Vec::new(),
+        }
+    }
+
+    /// Adds `value` to the set, returning whether no equal value was in it.
+    pub(crate) fn insert(&mut self, value: &'a Value) -> bool {
+        match SetKey::of(value) {
+            Some(key) => self.hashed.insert(key),
+            None if self.others.contains(&value) => false,
+            None => {
+                self.others.push(value);
+                true
+            }
+        }
+    }
+
+    fn contains(&self, value: &Value) -> bool {
+        match SetKey::of(value) {
+            Some(key) => self.hashed.contains(&key),
+            None => self.others.contains(&value),
+        }
+    }
+}
+
+/// The hashable representation of a value, in which numbers which are equal share a key.
+#[derive(PartialEq, Eq, Hash)]
+enum SetKey<'a> {
+    /// An int, a uint or an integral double.
+    Integer(i128),
+    /// The bits of a double with a fractional part.
+    Fraction(u64),
+    String(&'a str),
+    Bytes(&'a [u8]),
+    Bool(bool),
+    Null,
+}
+
+impl<'a> SetKey<'a> {
+    fn of(value: &'a Value) -> Option<Self> {
+        Some(match value {
+            Value::Int(i) => SetKey::Integer(*i as i128),
+            Value::UInt(u) => SetKey::Integer(*u as i128),
+            // Doubles of 2^64 or more are integral, but can't be equal to an int or a uint.
+            Value::Float(f) if f.fract() == 0.0 && f.abs() < 2f64.powi(64) => {
+                SetKey::Integer(*f as i128)
+            }
+            Value::Float(f) if f.is_finite() => SetKey::Fraction(f.to_bits()),
+            Value::String(s) => SetKey::String(s),
+            Value::Bytes(b) => SetKey::Bytes(b),
+            Value::Bool(b) => SetKey::Bool(*b),
+            Value::Null => SetKey::Null,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::Context;
+    use crate::{ExecutionError, Program, Value};
+
+    fn execute(expr: &str) -> std::result::Result<Value, ExecutionError> {
+        let mut context = Context::default();
+        context.add_sets_extension();
+        Program::compile(expr).unwrap().execute(&context)
+    }
+
+    #[test]
+    fn sets() {
+        for expr in [
+            "sets.contains([], [])",
+            "sets.contains([1], [])",
+            "!sets.contains([], [1])",
+            "sets.contains([1, 2, 3], [3, 1])",
+            "sets.contains([1, 2], [2, 2, 2])",
+            "sets.contains([1], [1u, 1.0])",
+            "!sets.contains([1], [1.5])",
+            "sets.contains([1.5, 'a', b'b', true, null], [null, b'b', 'a', 1.5, true])",
+            "sets.contains([[1], {'a': 1}], [[1.0], {'a': 1u}])",
+            "!sets.contains([[1]], [[2]])",
+            "!sets.contains([double('NaN')], [double('NaN')])",
+            "!sets.contains([1, 2], ['1'])",
+            "sets.equivalent([], [])",
+            "sets.equivalent([1, 2, 2], [2u, 1.0])",
+            "!sets.equivalent([1, 2], [1])",
+            "!sets.equivalent([1], [1, 2])",
+            "sets.intersects([1, 2], [2, 3])",
+            "sets.intersects(['a'], ['b', 'c', 'a'])",
+            "sets.intersects([1u], [1.0])",
+            "!sets.intersects([1, 2], [3])",
+            "!sets.intersects([], [1])",
+        ] {
+            assert_eq!(execute(expr), Ok(Value::Bool(true)), "{expr}");
+        }
+    }
+}
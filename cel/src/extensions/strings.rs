@@ -0,0 +1,667 @@
+//! The strings extension, which adds functions to inspect and manipulate strings.
+//!
+//! Indices and lengths are counted in code points, not in bytes.
+//!
+//! # Example
+//! ```cel
+//! 'hello'.charAt(1) == 'e'
+//! 'a,b,c'.split(',') == ['a', 'b', 'c']
+//! ['a', 'b'].join('-') == 'a-b'
+//! '%s is %d years old'.format(['Ada', 36]) == 'Ada is 36 years old'
+//! strings.quote('a"b') == '"a\\"b"'
+//! ```
+
+use super::method_args;
+use crate::checker::{FunctionDecl, OverloadDecl};
+use crate::common::types::{Type, DYN_TYPE, INT_TYPE, STRING_TYPE};
+use crate::magic::This;
+use crate::{Context, ExecutionError, FunctionContext, Library, Value};
+use std::fmt::Write;
+use std::sync::Arc;
+
+type Result<T> = std::result::Result<T, ExecutionError>;
+
+/// The strings extension as a [`Library`], to add it to an [`Env`](crate::Env).
+pub struct Strings;
+
+impl Library for Strings {
+    fn declarations(&self) -> Vec<FunctionDecl> {
+        let list_string = Type::new_list(&STRING_TYPE);
+        let list_dyn = Type::new_list(&DYN_TYPE);
+        let member = |id: &str, args: &[Type<'static>], result: Type<'static>| {
+            OverloadDecl::member(format!("string_{id}"), args, result)
+        };
+        vec![
+            FunctionDecl::new("charAt").overload(member(
+                "char_at_int",
+                &[STRING_TYPE, INT_TYPE],
+                STRING_TYPE,
+            )),
+            FunctionDecl::new("indexOf")
+                .overload(member(
+                    "index_of_string",
+                    &[STRING_TYPE, STRING_TYPE],
+                    INT_TYPE,
+                ))
+                .overload(member(
+                    "index_of_string_int",
+                    &[STRING_TYPE, STRING_TYPE, INT_TYPE],
+                    INT_TYPE,
+                )),
+            FunctionDecl::new("lastIndexOf")
+                .overload(member(
+                    "last_index_of_string",
+                    &[STRING_TYPE, STRING_TYPE],
+                    INT_TYPE,
+                ))
+                .overload(member(
+                    "last_index_of_string_int",
+                    &[STRING_TYPE, STRING_TYPE, INT_TYPE],
+                    INT_TYPE,
+                )),
+            FunctionDecl::new("lowerAscii").overload(member(
+                "lower_ascii",
+                &[STRING_TYPE],
+                STRING_TYPE,
+            )),
+            FunctionDecl::new("upperAscii").overload(member(
+                "upper_ascii",
+                &[STRING_TYPE],
+                STRING_TYPE,
+            )),
+            FunctionDecl::new("replace")
+                .overload(member(
+                    "replace_string_string",
+                    &[STRING_TYPE, STRING_TYPE, STRING_TYPE],
+                    STRING_TYPE,
+                ))
+                .overload(member(
+                    "replace_string_string_int",
+                    &[STRING_TYPE, STRING_TYPE, STRING_TYPE, INT_TYPE],
+                    STRING_TYPE,
+                )),
+            FunctionDecl::new("split")
+                .overload(member(
+                    "split_string",
+                    &[STRING_TYPE, STRING_TYPE],
+                    list_string.clone(),
+                ))
+                .overload(member(
+                    "split_string_int",
+                    &[STRING_TYPE, STRING_TYPE, INT_TYPE],
+                    list_string.clone(),
+                )),
+            FunctionDecl::new("substring")
+                .overload(member(
+                    "substring_int",
+                    &[STRING_TYPE, INT_TYPE],
+                    STRING_TYPE,
+                ))
+                .overload(member(
+                    "substring_int_int",
+                    &[STRING_TYPE, INT_TYPE, INT_TYPE],
+                    STRING_TYPE,
+                )),
+            FunctionDecl::new("trim").overload(member("trim", &[STRING_TYPE], STRING_TYPE)),
+            FunctionDecl::new("join")
+                .overload(OverloadDecl::member(
+                    "list_join",
+                    std::slice::from_ref(&list_string),
+                    STRING_TYPE,
+                ))
+                .overload(OverloadDecl::member(
+                    "list_join_string",
+                    &[list_string, STRING_TYPE],
+                    STRING_TYPE,
+                )),
+            FunctionDecl::new("reverse").overload(member("reverse", &[STRING_TYPE], STRING_TYPE)),
+            FunctionDecl::new("strings.quote").overload(OverloadDecl::global(
+                "strings_quote",
+                &[STRING_TYPE],
+                STRING_TYPE,
+            )),
+            FunctionDecl::new("format").overload(member(
+                "format_list_dyn",
+                &[STRING_TYPE, list_dyn],
+                STRING_TYPE,
+            )),
+        ]
+    }
+
+    fn add_functions(&self, context: &mut Context) {
+        context.add_strings_extension();
+    }
+}
+
+/// `s.charAt(i)` returns the code point at index `i`, or an empty string if `i` is the length
+/// of `s`.
+pub fn char_at(ftx: &FunctionContext, This(this): This<Arc<String>>, index: i64) -> Result<String> {
+    let start = byte_offset(ftx, &this, index)?;
+    Ok(this[start..]
+        .chars()
+        .next()
+        .map(String::from)
+        .unwrap_or_default())
+}
+
+/// `s.indexOf(substr)` and `s.indexOf(substr, offset)` return the index of the first
+/// occurrence of `substr` at or after `offset`, or -1.
+pub fn index_of(ftx: &FunctionContext, This(this): This<Arc<String>>) -> Result<i64> {
+    let (substr, offset) = match method_args(ftx)?.as_slice() {
+        [Value::String(substr)] => (substr.clone(), 0),
+        [Value::String(substr), Value::Int(offset)] => (substr.clone(), *offset),
+        _ => return Err(ExecutionError::NoSuchOverload),
+    };
+    let start = byte_offset(ftx, &this, offset)?;
+    Ok(match this[start..].find(substr.as_str()) {
+        Some(i) => offset + this[start..start + i].chars().count() as i64,
+        None => -1,
+    })
+}
+
+/// `s.lastIndexOf(substr)` and `s.lastIndexOf(substr, offset)` return the index of the last
+/// occurrence of `substr` starting at or before `offset`, or -1.
+pub fn last_index_of(ftx: &FunctionContext, This(this): This<Arc<String>>) -> Result<i64> {
+    let (substr, offset) = match method_args(ftx)?.as_slice() {
+        [Value::String(substr)] => (substr.clone(), this.chars().count() as i64),
+        [Value::String(substr), Value::Int(offset)] => (substr.clone(), *offset),
+        _ => return Err(ExecutionError::NoSuchOverload),
+    };
+    byte_offset(ftx, &this, offset)?;
+    let end = this
+        .char_indices()
+        .map(|(i, _)| i)
+        .nth(offset as usize + substr.chars().count())
+        .unwrap_or(this.len());
+    Ok(match this[..end].rfind(substr.as_str()) {
+        Some(i) => this[..i].chars().count() as i64,
+        None => -1,
+    })
+}
+
+/// `s.lowerAscii()` converts the ASCII letters of `s` to lower case.
+pub fn lower_ascii(This(this): This<Arc<String>>) -> String {
+    this.to_ascii_lowercase()
+}
+
+/// `s.upperAscii()` converts the ASCII letters of `s` to upper case.
+pub fn upper_ascii(This(this): This<Arc<String>>) -> String {
+    this.to_ascii_uppercase()
+}
+
+/// `s.replace(old, new)` replaces all the occurrences of `old` with `new`, and
+/// `s.replace(old, new, n)` only the first `n` ones, or all of them if `n` is negative.
+pub fn replace(ftx: &FunctionContext, This(this): This<Arc<String>>) -> Result<String> {
+    let (old, new, n) = match method_args(ftx)?.as_slice() {
+        [Value::String(old), Value::String(new)] => (old.clone(), new.clone(), -1),
+        [Value::String(old), Value::String(new), Value::Int(n)] => (old.clone(), new.clone(), *n),
+        _ => return Err(ExecutionError::NoSuchOverload),
+    };
+    // The result can be much larger than `s`, so it is charged before being built.
+    let count = this.matches(old.as_str()).take(n as usize).count();
+    ftx.charge_size(this.len() + count * new.len().saturating_sub(old.len()))?;
+    Ok(match usize::try_from(n) {
+        Ok(n) => this.replacen(old.as_str(), &new, n),
+        Err(_) => this.replace(old.as_str(), &new),
+    })
+}
+
+/// `s.split(sep)` splits `s` around the occurrences of `sep`, and `s.split(sep, n)` into at
+/// most `n` substrings, the last one holding the unsplit remainder. If `n` is 0 the result is
+/// empty, if it is negative `s` is split around all the occurrences of `sep`.
+///
+/// An empty separator splits the string into its code points.
+pub fn split(ftx: &FunctionContext, This(this): This<Arc<String>>) -> Result<Value> {
+    let (sep, n) = match method_args(ftx)?.as_slice() {
+        [Value::String(sep)] => (sep.clone(), -1),
+        [Value::String(sep), Value::Int(n)] => (sep.clone(), *n),
+        _ => return Err(ExecutionError::NoSuchOverload),
+    };
+    let parts: Vec<Value> = match usize::try_from(n) {
+        Ok(0) => Vec::new(),
+        _ if sep.is_empty() => {
+            let mut parts = Vec::new();
+            let mut rest = this.as_str();
+            while let Some(c) = rest.chars().next() {
+                if n > 0 && parts.len() as i64 == n - 1 {
+                    break;
+                }
+                parts.push(c.to_string().into());
+                rest = &rest[c.len_utf8()..];
+            }
+            if !rest.is_empty() {
+                parts.push(rest.into());
+            }
+            parts
+        }
+        Ok(n) => this.splitn(n, sep.as_str()).map(Value::from).collect(),
+        Err(_) => this.split(sep.as_str()).map(Value::from).collect(),
+    };
+    Ok(Value::List(Arc::new(parts)))
+}
+
+/// `s.substring(start)` returns the code points of `s` from `start` on, and
+/// `s.substring(start, end)` the ones from `start` to `end`, excluded.
+pub fn substring(ftx: &FunctionContext, This(this): This<Arc<String>>) -> Result<String> {
+    let (start, end) = match method_args(ftx)?.as_slice() {
+        [Value::Int(start)] => (*start, None),
+        [Value::Int(start), Value::Int(end)] => (*start, Some(*end)),
+        _ => return Err(ExecutionError::NoSuchOverload),
+    };
+    let start_byte = byte_offset(ftx, &this, start)?;
+    let end_byte = match end {
+        Some(end) if end < start => {
+            return Err(ftx.error(format!(
+                "invalid substring range. start: {start}, end: {end}"
+            )))
+        }
+        Some(end) => byte_offset(ftx, &this, end)?,
+        None => this.len(),
+    };
+    Ok(this[start_byte..end_byte].to_string())
+}
+
+/// `s.trim()` removes the leading and trailing whitespace of `s`.
+pub fn trim(This(this): This<Arc<String>>) -> String {
+    this.trim().to_string()
+}
+
+/// `list.join()` concatenates a list of strings, and `list.join(sep)` inserts `sep` between
+/// them.
+pub fn join(ftx: &FunctionContext, This(this): This<Arc<Vec<Value>>>) -> Result<String> {
+    let sep = match method_args(ftx)?.as_slice() {
+        [] => Arc::default(),
+        [Value::String(sep)] => sep.clone(),
+        _ => return Err(ExecutionError::NoSuchOverload),
+    };
+    let mut joined = String::new();
+    for (i, value) in this.iter().enumerate() {
+        let Value::String(s) = value else {
+            return Err(ftx.error(format!("expected a list of strings, got {value:?}")));
+        };
+        if i > 0 {
+            joined.push_str(&sep);
+        }
+        joined.push_str(s);
+    }
+    Ok(joined)
+}
+
+/// `s.reverse()` reverses the code points of `s`. The lists extension adds
+/// [`reverse`](super::lists::reverse) for lists.
+pub fn reverse(This(this): This<Arc<String>>) -> String {
+    this.chars().rev().collect()
+}
+
+/// `strings.quote(s)` returns `s` as a double quoted string literal, escaping the quotes,
+/// backslashes and control characters.
+pub fn quote(value: Arc<String>) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '\u{7}' => quoted.push_str("\\a"),
+            '\u{8}' => quoted.push_str("\\b"),
+            '\u{c}' => quoted.push_str("\\f"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '\u{b}' => quoted.push_str("\\v"),
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// `s.format(args)` replaces the formatting clauses of `s` with the values of the list `args`,
+/// in order:
+/// - `%s` any value as a string, lists and maps included, e.g. `[1, a]` or `{a: 1}`, truncated
+///   to a number of code points if specified as in `%.3s`
+/// - `%d` an integer in decimal
+/// - `%f` a number in fixed-point notation, with 6 decimals unless specified as in `%.2f`
+/// - `%e` a number in scientific notation, e.g. `1.000000e+03`, with the same precision
+/// - `%x` and `%X` an integer, a string or bytes in lower or upper case hexadecimal
+/// - `%o` an integer in octal
+/// - `%b` an integer in binary
+/// - `%%` a literal `%`
+///
+/// Precisions are at most 65535, and widths, e.g. `%10d`, are not supported.
+pub fn format(
+    ftx: &FunctionContext,
+    This(this): This<Arc<String>>,
+    args: Arc<Vec<Value>>,
+) -> Result<String> {
+    let mut formatted = String::with_capacity(this.len());
+    let mut args = args.iter();
+    let mut chars = this.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            formatted.push(c);
+            continue;
+        }
+        if chars.next_if_eq(&'%').is_some() {
+            formatted.push('%');
+            continue;
+        }
+        if chars.next_if(char::is_ascii_digit).is_some() {
+            return Err(ftx.error("formatting clauses with a width are not supported"));
+        }
+        let precision = match chars.next_if_eq(&'.') {
+            Some(_) => {
+                let mut digits = String::new();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(digit);
+                }
+                if digits.is_empty() {
+                    return Err(ftx.error("expected a precision after '%.'"));
+                }
+                // Larger precisions are out of the range `std::fmt` supports.
+                let precision = digits.parse::<u16>().map_err(|_| {
+                    ftx.error(format!(
+                        "precision {digits} is larger than the maximum of {}",
+                        u16::MAX
+                    ))
+                })?;
+                Some(usize::from(precision))
+            }
+            None => None,
+        };
+        let verb = chars
+            .next()
+            .ok_or_else(|| ftx.error("unterminated formatting clause"))?;
+        let arg = args.next().ok_or_else(|| {
+            ftx.error(format!("missing argument for formatting clause '%{verb}'"))
+        })?;
+        let invalid = |expected: &str| {
+            ftx.error(format!(
+                "formatting clause '%{verb}' requires {expected}, got {arg:?}"
+            ))
+        };
+        if precision.is_some() && !matches!(verb, 's' | 'f' | 'e') {
+            return Err(ftx.error(format!(
+                "formatting clause '%{verb}' does not support a precision"
+            )));
+        }
+        match (verb, arg) {
+            ('s', arg) => match precision {
+                Some(precision) => formatted.extend(to_string(ftx, arg)?.chars().take(precision)),
+                None => formatted.push_str(&to_string(ftx, arg)?),
+            },
+            ('d', Value::Int(i)) => write!(formatted, "{i}").unwrap(),
+            ('d', Value::UInt(u)) => write!(formatted, "{u}").unwrap(),
+            ('f' | 'e', arg) => {
+                let f = match arg {
+                    Value::Float(f) => *f,
+                    Value::Int(i) => *i as f64,
+                    Value::UInt(u) => *u as f64,
+                    _ => return Err(invalid("a number")),
+                };
+                let precision = precision.unwrap_or(6);
+                ftx.charge_size(precision)?;
+                if !f.is_finite() {
+                    formatted.push_str(&to_string(ftx, &Value::Float(f))?);
+                } else if verb == 'f' {
+                    write!(formatted, "{f:.precision$}").unwrap();
+                } else {
+                    // Rust writes the exponent as `e3`, and C as `e+03`.
+                    let scientific = format!("{f:.precision$e}");
+                    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+                    let exponent: i32 = exponent.parse().unwrap();
+                    let sign = if exponent < 0 { '-' } else { '+' };
+                    write!(formatted, "{mantissa}e{sign}{:02}", exponent.abs()).unwrap();
+                }
+            }
+            ('x' | 'X', Value::String(_) | Value::Bytes(_)) => {
+                let bytes = match arg {
+                    Value::String(s) => s.as_bytes(),
+                    Value::Bytes(b) => b.as_slice(),
+                    _ => unreachable!(),
+                };
+                for b in bytes {
+                    match verb {
+                        'x' => write!(formatted, "{b:02x}").unwrap(),
+                        _ => write!(formatted, "{b:02X}").unwrap(),
+                    }
+                }
+            }
+            ('x' | 'X' | 'o' | 'b', Value::Int(_) | Value::UInt(_)) => {
+                let (negative, magnitude) = match arg {
+                    Value::Int(i) => (*i < 0, i.unsigned_abs()),
+                    Value::UInt(u) => (false, *u),
+                    _ => unreachable!(),
+                };
+                if negative {
+                    formatted.push('-');
+                }
+                match verb {
+                    'x' => write!(formatted, "{magnitude:x}"),
+                    'X' => write!(formatted, "{magnitude:X}"),
+                    'o' => write!(formatted, "{magnitude:o}"),
+                    _ => write!(formatted, "{magnitude:b}"),
+                }
+                .unwrap();
+            }
+            ('d' | 'o' | 'b', _) => return Err(invalid("an integer")),
+            ('x' | 'X', _) => return Err(invalid("an integer, a string or bytes")),
+            _ => {
+                return Err(ftx.error(format!("unrecognized formatting clause '%{verb}'")));
+            }
+        }
+    }
+    Ok(formatted)
+}
+
+/// Formats a value for the `%s` clause.
+fn to_string(ftx: &FunctionContext, value: &Value) -> Result<String> {
+    Ok(match value {
+        Value::String(s) => s.to_string(),
+        Value::Int(i) => i.to_string(),
+        Value::UInt(u) => u.to_string(),
+        Value::Float(f) if f.is_nan() => "NaN".to_string(),
+        Value::Float(f) if f.is_infinite() && *f > 0.0 => "Infinity".to_string(),
+        Value::Float(f) if f.is_infinite() => "-Infinity".to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Null => "null".to_string(),
+        Value::Bytes(b) => String::from_utf8_lossy(b).into_owned(),
+        Value::List(list) => {
+            let elements = list
+                .iter()
+                .map(|v| to_string(ftx, v))
+                .collect::<Result<Vec<_>>>()?;
+            format!("[{}]", elements.join(", "))
+        }
+        Value::Map(map) => {
+            let mut entries: Vec<_> = map.map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            let entries = entries
+                .into_iter()
+                .map(|(k, v)| Ok(format!("{k}: {}", to_string(ftx, v)?)))
+                .collect::<Result<Vec<_>>>()?;
+            format!("{{{}}}", entries.join(", "))
+        }
+        #[cfg(feature = "chrono")]
+        Value::Timestamp(t) => t.to_rfc3339(),
+        #[cfg(feature = "chrono")]
+        Value::Duration(d) => crate::duration::format_duration(d),
+        value => return Err(ftx.error(format!("cannot format {value:?} as a string"))),
+    })
+}
+
+/// Returns the byte offset of the code point at `index` in `s`, which may be the length of `s`.
+fn byte_offset(ftx: &FunctionContext, s: &str, index: i64) -> Result<usize> {
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| {
+            s.char_indices()
+                .map(|(i, _)| i)
+                .chain(std::iter::once(s.len()))
+                .nth(index)
+        })
+        .ok_or_else(|| ftx.error(format!("index out of range: {index}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::Context;
+    use crate::{ExecutionError, Program, Value};
+
+    fn execute(expr: &str) -> std::result::Result<Value, ExecutionError> {
+        let mut context = Context::default();
+        context.add_strings_extension();
+        Program::compile(expr).unwrap().execute(&context)
+    }
+
+    #[test]
+    fn strings() {
+        for expr in [
+            "'hello'.charAt(1) == 'e'",
+            "'héllo'.charAt(1) == 'é'",
+            "'hello'.charAt(5) == ''",
+            "'hello mellow'.indexOf('') == 0",
+            "'hello mellow'.indexOf('ello') == 1",
+            "'hello mellow'.indexOf('ello', 6) == 7",
+            "'hello mellow'.indexOf('jello') == -1",
+            "'héllo héllo'.indexOf('llo', 4) == 8",
+            "'hello mellow'.lastIndexOf('') == 12",
+            "'hello mellow'.lastIndexOf('ello') == 7",
+            "'hello mellow'.lastIndexOf('ello', 6) == 1",
+            "'hello mellow'.lastIndexOf('ello', 7) == 7",
+            "'héllo héllo'.lastIndexOf('é') == 7",
+            "'TacoCat'.lowerAscii() == 'tacocat'",
+            "'TacoCÆt'.upperAscii() == 'TACOCÆT'",
+            "'hello hello'.replace('he', 'we') == 'wello wello'",
+            "'hello hello'.replace('he', 'we', 1) == 'wello hello'",
+            "'hello hello'.replace('he', 'we', -1) == 'wello wello'",
+            "'hello hello'.replace('he', 'we', 0) == 'hello hello'",
+            "'a,b,c'.split(',') == ['a', 'b', 'c']",
+            "'a,b,c'.split(',', 2) == ['a', 'b,c']",
+            "'a,b,c'.split(',', 0) == []",
+            "'a,b,c'.split(',', -1) == ['a', 'b', 'c']",
+            "'abc'.split('') == ['a', 'b', 'c']",
+            "'abc'.split('', 2) == ['a', 'bc']",
+            "split('a b', ' ') == ['a', 'b']",
+            "'tacocat'.substring(4) == 'cat'",
+            "'tacocat'.substring(0, 4) == 'taco'",
+            "'tacocat'.substring(7) == ''",
+            "'ta©o©αT'.substring(2, 6) == '©o©α'",
+            "'  \\ttrim\\n '.trim() == 'trim'",
+            "['a', 'b', 'c'].join() == 'abc'",
+            "['a', 'b', 'c'].join(', ') == 'a, b, c'",
+            "[].join('-') == ''",
+            "'gums'.reverse() == 'smug'",
+            "'héllo'.reverse() == 'olléh'",
+            "strings.quote('a\"b\\\\c\\n') == '\"a\\\\\"b\\\\\\\\c\\\\n\"'",
+            "strings.quote('é') == '\"é\"'",
+        ] {
+            assert_eq!(execute(expr), Ok(Value::Bool(true)), "{expr}");
+        }
+    }
+
+    #[test]
+    fn format() {
+        for (expr, expected) in [
+            ("'%s is %d'.format(['x', 1])", "x is 1"),
+            ("'100%%'.format([])", "100%"),
+            ("'%s'.format([[1, 'a', null]])", "[1, a, null]"),
+            ("'%s'.format([{'b': 2, 'a': 1.5}])", "{a: 1.5, b: 2}"),
+            ("'%s %s'.format([true, b'abc'])", "true abc"),
+            ("'%s'.format([double('NaN')])", "NaN"),
+            ("'%d'.format([2u])", "2"),
+            ("'%f'.format([3.14])", "3.140000"),
+            ("'%.2f'.format([2])", "2.00"),
+            ("'%.0f'.format([3u])", "3"),
+            ("'%f'.format([-double('inf')])", "-Infinity"),
+            ("'%e'.format([1052.033])", "1.052033e+03"),
+            ("'%.1e'.format([0.001])", "1.0e-03"),
+            ("'%x %X'.format([255, 255u])", "ff FF"),
+            ("'%x'.format([-255])", "-ff"),
+            ("'%x'.format(['Hi'])", "4869"),
+            ("'%X'.format([b'\\xff'])", "FF"),
+            ("'%o'.format([8])", "10"),
+            ("'%b'.format([5u])", "101"),
+            ("'é%s'.format(['é'])", "éé"),
+            ("'%.3s'.format(['abcdef'])", "abc"),
+            ("'%.2s'.format(['éèê'])", "éè"),
+            ("'%.10s'.format([true])", "true"),
+            ("'%.0s'.format([[1, 2]])", ""),
+        ] {
+            assert_eq!(execute(expr), Ok(expected.into()), "{expr}");
+        }
+    }
+
+    #[test]
+    fn errors() {
+        for (expr, expected) in [
+            (
+                "'hello'.charAt(6)",
+                ExecutionError::function_error("charAt", "index out of range: 6"),
+            ),
+            (
+                "'hello'.indexOf('l', -1)",
+                ExecutionError::function_error("indexOf", "index out of range: -1"),
+            ),
+            (
+                "'hello'.substring(3, 1)",
+                ExecutionError::function_error(
+                    "substring",
+                    "invalid substring range. start: 3, end: 1",
+                ),
+            ),
+            (
+                "'hello'.substring(6)",
+                ExecutionError::function_error("substring", "index out of range: 6"),
+            ),
+            ("'hello'.split(1)", ExecutionError::NoSuchOverload),
+            (
+                "[1].join()",
+                ExecutionError::function_error("join", "expected a list of strings, got Int(1)"),
+            ),
+            (
+                "'%d'.format([1.5])",
+                ExecutionError::function_error(
+                    "format",
+                    "formatting clause '%d' requires an integer, got Float(1.5)",
+                ),
+            ),
+            (
+                "'%s %s'.format([1])",
+                ExecutionError::function_error(
+                    "format",
+                    "missing argument for formatting clause '%s'",
+                ),
+            ),
+            (
+                "'%10d'.format([1])",
+                ExecutionError::function_error(
+                    "format",
+                    "formatting clauses with a width are not supported",
+                ),
+            ),
+            (
+                "'%.100000f'.format([1.0])",
+                ExecutionError::function_error(
+                    "format",
+                    "precision 100000 is larger than the maximum of 65535",
+                ),
+            ),
+            (
+                "'%.2d'.format([1])",
+                ExecutionError::function_error(
+                    "format",
+                    "formatting clause '%d' does not support a precision",
+                ),
+            ),
+            (
+                "'%q'.format([1])",
+                ExecutionError::function_error("format", "unrecognized formatting clause '%q'"),
+            ),
+        ] {
+            assert_eq!(execute(expr), Err(expected), "{expr}");
+        }
+    }
+}
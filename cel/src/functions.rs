@@ -3,7 +3,9 @@ use crate::magic::{Arguments, This};
 use crate::objects::Value;
 use crate::parser::Expression;
 use crate::resolvers::Resolver;
+use crate::unknown::Unknown;
 use crate::ExecutionError;
+use std::cell::{Cell, RefCell};
 use std::cmp::Ordering;
 use std::convert::TryInto;
 use std::sync::Arc;
@@ -22,6 +24,13 @@ pub struct FunctionContext<'context, 'call: 'context> {
     pub ptx: &'context Context<'context>,
     pub args: &'call [Expression],
     pub arg_idx: usize,
+    /// The already resolved values of `args`, if any.
+    pub(crate) resolved_args: Option<Vec<Result<Value>>>,
+    /// The unknown values among the arguments resolved so far, which make the result of the
+    /// call unknown.
+    pub(crate) unknown: RefCell<Option<Unknown>>,
+    /// The cost charged by the function itself through [`FunctionContext::charge_size`].
+    pub(crate) charged: Cell<u64>,
 }
 
 impl<'context, 'call: 'context> FunctionContext<'context, 'call> {
@@ -37,6 +46,9 @@ impl<'context, 'call: 'context> FunctionContext<'context, 'call> {
             ptx,
             args,
             arg_idx: 0,
+            resolved_args: None,
+            unknown: RefCell::new(None),
+            charged: Cell::new(0),
         }
     }
 
@@ -48,6 +60,16 @@ impl<'context, 'call: 'context> FunctionContext<'context, 'call> {
         resolver.resolve(self)
     }
 
+    /// Charges the cost of a value of `size` elements built by the function, before building
+    /// it. Calls are charged for the size of their result anyway, but functions whose result
+    /// can be much larger than their arguments, e.g. `lists.range(n)`, should charge upfront so
+    /// that the cost limit is enforced before allocating it.
+    pub fn charge_size(&self, size: usize) -> Result<()> {
+        let cost = crate::cost::traversal_units(size as u64);
+        self.charged.set(self.charged.get().saturating_add(cost));
+        self.ptx.charge(cost)
+    }
+
     /// Returns an execution error for the currently execution function.
     pub fn error<M: ToString>(&self, message: M) -> ExecutionError {
         ExecutionError::function_error(self.name, message)
@@ -270,8 +292,8 @@ pub use time::timestamp;
 pub mod time {
     use super::Result;
     use crate::magic::This;
-    use crate::{ExecutionError, Value};
-    use chrono::{Datelike, Days, Months, Timelike};
+    use crate::{ExecutionError, FunctionContext, Value};
+    use chrono::{Datelike, Timelike};
     use std::sync::Arc;
 
     /// Duration parses the provided argument into a [`Value::Duration`] value.
@@ -315,68 +337,80 @@ pub mod time {
             .map_err(|e| ExecutionError::function_error("timestamp", e.to_string()))
     }
 
-    pub fn timestamp_year(
-        This(this): This<chrono::DateTime<chrono::FixedOffset>>,
-    ) -> Result<Value> {
+    type DateTime = chrono::DateTime<chrono::FixedOffset>;
+
+    /// Converts the target of a timestamp accessor to the timezone of its optional argument,
+    /// e.g. `ts.getHours('America/New_York')`.
+    ///
+    /// The timezone is either a UTC offset, e.g. `+05:30` or `-08:00`, `UTC`, or, with the
+    /// `chrono-tz` feature, the name of a timezone of the IANA database. Without a timezone,
+    /// the timestamp is left in its own offset.
+    fn in_timezone(ftx: &FunctionContext, this: DateTime) -> Result<DateTime> {
+        let tz = match crate::extensions::method_args(ftx)?.as_slice() {
+            [] => return Ok(this),
+            [Value::String(tz)] => tz.clone(),
+            _ => return Err(ExecutionError::NoSuchOverload),
+        };
+        if tz.as_str() == "UTC" {
+            return Ok(this.to_utc().fixed_offset());
+        }
+        if let Ok(offset) = tz.parse::<chrono::FixedOffset>() {
+            return Ok(this.with_timezone(&offset));
+        }
+        #[cfg(feature = "chrono-tz")]
+        if let Ok(tz) = tz.parse::<chrono_tz::Tz>() {
+            return Ok(this.with_timezone(&tz).fixed_offset());
+        }
+        Err(ftx.error(format!("unknown timezone: {tz}")))
+    }
+
+    pub fn timestamp_year(ftx: &FunctionContext, This(this): This<DateTime>) -> Result<Value> {
+        let this = in_timezone(ftx, this)?;
         Ok(this.year().into())
     }
 
-    pub fn timestamp_month(
-        This(this): This<chrono::DateTime<chrono::FixedOffset>>,
-    ) -> Result<Value> {
+    pub fn timestamp_month(ftx: &FunctionContext, This(this): This<DateTime>) -> Result<Value> {
+        let this = in_timezone(ftx, this)?;
         Ok((this.month0() as i32).into())
     }
 
-    pub fn timestamp_year_day(
-        This(this): This<chrono::DateTime<chrono::FixedOffset>>,
-    ) -> Result<Value> {
-        let year = this
-            .checked_sub_days(Days::new(this.day0() as u64))
-            .unwrap()
-            .checked_sub_months(Months::new(this.month0()))
-            .unwrap();
-        Ok(this.signed_duration_since(year).num_days().into())
+    pub fn timestamp_year_day(ftx: &FunctionContext, This(this): This<DateTime>) -> Result<Value> {
+        let this = in_timezone(ftx, this)?;
+        Ok((this.ordinal0() as i32).into())
     }
 
-    pub fn timestamp_month_day(
-        This(this): This<chrono::DateTime<chrono::FixedOffset>>,
-    ) -> Result<Value> {
+    pub fn timestamp_month_day(ftx: &FunctionContext, This(this): This<DateTime>) -> Result<Value> {
+        let this = in_timezone(ftx, this)?;
         Ok((this.day0() as i32).into())
     }
 
-    pub fn timestamp_date(
-        This(this): This<chrono::DateTime<chrono::FixedOffset>>,
-    ) -> Result<Value> {
+    pub fn timestamp_date(ftx: &FunctionContext, This(this): This<DateTime>) -> Result<Value> {
+        let this = in_timezone(ftx, this)?;
         Ok((this.day() as i32).into())
     }
 
-    pub fn timestamp_weekday(
-        This(this): This<chrono::DateTime<chrono::FixedOffset>>,
-    ) -> Result<Value> {
+    pub fn timestamp_weekday(ftx: &FunctionContext, This(this): This<DateTime>) -> Result<Value> {
+        let this = in_timezone(ftx, this)?;
         Ok((this.weekday().num_days_from_sunday() as i32).into())
     }
 
-    pub fn timestamp_hours(
-        This(this): This<chrono::DateTime<chrono::FixedOffset>>,
-    ) -> Result<Value> {
+    pub fn timestamp_hours(ftx: &FunctionContext, This(this): This<DateTime>) -> Result<Value> {
+        let this = in_timezone(ftx, this)?;
         Ok((this.hour() as i32).into())
     }
 
-    pub fn timestamp_minutes(
-        This(this): This<chrono::DateTime<chrono::FixedOffset>>,
-    ) -> Result<Value> {
+    pub fn timestamp_minutes(ftx: &FunctionContext, This(this): This<DateTime>) -> Result<Value> {
+        let this = in_timezone(ftx, this)?;
         Ok((this.minute() as i32).into())
     }
 
-    pub fn timestamp_seconds(
-        This(this): This<chrono::DateTime<chrono::FixedOffset>>,
-    ) -> Result<Value> {
+    pub fn timestamp_seconds(ftx: &FunctionContext, This(this): This<DateTime>) -> Result<Value> {
+        let this = in_timezone(ftx, this)?;
         Ok((this.second() as i32).into())
     }
 
-    pub fn timestamp_millis(
-        This(this): This<chrono::DateTime<chrono::FixedOffset>>,
-    ) -> Result<Value> {
+    pub fn timestamp_millis(ftx: &FunctionContext, This(this): This<DateTime>) -> Result<Value> {
+        let this = in_timezone(ftx, this)?;
         Ok((this.timestamp_subsec_millis() as i32).into())
     }
 }
@@ -429,6 +463,102 @@ pub fn min(Arguments(args): Arguments) -> Result<Value> {
         .cloned()
 }
 
+/// The functions of the `optional` namespace and the methods of optional values.
+///
+/// # Example
+/// ```cel
+/// optional.of(1).orValue(2) == 1
+/// optional.none().or(optional.of(2)).value() == 2
+/// {'a': 1}.?b.hasValue() == false
+/// ```
+pub mod optional {
+    use super::Result;
+    use crate::magic::This;
+    use crate::objects::Value;
+    use crate::parser::Expression;
+    use crate::FunctionContext;
+
+    /// `optional.of(value)`
+    pub fn of(value: Value) -> Result<Value> {
+        Ok(Value::Optional(Some(Box::new(value))))
+    }
+
+    /// `optional.none()`
+    pub fn none() -> Result<Value> {
+        Ok(Value::Optional(None))
+    }
+
+    /// `optional.ofNonZeroValue(value)` is `optional.none()` for the zero value of any type,
+    /// e.g. `0`, `''`, `[]` or `null`.
+    pub fn of_non_zero_value(value: Value) -> Result<Value> {
+        let zero = match &value {
+            Value::List(l) => l.is_empty(),
+            Value::Map(m) => m.map.is_empty(),
+            Value::Int(i) => *i == 0,
+            Value::UInt(u) => *u == 0,
+            Value::Float(f) => *f == 0.0,
+            Value::String(s) => s.is_empty(),
+            Value::Bytes(b) => b.is_empty(),
+            Value::Bool(b) => !*b,
+            #[cfg(feature = "chrono")]
+            Value::Duration(d) => d.is_zero(),
+            #[cfg(feature = "chrono")]
+            Value::Timestamp(t) => t.timestamp() == 0 && t.timestamp_subsec_nanos() == 0,
+            Value::Struct(s) => s.fields().is_empty(),
+            Value::Optional(o) => o.is_none(),
+            Value::Null => true,
+            Value::Function(_, _) | Value::Opaque(_) | Value::Unknown(_) => false,
+        };
+        if zero {
+            none()
+        } else {
+            of(value)
+        }
+    }
+
+    /// `opt.hasValue()`
+    pub fn has_value(ftx: &FunctionContext, This(this): This<Value>) -> Result<bool> {
+        Ok(unwrap(ftx, this)?.is_some())
+    }
+
+    /// `opt.value()` returns the value of the optional, and fails if it has none.
+    pub fn value(ftx: &FunctionContext, This(this): This<Value>) -> Result<Value> {
+        unwrap(ftx, this)?.ok_or_else(|| ftx.error("optional.none() dereference"))
+    }
+
+    /// `opt.orValue(default)` returns the value of the optional, or `default` if it has none.
+    /// `default` is only evaluated when needed.
+    pub fn or_value(
+        ftx: &FunctionContext,
+        This(this): This<Value>,
+        default: Expression,
+    ) -> Result<Value> {
+        match unwrap(ftx, this)? {
+            Some(value) => Ok(value),
+            None => ftx.resolve(default),
+        }
+    }
+
+    /// `opt.or(other)` returns the optional if it has a value, or the optional `other`
+    /// otherwise. `other` is only evaluated when needed.
+    pub fn or(ftx: &FunctionContext, This(this): This<Value>, other: Expression) -> Result<Value> {
+        match unwrap(ftx, this)? {
+            Some(value) => of(value),
+            None => match ftx.resolve(other)? {
+                Value::Optional(value) => Ok(Value::Optional(value)),
+                value => Err(ftx.error(format!("expected an optional value, got {value:?}"))),
+            },
+        }
+    }
+
+    fn unwrap(ftx: &FunctionContext, value: Value) -> Result<Option<Value>> {
+        match value {
+            Value::Optional(value) => Ok(value.map(|v| *v)),
+            value => Err(ftx.error(format!("expected an optional value, got {value:?}"))),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::context::Context;
@@ -540,6 +670,124 @@ mod tests {
         .for_each(assert_script);
     }
 
+    #[test]
+    fn test_comprehensions_v2() {
+        [
+            ("all list", "[1, 2, 3].all(i, v, v == i + 1)"),
+            ("all map", "{'a': 1, 'b': 2}.all(k, v, k != 'c' && v > 0)"),
+            ("exists list", "[1, 2, 3].exists(i, v, i == 2 && v == 3)"),
+            (
+                "exists map",
+                "{'a': 1, 'b': 2}.exists(k, v, k == 'b' && v == 2)",
+            ),
+            ("existsOne list", "[1, 1, 3].existsOne(i, v, i == v)"),
+            ("existsOne map", "!{'a': 1, 'b': 1}.existsOne(k, v, v == 1)"),
+            (
+                "transformList",
+                "[1, 2, 3].transformList(i, v, i * v) == [0, 2, 6]",
+            ),
+            (
+                "transformList filter",
+                "[1, 2, 3].transformList(i, v, i > 0, v) == [2, 3]",
+            ),
+            (
+                "transformList map",
+                "{'a': 1}.transformList(k, v, k + string(v)) == ['a1']",
+            ),
+            (
+                "transformMap",
+                "{'a': 1, 'b': 2}.transformMap(k, v, v * 10) == {'a': 10, 'b': 20}",
+            ),
+            (
+                "transformMap filter",
+                "{'a': 1, 'b': 2}.transformMap(k, v, v > 1, k) == {'b': 'b'}",
+            ),
+            (
+                "transformMap list",
+                "['a', 'b'].transformMap(i, v, v) == {0: 'a', 1: 'b'}",
+            ),
+            (
+                "transformMapEntry",
+                "{'a': 1, 'b': 2}.transformMapEntry(k, v, {v: k}) == {1: 'a', 2: 'b'}",
+            ),
+            (
+                "transformMapEntry filter",
+                "[1, 2].transformMapEntry(i, v, v > 1, {'k' + string(i): v}) == {'k1': 2}",
+            ),
+        ]
+        .iter()
+        .for_each(assert_script);
+    }
+
+    #[test]
+    fn test_bindings() {
+        [
+            ("bind", "cel.bind(x, 2, x * x) == 4"),
+            (
+                "bind nested",
+                "cel.bind(x, 1, cel.bind(y, x + 1, [x, y])) == [1, 2]",
+            ),
+            (
+                "bind shadows",
+                "cel.bind(x, 'a', cel.bind(x, x + 'b', x)) == 'ab'",
+            ),
+            (
+                "bind in comprehension",
+                "[1, 2].map(x, cel.bind(y, x * 2, y + y)) == [4, 8]",
+            ),
+            (
+                "block",
+                "cel.block([1, cel.index(0) + 1, cel.index(1) * 3], cel.index(2)) == 6",
+            ),
+            (
+                "block in comprehension",
+                "cel.block([[1, 2]], cel.index(0).all(x, x in cel.index(0)))",
+            ),
+            (
+                "block unused index",
+                "cel.block([1 / 0 > 0], false && cel.index(0)) == false",
+            ),
+            (
+                "block index used by unused index",
+                "cel.block([1 / 0, cel.index(0) + 1], 2) == 2",
+            ),
+        ]
+        .iter()
+        .for_each(assert_script);
+    }
+
+    #[test]
+    fn test_block_evaluates_indexes_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut context = Context::default();
+        let counter = calls.clone();
+        context.add_function("inc", move |x: i64| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            x + 1
+        });
+
+        let program = crate::Program::compile(
+            "cel.block([inc(0), inc(cel.index(0))], [1, 2].map(x, x + cel.index(1) + cel.index(1)))",
+        )
+        .unwrap();
+        assert_eq!(program.execute(&context), Ok(vec![5, 6].into()));
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_transform_map_entry_duplicate_key() {
+        [(
+            "transformMapEntry duplicate key",
+            "[1, 2].transformMapEntry(i, v, {'k': v})",
+            "Error executing function 'cel.@mapInsert': insert failed: key k already exists",
+        )]
+        .iter()
+        .for_each(assert_error);
+    }
+
     #[test]
     fn test_max() {
         [
@@ -696,6 +944,80 @@ mod tests {
         .for_each(assert_error)
     }
 
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_timestamp_timezone() {
+        [
+            (
+                "getHours with offset",
+                "timestamp('2023-05-28T02:00:00Z').getHours('+05:30') == 7",
+            ),
+            (
+                "getMinutes with offset",
+                "timestamp('2023-05-28T02:00:00Z').getMinutes('+05:30') == 30",
+            ),
+            (
+                "getDate with negative offset",
+                "timestamp('2023-05-28T02:00:00Z').getDate('-08:00') == 27",
+            ),
+            (
+                "getDayOfWeek with negative offset",
+                "timestamp('2023-05-28T02:00:00Z').getDayOfWeek('-08:00') == 6",
+            ),
+            (
+                "getFullYear with offset",
+                "timestamp('2023-12-31T23:00:00Z').getFullYear('+01:00') == 2024",
+            ),
+            (
+                "getDayOfYear with offset",
+                "timestamp('2023-12-31T23:00:00Z').getDayOfYear('+01:00') == 0",
+            ),
+            (
+                "getHours in UTC",
+                "timestamp('2023-05-28T02:00:00+02:00').getHours('UTC') == 0",
+            ),
+            (
+                "getHours without timezone",
+                "timestamp('2023-05-28T02:00:00+02:00').getHours() == 2",
+            ),
+        ]
+        .iter()
+        .for_each(assert_script);
+
+        [(
+            "unknown timezone",
+            "timestamp('2023-05-28T02:00:00Z').getHours('Mars/Olympus_Mons')",
+            "Error executing function 'getHours': unknown timezone: Mars/Olympus_Mons",
+        )]
+        .iter()
+        .for_each(assert_error)
+    }
+
+    #[cfg(feature = "chrono-tz")]
+    #[test]
+    fn test_timestamp_iana_timezone() {
+        [
+            (
+                "getHours in New York during DST",
+                "timestamp('2023-05-28T14:00:00Z').getHours('America/New_York') == 10",
+            ),
+            (
+                "getHours in New York outside DST",
+                "timestamp('2023-01-28T14:00:00Z').getHours('America/New_York') == 9",
+            ),
+            (
+                "getDayOfMonth in Tokyo",
+                "timestamp('2023-05-28T20:00:00Z').getDayOfMonth('Asia/Tokyo') == 28",
+            ),
+            (
+                "getMonth in Sydney",
+                "timestamp('2023-05-31T20:00:00Z').getMonth('Australia/Sydney') == 5",
+            ),
+        ]
+        .iter()
+        .for_each(assert_script);
+    }
+
     #[cfg(feature = "chrono")]
     #[test]
     fn test_duration() {
@@ -865,4 +1187,85 @@ mod tests {
         .iter()
         .for_each(assert_error)
     }
+
+    #[test]
+    fn test_optional() {
+        [
+            ("of", "optional.of(1).value() == 1"),
+            ("none", "!optional.none().hasValue()"),
+            ("of non zero", "optional.ofNonZeroValue(1).hasValue()"),
+            ("of zero", "!optional.ofNonZeroValue(0).hasValue()"),
+            ("of empty list", "!optional.ofNonZeroValue([]).hasValue()"),
+            ("orValue", "optional.none().orValue(2) == 2"),
+            ("orValue present", "optional.of(1).orValue(2) == 1"),
+            ("orValue lazy", "optional.of(1).orValue(1 / 0) == 1"),
+            ("or", "optional.none().or(optional.of(2)).value() == 2"),
+            (
+                "or present",
+                "optional.of(1).or(optional.of(2)) == optional.of(1)",
+            ),
+            ("select", "{'a': 1}.?a == optional.of(1)"),
+            ("select missing", "{'a': 1}.?b == optional.none()"),
+            ("select chain", "{'a': {'b': 1}}.?a.?b.value() == 1"),
+            ("select chain missing", "!{'a': {'b': 1}}.?c.?b.hasValue()"),
+            (
+                "select after optional",
+                "{'a': {'b': 1}}.?a.b == optional.of(1)",
+            ),
+            ("index", "[1, 2][?1] == optional.of(2)"),
+            ("index out of bounds", "[1, 2][?2] == optional.none()"),
+            ("map index", "{'a': 1}[?'a'] == optional.of(1)"),
+            ("map index missing", "{'a': 1}[?'b'] == optional.none()"),
+            (
+                "index after optional",
+                "optional.of([1, 2])[1] == optional.of(2)",
+            ),
+            (
+                "select missing after optional",
+                "{'a': {}}.?a.b == optional.none()",
+            ),
+            (
+                "select missing after optional value",
+                "optional.of({'a': {}}).?a.b == optional.none()",
+            ),
+            (
+                "select missing after optional chain",
+                "!{'a': {'b': {}}}.?a.b.c.d.hasValue()",
+            ),
+            (
+                "index out of bounds after optional",
+                "optional.of([1, 2])[2] == optional.none()",
+            ),
+            (
+                "index missing after optional",
+                "{'a': {}}.?a['b'] == optional.none()",
+            ),
+            (
+                "mixed chain after optional",
+                "{'a': [{'b': 1}]}.?a[0].b == optional.of(1)",
+            ),
+            ("list", "[?optional.of(1), ?optional.none(), 2] == [1, 2]"),
+            (
+                "map",
+                "{?'a': optional.of(1), ?'b': optional.none()} == {'a': 1}",
+            ),
+        ]
+        .iter()
+        .for_each(assert_script);
+
+        [
+            (
+                "value of none",
+                "optional.none().value()",
+                "Error executing function 'value': optional.none() dereference",
+            ),
+            (
+                "optional list element",
+                "[?1]",
+                "Unexpected type: got 'int', want 'optional_type'",
+            ),
+        ]
+        .iter()
+        .for_each(assert_error)
+    }
 }
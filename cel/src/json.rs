@@ -1,7 +1,10 @@
+use crate::objects::{Key, Map};
 use crate::Value;
 use base64::prelude::*;
 #[cfg(feature = "chrono")]
 use chrono::Duration;
+use std::collections::HashMap;
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Debug, Clone, Error)]
@@ -62,13 +65,123 @@ impl Value {
                     .ok_or(ConvertToJsonError::DurationOverflow(v))?,
             )),
             Value::Opaque(ref opaque) => (**opaque).json().unwrap_or(serde_json::Value::Null),
+            Value::Optional(Some(ref v)) => v.json()?,
+            Value::Optional(None) => serde_json::Value::Null,
+            Value::Struct(ref s) => {
+                let mut obj = serde_json::Map::new();
+                for (name, _) in s.struct_type().fields() {
+                    if let Some(v) = s.get(name) {
+                        obj.insert(name.to_string(), v.json()?);
+                    }
+                }
+                serde_json::Value::Object(obj)
+            }
             _ => return Err(ConvertToJsonError::Value(self)),
         })
     }
 }
 
+/// How JSON numbers are converted to CEL values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonNumbers {
+    /// Numbers which fit an `int` become [`Value::Int`], then those which fit a `uint` become
+    /// [`Value::UInt`], and all others become [`Value::Float`].
+    IntFirst,
+    /// All numbers become [`Value::Float`], as in the CEL mapping of JSON values.
+    #[default]
+    Double,
+}
+
+impl Value {
+    /// Converts a JSON value to a CEL value, mapping numbers as given by `numbers`.
+    ///
+    /// The JSON value is consumed, so that its strings and object keys are moved into the
+    /// CEL value rather than copied.
+    ///
+    /// # Example
+    /// ```
+    /// use cel::{JsonNumbers, Value};
+    /// use serde_json::json;
+    ///
+    /// let value = Value::from_json(json!([1, 1.5]), JsonNumbers::IntFirst);
+    /// assert_eq!(value, Value::List(vec![Value::Int(1), Value::Float(1.5)].into()));
+    /// ```
+    pub fn from_json(value: serde_json::Value, numbers: JsonNumbers) -> Value {
+        match value {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(b),
+            serde_json::Value::Number(n) => from_json_number(&n, numbers),
+            serde_json::Value::String(s) => Value::String(Arc::new(s)),
+            serde_json::Value::Array(vec) => Value::List(Arc::new(
+                vec.into_iter()
+                    .map(|v| Value::from_json(v, numbers))
+                    .collect(),
+            )),
+            serde_json::Value::Object(obj) => {
+                let map: HashMap<_, _> = obj
+                    .into_iter()
+                    .map(|(k, v)| (Key::String(Arc::new(k)), Value::from_json(v, numbers)))
+                    .collect();
+                Value::Map(Map { map: Arc::new(map) })
+            }
+        }
+    }
+}
+
+fn from_json_number(n: &serde_json::Number, numbers: JsonNumbers) -> Value {
+    if numbers == JsonNumbers::IntFirst {
+        if let Some(i) = n.as_i64() {
+            return Value::Int(i);
+        }
+        if let Some(u) = n.as_u64() {
+            return Value::UInt(u);
+        }
+    }
+    // Without the arbitrary_precision feature of serde_json, every number is an f64.
+    Value::Float(n.as_f64().unwrap_or(f64::NAN))
+}
+
+/// Converts a JSON value, mapping numbers to doubles as with [`JsonNumbers::Double`].
+impl From<serde_json::Value> for Value {
+    fn from(value: serde_json::Value) -> Self {
+        Value::from_json(value, JsonNumbers::default())
+    }
+}
+
+/// Converts a JSON value, mapping numbers to doubles as with [`JsonNumbers::Double`]. Prefer
+/// converting an owned value where possible, which avoids copying strings.
+impl From<&serde_json::Value> for Value {
+    fn from(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(*b),
+            serde_json::Value::Number(n) => from_json_number(n, JsonNumbers::default()),
+            serde_json::Value::String(s) => Value::String(Arc::new(s.clone())),
+            serde_json::Value::Array(vec) => {
+                Value::List(Arc::new(vec.iter().map(Value::from).collect()))
+            }
+            serde_json::Value::Object(obj) => {
+                let map: HashMap<_, _> = obj
+                    .iter()
+                    .map(|(k, v)| (Key::String(Arc::new(k.clone())), Value::from(v)))
+                    .collect();
+                Value::Map(Map { map: Arc::new(map) })
+            }
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for serde_json::Value {
+    type Error = ConvertToJsonError<'a>;
+
+    fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
+        value.json()
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::JsonNumbers;
     use crate::objects::Map;
     use crate::Value as CelValue;
     #[cfg(feature = "chrono")]
@@ -109,4 +222,50 @@ mod tests {
             assert_eq!(value.json().unwrap(), *expected, "{value:?}={expected:?}");
         }
     }
+
+    #[test]
+    fn test_json_to_cel_value() {
+        let value = json!({"a": [1, -2, 18446744073709551615u64, 1.5, "s", true, null]});
+        let expected = CelValue::Map(Map::from(HashMap::from([(
+            "a".to_string(),
+            CelValue::List(
+                vec![
+                    CelValue::Int(1),
+                    CelValue::Int(-2),
+                    CelValue::UInt(u64::MAX),
+                    CelValue::Float(1.5),
+                    CelValue::String("s".to_string().into()),
+                    CelValue::Bool(true),
+                    CelValue::Null,
+                ]
+                .into(),
+            ),
+        )])));
+        assert_eq!(CelValue::from_json(value, JsonNumbers::IntFirst), expected);
+    }
+
+    #[test]
+    fn test_json_numbers() {
+        let value = json!([1, -2, 18446744073709551615u64, 1.5]);
+        let doubles = CelValue::List(
+            vec![
+                CelValue::Float(1.0),
+                CelValue::Float(-2.0),
+                CelValue::Float(u64::MAX as f64),
+                CelValue::Float(1.5),
+            ]
+            .into(),
+        );
+        assert_eq!(CelValue::from(&value), doubles);
+        assert_eq!(CelValue::from(value.clone()), doubles);
+        assert_eq!(CelValue::from_json(value, JsonNumbers::Double), doubles);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let value =
+            json!({"name": "cel", "tags": ["a", "b"], "size": 3, "ratio": 0.5, "ok": false});
+        let cel = CelValue::from_json(value.clone(), JsonNumbers::IntFirst);
+        assert_eq!(serde_json::Value::try_from(&cel).unwrap(), value);
+    }
 }
@@ -6,21 +6,33 @@ use thiserror::Error;
 
 mod macros;
 
+pub mod checker;
 pub mod common;
 pub mod context;
+pub mod cost;
+mod env;
+pub mod extensions;
 pub mod parser;
 
 pub use common::ast::IdedExpr;
-use common::ast::SelectExpr;
+use common::ast::{SelectExpr, SourceInfo};
 pub use context::Context;
+pub use env::{CompileError, Env, Library};
 pub use functions::FunctionContext;
+use locate::ErrorLocations;
 pub use objects::{ResolveResult, Value};
 use parser::{Expression, ExpressionReferences, Parser};
 pub use parser::{ParseError, ParseErrors};
 pub mod functions;
+mod locate;
 mod magic;
 pub mod objects;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+mod residual;
 mod resolvers;
+pub mod structs;
+pub mod unknown;
 
 #[cfg(feature = "chrono")]
 mod duration;
@@ -31,10 +43,14 @@ mod ser;
 pub use ser::to_value;
 pub use ser::SerializationError;
 
+mod de;
+pub use de::from_value;
+pub use de::DeserializationError;
+
 #[cfg(feature = "json")]
 mod json;
 #[cfg(feature = "json")]
-pub use json::ConvertToJsonError;
+pub use json::{ConvertToJsonError, JsonNumbers};
 
 use magic::FromContext;
 
@@ -113,6 +129,10 @@ pub enum ExecutionError {
     Overflow(&'static str, Value, Value),
     #[error("Index out of bounds: {0:?}")]
     IndexOutOfBounds(Value),
+    /// Indicates that the cost of the execution exceeded the limit of its
+    /// [`cost::CostTracker`].
+    #[error("Cost limit of {0} exceeded")]
+    CostLimitExceeded(u64),
 }
 
 impl ExecutionError {
@@ -155,23 +175,147 @@ impl ExecutionError {
     }
 }
 
+/// An [`ExecutionError`] along with the expression which raised it, as returned by
+/// [`Program::execute_located`].
+///
+/// Its display points at the expression in the source of the program:
+/// ```text
+/// ERROR: <input>:1:10: No such key: baz
+/// | foo.bar.baz > 1
+/// | .........^
+/// ```
+#[derive(Clone, Debug)]
+pub struct LocatedError {
+    pub error: ExecutionError,
+    /// The id of the expression which raised the error, see [`IdedExpr::id`].
+    pub expr_id: u64,
+    /// The line and column of the expression, both starting at 1, or `(0, 0)` if unknown.
+    pub pos: (isize, isize),
+    pub source_info: Arc<SourceInfo>,
+}
+
+impl std::fmt::Display for LocatedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ERROR: <input>:{}:{}: {}",
+            self.pos.0, self.pos.1, self.error
+        )?;
+        if let Some(line) = self.source_info.snippet(self.pos.0 - 1) {
+            write!(f, "\n| {line}")?;
+            write!(f, "\n| {:.>width$}", "^", width = self.pos.1 as usize)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LocatedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
 #[derive(Debug)]
 pub struct Program {
     expression: Expression,
+    source_info: Arc<SourceInfo>,
 }
 
 impl Program {
     pub fn compile(source: &str) -> Result<Program, ParseErrors> {
-        let parser = Parser::default();
+        Program::compile_with(source, Parser::default())
+    }
+
+    /// Compiles `source` with a configured parser, e.g. one with user-defined macros, see
+    /// [`parser::Macro`].
+    pub fn compile_with(source: &str, parser: Parser) -> Result<Program, ParseErrors> {
         parser
-            .parse(source)
-            .map(|expression| Program { expression })
+            .parse_with_source_info(source)
+            .map(|(expression, source_info)| Program {
+                expression,
+                source_info,
+            })
     }
 
     pub fn execute(&self, context: &Context) -> ResolveResult {
         Value::resolve(&self.expression, context)
     }
 
+    /// Executes the program like [`Program::execute`], charging the cost of the evaluation to
+    /// `tracker` and aborting it once its limit is exceeded.
+    ///
+    /// The cost adds up when the same tracker is used for several executions. See
+    /// [`cost::CostTracker`] for the cost of each operation.
+    pub fn execute_with_cost(
+        &self,
+        context: &Context,
+        tracker: &cost::CostTracker,
+    ) -> ResolveResult {
+        Value::resolve(&self.expression, &context.new_tracked_scope(tracker))
+    }
+
+    /// Executes the program like [`Program::execute`], but on failure also locates the
+    /// expression which raised the error in the source of the program.
+    ///
+    /// The expressions raising errors are recorded along the execution, which makes it
+    /// somewhat slower than [`Program::execute`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use cel::{Context, Program};
+    /// use std::collections::HashMap;
+    ///
+    /// let mut context = Context::default();
+    /// context.add_variable_from_value("foo", HashMap::from([("bar", HashMap::from([("qux", 1)]))]));
+    ///
+    /// let program = Program::compile("foo.bar.baz > 1").unwrap();
+    /// let error = program.execute_located(&context).unwrap_err();
+    /// assert_eq!(error.pos, (1, 8));
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "ERROR: <input>:1:8: No such key: baz\n| foo.bar.baz > 1\n| .......^"
+    /// );
+    /// ```
+    pub fn execute_located(&self, context: &Context) -> Result<Value, LocatedError> {
+        let errors = ErrorLocations::default();
+        let result = Value::resolve(&self.expression, &context.new_located_scope(&errors));
+        result.map_err(|error| {
+            let expr_id = errors.locate(&error).unwrap_or(self.expression.id);
+            LocatedError {
+                error,
+                expr_id,
+                pos: self.source_info.pos_for(expr_id).unwrap_or((0, 0)),
+                source_info: self.source_info.clone(),
+            }
+        })
+    }
+
+    /// Partially evaluates the program, returning its residual: the expression in which every
+    /// subexpression that could be evaluated in `context` is replaced by its value, and
+    /// logical operators and conditionals are simplified where their result is decided.
+    ///
+    /// This is meant for contexts where some attributes are unknown, see
+    /// [`Context::add_unknown`]. The residual can be turned back into CEL source with
+    /// [`parser::unparse`] and evaluated later, once the unknown attributes are known.
+    ///
+    /// # Example
+    /// ```rust
+    /// use cel::parser::unparse;
+    /// use cel::unknown::AttributePattern;
+    /// use cel::{Context, Program};
+    ///
+    /// let mut context = Context::default();
+    /// context.add_variable_from_value("path", "/admin");
+    /// context.add_unknown(AttributePattern::new("user"));
+    ///
+    /// let program = Program::compile("path.startsWith('/admin') && user.role == 'admin'").unwrap();
+    /// let residual = program.residual(&context);
+    /// assert_eq!(unparse(&residual).unwrap(), r#"user.role == "admin""#);
+    /// ```
+    pub fn residual(&self, context: &Context) -> Expression {
+        residual::residual(&self.expression, context)
+    }
+
     /// Returns the variables and functions referenced by the CEL program
     ///
     /// # Example
@@ -191,6 +335,11 @@ impl Program {
     pub fn expression(&self) -> &Expression {
         &self.expression
     }
+
+    /// Returns the source of the program and the positions of its expressions
+    pub fn source_info(&self) -> &SourceInfo {
+        &self.source_info
+    }
 }
 
 impl TryFrom<&str> for Program {
@@ -292,6 +441,16 @@ mod tests {
                 "{null: true}",
                 ExecutionError::unsupported_key_type(Value::Null),
             ),
+            (
+                "comprehension over a scalar",
+                "1.all(x, x > 0)",
+                ExecutionError::NoSuchOverload,
+            ),
+            (
+                "two variable comprehension over a string",
+                "'abc'.exists(i, c, c == 'a')",
+                ExecutionError::NoSuchOverload,
+            ),
         ];
 
         for (name, script, error) in tests {
@@ -301,4 +460,41 @@ mod tests {
             assert_eq!(res, error.into(), "{name}");
         }
     }
+
+    #[test]
+    fn located_errors() {
+        let tests = [
+            ("foo.baz", (1, 4)),
+            ("1 + foo.bar / 0", (1, 13)),
+            ("size(foo.baz) > 0", (1, 9)),
+            ("foo.bar + foo.baz", (1, 14)),
+            ("[1, 2].map(x, x / (x - 1))", (1, 17)),
+            ("[1, 2].all(x, x / 0 > 1)", (1, 17)),
+            ("1 / 0 > 1 || false", (1, 3)),
+            ("foo.bar == 1 &&\n  missing", (2, 3)),
+            ("false ? foo.bar / 0 : foo.bar / 0", (1, 31)),
+            ("(foo.bar / 0 > 1 || true) && foo.bar / 0 > 2", (1, 38)),
+        ];
+
+        for (script, pos) in tests {
+            let mut ctx = Context::default();
+            ctx.add_variable_from_value("foo", HashMap::from([("bar", 1)]));
+            let program = Program::compile(script).unwrap();
+            let error = program.execute_located(&ctx).unwrap_err();
+            assert_eq!(Err(error.error.clone()), program.execute(&ctx), "{script}");
+            assert_eq!(error.pos, pos, "{script}");
+        }
+    }
+
+    #[test]
+    fn located_error_display() {
+        let program = Program::compile("[1, 2].map(x, x / (x - 1))").unwrap();
+        let error = program.execute_located(&Context::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "ERROR: <input>:1:17: Division by zero of Int(1)\n\
+             | [1, 2].map(x, x / (x - 1))\n\
+             | ................^"
+        );
+    }
 }
@@ -0,0 +1,44 @@
+use crate::objects::ResolveResult;
+use crate::ExecutionError;
+use std::sync::Mutex;
+
+/// Records the expressions raising the errors of an execution, see
+/// [`crate::Program::execute_located`].
+///
+/// Every evaluated expression reports its result, see [`Value::resolve`](crate::Value::resolve):
+/// a failing expression is recorded as the location of its error unless one of its children
+/// already failed with it, and the errors raised by the children of an expression which
+/// succeeds, e.g. `1 / 0 > 1 || true`, are forgotten.
+#[derive(Debug, Default)]
+pub struct ErrorLocations {
+    raised: Mutex<Vec<(u64, ExecutionError)>>,
+}
+
+impl ErrorLocations {
+    /// Returns the mark to pass to [`ErrorLocations::record`] once the expression is evaluated.
+    pub(crate) fn mark(&self) -> usize {
+        self.raised.lock().unwrap().len()
+    }
+
+    /// Records the result of the evaluation of the expression `id`.
+    pub(crate) fn record(&self, id: u64, mark: usize, result: &ResolveResult) {
+        let mut raised = self.raised.lock().unwrap();
+        match result {
+            Ok(_) => raised.truncate(mark),
+            Err(error) => {
+                if !raised[mark..].iter().any(|(_, raised)| raised == error) {
+                    raised.push((id, error.clone()));
+                }
+            }
+        }
+    }
+
+    /// Returns the id of the expression which raised `error`.
+    pub(crate) fn locate(&self, error: &ExecutionError) -> Option<u64> {
+        let raised = self.raised.lock().unwrap();
+        raised
+            .iter()
+            .find(|(_, raised)| raised == error)
+            .map(|(id, _)| *id)
+    }
+}
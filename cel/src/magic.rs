@@ -60,6 +60,12 @@ impl IntoResolveResult for String {
     }
 }
 
+impl IntoResolveResult for Result<String, ExecutionError> {
+    fn into_resolve_result(self) -> ResolveResult {
+        self.map(|s| Value::String(Arc::new(s)))
+    }
+}
+
 impl IntoResolveResult for Result<Value, ExecutionError> {
     fn into_resolve_result(self) -> ResolveResult {
         self
@@ -139,10 +145,11 @@ where
     {
         if let Some(ref this) = ctx.this {
             Ok(This(T::from_value(this)?))
-        } else {
-            let arg = arg_value_from_context(ctx)
-                .map_err(|_| ExecutionError::missing_argument_or_target())?;
+        } else if ctx.arg_idx < ctx.args.len() {
+            let arg = arg_value_from_context(ctx)?;
             Ok(This(T::from_value(&arg)?))
+        } else {
+            Err(ExecutionError::missing_argument_or_target())
         }
     }
 }
@@ -303,9 +310,11 @@ impl_handler!(C1, C2, C3, C4, C5, C6, C7, C8, C9);
 // Heavily inspired by https://users.rust-lang.org/t/common-data-type-for-functions-with-different-parameters-e-g-axum-route-handlers/90207/6
 // and https://play.rust-lang.org/?version=stable&mode=debug&edition=2021&gist=c6744c27c2358ec1d1196033a0ec11e4
 
-#[derive(Default)]
+/// Functions are reference counted so that a registry can be cheaply cloned into every
+/// [`crate::Context`] created from an [`crate::Env`].
+#[derive(Clone, Default)]
 pub struct FunctionRegistry {
-    functions: BTreeMap<String, Function>,
+    functions: BTreeMap<String, Arc<Function>>,
 }
 
 impl FunctionRegistry {
@@ -315,11 +324,24 @@ impl FunctionRegistry {
         T: 'static,
     {
         self.functions
-            .insert(name.to_string(), function.into_function());
+            .insert(name.to_string(), Arc::new(function.into_function()));
     }
 
     pub(crate) fn get(&self, name: &str) -> Option<&Function> {
-        self.functions.get(name)
+        self.functions.get(name).map(|f| &**f)
+    }
+
+    pub(crate) fn get_shared(&self, name: &str) -> Option<Arc<Function>> {
+        self.functions.get(name).cloned()
+    }
+
+    pub(crate) fn insert(&mut self, name: &str, function: Arc<Function>) {
+        self.functions.insert(name.to_string(), function);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.functions.keys().map(String::as_str)
     }
 }
 
@@ -1,10 +1,15 @@
 use crate::common::ast::{operators, EntryExpr, Expr};
 use crate::context::Context;
+use crate::cost;
 use crate::functions::FunctionContext;
+use crate::magic::Function;
+use crate::parser;
+use crate::structs::Struct;
+use crate::unknown::{self, Unknown};
 use crate::{ExecutionError, Expression};
 use std::any::Any;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::{Infallible, TryFrom, TryInto};
 use std::fmt::{Debug, Display, Formatter};
 use std::ops;
@@ -321,6 +326,12 @@ pub enum Value {
     #[cfg(feature = "chrono")]
     Timestamp(chrono::DateTime<chrono::FixedOffset>),
     Opaque(Arc<dyn Opaque>),
+    Struct(Struct),
+    /// An optional value, e.g. `optional.of(1)`, `optional.none()`, or the result of `a.?b`.
+    Optional(Option<Box<Value>>),
+    /// The result of an expression depending on attributes which were marked unknown with
+    /// [`Context::add_unknown`].
+    Unknown(Unknown),
     Null,
 }
 
@@ -341,6 +352,9 @@ impl Debug for Value {
             #[cfg(feature = "chrono")]
             Value::Timestamp(t) => write!(f, "Timestamp({:?})", t),
             Value::Opaque(o) => write!(f, "Opaque<{}>({:?})", o.runtime_type_name(), o.as_debug()),
+            Value::Struct(s) => write!(f, "Struct<{}>({:?})", s.type_name(), s.fields()),
+            Value::Optional(o) => write!(f, "Optional({:?})", o),
+            Value::Unknown(u) => write!(f, "Unknown({:?})", u.ids().collect::<Vec<_>>()),
             Value::Null => write!(f, "Null"),
         }
     }
@@ -375,6 +389,9 @@ pub enum ValueType {
     Duration,
     Timestamp,
     Opaque,
+    Struct,
+    Optional,
+    Unknown,
     Null,
 }
 
@@ -391,6 +408,9 @@ impl Display for ValueType {
             ValueType::Bytes => write!(f, "bytes"),
             ValueType::Bool => write!(f, "bool"),
             ValueType::Opaque => write!(f, "opaque"),
+            ValueType::Struct => write!(f, "struct"),
+            ValueType::Optional => write!(f, "optional_type"),
+            ValueType::Unknown => write!(f, "unknown"),
             ValueType::Duration => write!(f, "duration"),
             ValueType::Timestamp => write!(f, "timestamp"),
             ValueType::Null => write!(f, "null"),
@@ -411,6 +431,9 @@ impl Value {
             Value::Bytes(_) => ValueType::Bytes,
             Value::Bool(_) => ValueType::Bool,
             Value::Opaque(_) => ValueType::Opaque,
+            Value::Struct(_) => ValueType::Struct,
+            Value::Optional(_) => ValueType::Optional,
+            Value::Unknown(_) => ValueType::Unknown,
             #[cfg(feature = "chrono")]
             Value::Duration(_) => ValueType::Duration,
             #[cfg(feature = "chrono")]
@@ -466,6 +489,9 @@ impl PartialEq for Value {
             (Value::Float(a), Value::Int(b)) => *a == (*b as f64),
             (Value::Float(a), Value::UInt(b)) => *a == (*b as f64),
             (Value::Opaque(a), Value::Opaque(b)) => a.opaque_eq(b.deref()),
+            (Value::Struct(a), Value::Struct(b)) => a == b,
+            (Value::Optional(a), Value::Optional(b)) => a == b,
+            (Value::Unknown(a), Value::Unknown(b)) => a == b,
             (_, _) => false,
         }
     }
@@ -607,11 +633,25 @@ impl Value {
 
     #[inline(always)]
     pub fn resolve(expr: &Expression, ctx: &Context) -> ResolveResult {
+        let Some(errors) = ctx.error_locations() else {
+            return Value::resolve_expr(expr, ctx);
+        };
+        let mark = errors.mark();
+        let result = Value::resolve_expr(expr, ctx);
+        errors.record(expr.id, mark, &result);
+        result
+    }
+
+    fn resolve_expr(expr: &Expression, ctx: &Context) -> ResolveResult {
+        ctx.charge(cost::expr_cost(&expr.expr))?;
         match &expr.expr {
             Expr::Literal(val) => Ok(val.clone().into()),
             Expr::Call(call) => {
                 if call.args.len() == 3 && call.func_name == operators::CONDITIONAL {
                     let cond = Value::resolve(&call.args[0], ctx)?;
+                    if let Value::Unknown(_) = cond {
+                        return Ok(cond);
+                    }
                     return if cond.to_bool()? {
                         Value::resolve(&call.args[1], ctx)
                     } else {
@@ -620,180 +660,77 @@ impl Value {
                 }
                 if call.args.len() == 2 {
                     match call.func_name.as_str() {
-                        operators::ADD => {
-                            return Value::resolve(&call.args[0], ctx)?
-                                + Value::resolve(&call.args[1], ctx)?
-                        }
-                        operators::SUBSTRACT => {
-                            return Value::resolve(&call.args[0], ctx)?
-                                - Value::resolve(&call.args[1], ctx)?
-                        }
-                        operators::DIVIDE => {
-                            return Value::resolve(&call.args[0], ctx)?
-                                / Value::resolve(&call.args[1], ctx)?
-                        }
-                        operators::MULTIPLY => {
-                            return Value::resolve(&call.args[0], ctx)?
-                                * Value::resolve(&call.args[1], ctx)?
-                        }
-                        operators::MODULO => {
-                            return Value::resolve(&call.args[0], ctx)?
-                                % Value::resolve(&call.args[1], ctx)?
-                        }
-                        operators::EQUALS => {
-                            return Value::Bool(
-                                Value::resolve(&call.args[0], ctx)?
-                                    .eq(&Value::resolve(&call.args[1], ctx)?),
-                            )
-                            .into()
-                        }
-                        operators::NOT_EQUALS => {
-                            return Value::Bool(
-                                Value::resolve(&call.args[0], ctx)?
-                                    .ne(&Value::resolve(&call.args[1], ctx)?),
-                            )
-                            .into()
-                        }
-                        operators::LESS => {
-                            let left = Value::resolve(&call.args[0], ctx)?;
-                            let right = Value::resolve(&call.args[1], ctx)?;
-                            return Value::Bool(
-                                left.partial_cmp(&right)
-                                    .ok_or(ExecutionError::ValuesNotComparable(left, right))?
-                                    == Ordering::Less,
-                            )
-                            .into();
-                        }
-                        operators::LESS_EQUALS => {
-                            let left = Value::resolve(&call.args[0], ctx)?;
-                            let right = Value::resolve(&call.args[1], ctx)?;
-                            return Value::Bool(
-                                left.partial_cmp(&right)
-                                    .ok_or(ExecutionError::ValuesNotComparable(left, right))?
-                                    != Ordering::Greater,
-                            )
-                            .into();
-                        }
-                        operators::GREATER => {
-                            let left = Value::resolve(&call.args[0], ctx)?;
-                            let right = Value::resolve(&call.args[1], ctx)?;
-                            return Value::Bool(
-                                left.partial_cmp(&right)
-                                    .ok_or(ExecutionError::ValuesNotComparable(left, right))?
-                                    == Ordering::Greater,
-                            )
-                            .into();
-                        }
-                        operators::GREATER_EQUALS => {
-                            let left = Value::resolve(&call.args[0], ctx)?;
-                            let right = Value::resolve(&call.args[1], ctx)?;
-                            return Value::Bool(
-                                left.partial_cmp(&right)
-                                    .ok_or(ExecutionError::ValuesNotComparable(left, right))?
-                                    != Ordering::Less,
-                            )
-                            .into();
-                        }
-                        operators::IN => {
-                            let left = Value::resolve(&call.args[0], ctx)?;
-                            let right = Value::resolve(&call.args[1], ctx)?;
-                            match (left, right) {
-                                (Value::String(l), Value::String(r)) => {
-                                    return Value::Bool(r.contains(&*l)).into()
-                                }
-                                (any, Value::List(v)) => {
-                                    return Value::Bool(v.contains(&any)).into()
-                                }
-                                (any, Value::Map(m)) => match any.try_into() {
-                                    Ok(key) => return Value::Bool(m.map.contains_key(&key)).into(),
-                                    Err(_) => return Value::Bool(false).into(),
-                                },
-                                (left, right) => {
-                                    Err(ExecutionError::ValuesNotComparable(left, right))?
-                                }
-                            }
-                        }
                         operators::LOGICAL_OR => {
-                            let left = Value::resolve(&call.args[0], ctx)?;
-                            return if left.to_bool()? {
-                                left.into()
-                            } else {
-                                Value::resolve(&call.args[1], ctx)
-                            };
+                            return Value::resolve_logical(&call.args, true, ctx);
                         }
                         operators::LOGICAL_AND => {
-                            let left = Value::resolve(&call.args[0], ctx)?;
-                            return if !left.to_bool()? {
-                                Value::Bool(false)
-                            } else {
-                                let right = Value::resolve(&call.args[1], ctx)?;
-                                Value::Bool(right.to_bool()?)
-                            }
-                            .into();
+                            return Value::resolve_logical(&call.args, false, ctx);
                         }
-                        operators::INDEX => {
+                        operators::OPT_SELECT => {
+                            if let Some(unknown) = unknown::unknown_attribute(expr, ctx) {
+                                return Ok(unknown);
+                            }
                             let value = Value::resolve(&call.args[0], ctx)?;
-                            let idx = Value::resolve(&call.args[1], ctx)?;
-                            return match (value, idx) {
-                                (Value::List(items), Value::Int(idx)) => {
-                                    if idx >= 0 && (idx as usize) < items.len() {
-                                        items[idx as usize].clone().into()
-                                    } else {
-                                        Err(ExecutionError::IndexOutOfBounds(idx.into()))
-                                    }
-                                }
-                                (Value::List(items), Value::UInt(idx)) => {
-                                    if (idx as usize) < items.len() {
-                                        items[idx as usize].clone().into()
-                                    } else {
-                                        Err(ExecutionError::IndexOutOfBounds(idx.into()))
-                                    }
-                                }
-                                (Value::String(_), Value::Int(idx)) => {
-                                    Err(ExecutionError::NoSuchKey(idx.to_string().into()))
-                                }
-                                (Value::Map(map), Value::String(property)) => map
-                                    .get(&property.into())
-                                    .cloned()
-                                    .unwrap_or(Value::Null)
-                                    .into(),
-                                (Value::Map(map), Value::Bool(property)) => map
-                                    .get(&property.into())
-                                    .cloned()
-                                    .unwrap_or(Value::Null)
-                                    .into(),
-                                (Value::Map(map), Value::Int(property)) => map
-                                    .get(&property.into())
-                                    .cloned()
-                                    .unwrap_or(Value::Null)
-                                    .into(),
-                                (Value::Map(map), Value::UInt(property)) => map
-                                    .get(&property.into())
-                                    .cloned()
-                                    .unwrap_or(Value::Null)
-                                    .into(),
-                                (Value::Map(_), index) => {
-                                    Err(ExecutionError::UnsupportedMapIndex(index))
-                                }
-                                (Value::List(_), index) => {
-                                    Err(ExecutionError::UnsupportedListIndex(index))
-                                }
-                                (value, index) => {
-                                    Err(ExecutionError::UnsupportedIndex(value, index))
+                            return match &call.args[1].expr {
+                                Expr::Literal(CelVal::String(field)) => {
+                                    value.optional_member(field)
                                 }
+                                _ => Err(ExecutionError::UnsupportedFunctionCallIdentifierType(
+                                    call.args[1].clone(),
+                                )),
                             };
                         }
+                        operators::ADD
+                        | operators::SUBSTRACT
+                        | operators::DIVIDE
+                        | operators::MULTIPLY
+                        | operators::MODULO
+                        | operators::EQUALS
+                        | operators::NOT_EQUALS
+                        | operators::LESS
+                        | operators::LESS_EQUALS
+                        | operators::GREATER
+                        | operators::GREATER_EQUALS
+                        | operators::IN
+                        | operators::INDEX
+                        | operators::OPT_INDEX => {
+                            if let Some(unknown) = unknown::unknown_attribute(expr, ctx) {
+                                return Ok(unknown);
+                            }
+                            if call.func_name == operators::INDEX {
+                                if let Some(value) = Value::resolve_attribute(expr, ctx) {
+                                    return Ok(value);
+                                }
+                            }
+                            let left = Value::resolve(&call.args[0], ctx)?;
+                            let right = Value::resolve(&call.args[1], ctx)?;
+                            if let Some(unknown) = Unknown::merge([&left, &right]) {
+                                return Ok(Value::Unknown(unknown));
+                            }
+                            if !matches!(
+                                call.func_name.as_str(),
+                                operators::INDEX | operators::OPT_INDEX
+                            ) {
+                                ctx.charge(
+                                    cost::traversal_cost(&left) + cost::traversal_cost(&right),
+                                )?;
+                            }
+                            return Value::binary_operator(&call.func_name, left, right);
+                        }
                         _ => (),
                     }
                 }
                 if call.args.len() == 1 {
                     match call.func_name.as_str() {
                         operators::LOGICAL_NOT => {
-                            let expr = Value::resolve(&call.args[0], ctx)?;
-                            return Ok(Value::Bool(!expr.to_bool()?));
+                            return match Value::resolve(&call.args[0], ctx)? {
+                                Value::Unknown(unknown) => Ok(Value::Unknown(unknown)),
+                                value => Ok(Value::Bool(!value.to_bool()?)),
+                            };
                         }
                         operators::NEGATE => {
                             return match Value::resolve(&call.args[0], ctx)? {
+                                Value::Unknown(unknown) => Ok(Value::Unknown(unknown)),
                                 Value::Int(i) => Ok(Value::Int(-i)),
                                 Value::Float(f) => Ok(Value::Float(-f)),
                                 value => {
@@ -802,35 +739,55 @@ impl Value {
                             }
                         }
                         operators::NOT_STRICTLY_FALSE => {
-                            return match Value::resolve(&call.args[0], ctx)? {
-                                Value::Bool(b) => Ok(Value::Bool(b)),
+                            return match Value::resolve(&call.args[0], ctx) {
+                                Ok(Value::Bool(b)) => Ok(Value::Bool(b)),
                                 _ => Ok(Value::Bool(true)),
                             }
                         }
                         _ => (),
                     }
                 }
+                if call.func_name == operators::MAP_INSERT {
+                    return Value::map_insert(&call.args, ctx);
+                }
+                // Functions can be namespaced, e.g. `optional.of(x)`, in which case the target
+                // is the namespace rather than the receiver of a method call.
+                if let Some(namespace) = call.target.as_ref().and_then(|t| t.to_qualified_name()) {
+                    let name = format!("{namespace}.{}", call.func_name);
+                    if let Some(func) = ctx.get_function(&name) {
+                        return Value::call_function(func, &call.func_name, None, ctx, &call.args);
+                    }
+                }
                 let func = ctx.get_function(call.func_name.as_str()).ok_or_else(|| {
                     ExecutionError::UndeclaredReference(call.func_name.clone().into())
                 })?;
-                match &call.target {
-                    None => {
-                        let mut ctx = FunctionContext::new(&call.func_name, None, ctx, &call.args);
-                        (func)(&mut ctx)
-                    }
-                    Some(target) => {
-                        let mut ctx = FunctionContext::new(
-                            &call.func_name,
-                            Some(Value::resolve(target, ctx)?),
-                            ctx,
-                            &call.args,
-                        );
-                        (func)(&mut ctx)
+                let this = match &call.target {
+                    None => None,
+                    Some(target) => match Value::resolve(target, ctx)? {
+                        Value::Unknown(unknown) => return Ok(Value::Unknown(unknown)),
+                        this => Some(this),
+                    },
+                };
+                if let Some(this) = &this {
+                    if cost::traverses(&call.func_name) {
+                        ctx.charge(cost::traversal_cost(this))?;
                     }
                 }
+                Value::call_function(func, &call.func_name, this, ctx, &call.args)
             }
-            Expr::Ident(name) => ctx.get_variable(name),
+            Expr::Ident(name) => match unknown::unknown_attribute(expr, ctx) {
+                Some(unknown) => Ok(unknown),
+                None => ctx.get_variable(name),
+            },
             Expr::Select(select) => {
+                if let Some(unknown) = unknown::unknown_attribute(expr, ctx) {
+                    return Ok(unknown);
+                }
+                if !select.test {
+                    if let Some(value) = Value::resolve_attribute(expr, ctx) {
+                        return Ok(value);
+                    }
+                }
                 let left = Value::resolve(select.operand.deref(), ctx)?;
                 if select.test {
                     match &left {
@@ -842,6 +799,8 @@ impl Value {
                             }
                             Ok(Value::Bool(false))
                         }
+                        Value::Struct(s) => Ok(Value::Bool(s.has(&select.field))),
+                        Value::Unknown(_) => Ok(left),
                         _ => Ok(Value::Bool(false)),
                     }
                 } else {
@@ -849,67 +808,425 @@ impl Value {
                 }
             }
             Expr::List(list_expr) => {
-                let list = list_expr
-                    .elements
-                    .iter()
-                    .map(|i| Value::resolve(i, ctx))
-                    .collect::<Result<Vec<_>, _>>()?;
-                Value::List(list.into()).into()
+                let mut list = Vec::with_capacity(list_expr.elements.len());
+                for (i, element) in list_expr.elements.iter().enumerate() {
+                    let value = Value::resolve(element, ctx)?;
+                    if list_expr.optional_indices.contains(&i)
+                        && !matches!(value, Value::Unknown(_))
+                    {
+                        list.extend(value.into_optional()?);
+                    } else {
+                        list.push(value);
+                    }
+                }
+                match Unknown::merge(&list) {
+                    Some(unknown) => Ok(Value::Unknown(unknown)),
+                    None => Ok(Value::List(list.into())),
+                }
             }
             Expr::Map(map_expr) => {
                 let mut map = HashMap::with_capacity(map_expr.entries.len());
+                let mut unknowns = Vec::new();
                 for entry in map_expr.entries.iter() {
-                    let (k, v) = match &entry.expr {
+                    let (k, v, optional) = match &entry.expr {
                         EntryExpr::StructField(_) => panic!("WAT?"),
-                        EntryExpr::MapEntry(e) => (&e.key, &e.value),
+                        EntryExpr::MapEntry(e) => (&e.key, &e.value, e.optional),
                     };
-                    let key = Value::resolve(k, ctx)?
-                        .try_into()
-                        .map_err(ExecutionError::UnsupportedKeyType)?;
+                    let key = Value::resolve(k, ctx)?;
                     let value = Value::resolve(v, ctx)?;
-                    map.insert(key, value);
+                    if let Some(entry) = Unknown::merge([&key, &value]) {
+                        unknowns.push(Value::Unknown(entry));
+                        continue;
+                    }
+                    let key = key.try_into().map_err(ExecutionError::UnsupportedKeyType)?;
+                    if optional {
+                        if let Some(value) = value.into_optional()? {
+                            map.insert(key, value);
+                        }
+                    } else {
+                        map.insert(key, value);
+                    }
+                }
+                if let Some(unknown) = Unknown::merge(&unknowns) {
+                    return Ok(Value::Unknown(unknown));
                 }
                 Ok(Value::Map(Map {
                     map: Arc::from(map),
                 }))
             }
+            Expr::Comprehension(comprehension) if parser::is_block_binding(comprehension) => {
+                let ctx = ctx.new_lazy_scope(&comprehension.accu_var, &comprehension.accu_init);
+                Value::resolve(&comprehension.result, &ctx)
+            }
             Expr::Comprehension(comprehension) => {
                 let accu_init = Value::resolve(&comprehension.accu_init, ctx)?;
                 let iter = Value::resolve(&comprehension.iter_range, ctx)?;
+                if let Some(unknown) = Unknown::merge([&accu_init, &iter]) {
+                    return Ok(Value::Unknown(unknown));
+                }
                 let mut ctx = ctx.new_inner_scope();
                 ctx.add_variable(&comprehension.accu_var, accu_init)
                     .expect("Failed to add accu variable");
 
-                match iter {
-                    Value::List(items) => {
-                        for item in items.deref() {
-                            if !Value::resolve(&comprehension.loop_cond, &ctx)?.to_bool()? {
-                                break;
-                            }
-                            ctx.add_variable_from_value(&comprehension.iter_var, item.clone());
-                            let accu = Value::resolve(&comprehension.loop_step, &ctx)?;
-                            ctx.add_variable_from_value(&comprehension.accu_var, accu);
+                // The `all` and `exists` macros fold the predicate into the accumulator with
+                // `_&&_` or `_||_`. Errors of such steps are kept aside rather than returned
+                // right away, as a later step may still decide the result.
+                let absorbing = match &comprehension.loop_step.expr {
+                    Expr::Call(call)
+                        if call.args.len() == 2
+                            && matches!(&call.args[0].expr, Expr::Ident(name) if *name == comprehension.accu_var) =>
+                    {
+                        match call.func_name.as_str() {
+                            operators::LOGICAL_AND => Some(false),
+                            operators::LOGICAL_OR => Some(true),
+                            _ => None,
                         }
                     }
-                    Value::Map(map) => {
-                        for key in map.map.deref().keys() {
-                            if !Value::resolve(&comprehension.loop_cond, &ctx)?.to_bool()? {
-                                break;
+                    _ => None,
+                };
+                let items = iter
+                    .iteration_variables(comprehension.iter_var2.is_some())
+                    .ok_or(ExecutionError::NoSuchOverload)?;
+                let mut error = None;
+                for (item, item2) in items {
+                    ctx.charge(1)?;
+                    if !Value::resolve(&comprehension.loop_cond, &ctx)?.to_bool()? {
+                        break;
+                    }
+                    ctx.add_variable_from_value(&comprehension.iter_var, item);
+                    if let (Some(var), Some(item)) = (&comprehension.iter_var2, item2) {
+                        ctx.add_variable_from_value(var, item);
+                    }
+                    match Value::resolve(&comprehension.loop_step, &ctx) {
+                        Ok(accu) => {
+                            if absorbing.is_some_and(|b| accu == Value::Bool(b)) {
+                                error = None;
                             }
-                            ctx.add_variable_from_value(&comprehension.iter_var, key.clone());
-                            let accu = Value::resolve(&comprehension.loop_step, &ctx)?;
                             ctx.add_variable_from_value(&comprehension.accu_var, accu);
                         }
+                        Err(err) if absorbing.is_some() => {
+                            error.get_or_insert(err);
+                        }
+                        Err(err) => return Err(err),
                     }
-                    t => todo!("Support {t:?}"),
                 }
-                Value::resolve(&comprehension.result, &ctx)
+                let result = Value::resolve(&comprehension.result, &ctx)?;
+                match error {
+                    // Unknowns take precedence over errors, as in `_&&_` and `_||_`.
+                    Some(err) if !matches!(result, Value::Unknown(_)) => Err(err),
+                    _ => Ok(result),
+                }
+            }
+            Expr::Struct(struct_expr) => {
+                let ty = ctx
+                    .find_struct_type(&struct_expr.type_name)
+                    .ok_or_else(|| ExecutionError::undeclared_reference(&struct_expr.type_name))?;
+                let mut fields = BTreeMap::new();
+                let mut unknowns = Vec::new();
+                for entry in struct_expr.entries.iter() {
+                    let field = match &entry.expr {
+                        EntryExpr::StructField(field) => field,
+                        EntryExpr::MapEntry(_) => panic!("WAT?"),
+                    };
+                    let value = Value::resolve(&field.value, ctx)?;
+                    if let Value::Unknown(_) = value {
+                        unknowns.push(value);
+                    } else if field.optional {
+                        if let Some(value) = value.into_optional()? {
+                            fields.insert(field.field.clone(), value);
+                        }
+                    } else {
+                        fields.insert(field.field.clone(), value);
+                    }
+                }
+                if let Some(unknown) = Unknown::merge(&unknowns) {
+                    return Ok(Value::Unknown(unknown));
+                }
+                Ok(Value::Struct(Struct::new(ty, fields)?))
             }
-            Expr::Struct(_) => todo!("Support structs!"),
             Expr::Unspecified => panic!("Can't evaluate Unspecified Expr"),
         }
     }
 
+    /// Calls a registered function. When the context has unknown attributes, the arguments
+    /// are resolved upfront so that the call evaluates to their unknowns if any.
+    fn call_function(
+        func: &Function,
+        name: &str,
+        this: Option<Value>,
+        ctx: &Context,
+        args: &[Expression],
+    ) -> ResolveResult {
+        let mut ftx = FunctionContext::new(name, this, ctx, args);
+        let result = func(&mut ftx);
+        // Functions are strict: the result is unknown if any argument resolved as a value is.
+        if let Some(unknown) = ftx.unknown.into_inner() {
+            return Ok(Value::Unknown(unknown));
+        }
+        let value = result?;
+        // Charge for the size of the result, less what the function charged upfront for it.
+        ctx.charge(cost::traversal_cost(&value).saturating_sub(ftx.charged.get()))?;
+        Ok(value)
+    }
+
+    /// Returns the values taken by the variables of a comprehension iterating over `self`: the
+    /// elements of a list or the keys of a map, or with `two_vars`, the (index, element) pairs
+    /// of a list or the (key, value) pairs of a map.
+    pub(crate) fn iteration_variables(
+        &self,
+        two_vars: bool,
+    ) -> Option<Box<dyn Iterator<Item = (Value, Option<Value>)> + '_>> {
+        match self {
+            Value::List(items) if two_vars => Some(Box::new(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| (Value::Int(i as i64), Some(item.clone()))),
+            )),
+            Value::List(items) => Some(Box::new(items.iter().map(|item| (item.clone(), None)))),
+            Value::Map(map) if two_vars => {
+                Some(Box::new(map.map.iter().map(|(key, value)| {
+                    (key.clone().into(), Some(value.clone()))
+                })))
+            }
+            Value::Map(map) => Some(Box::new(
+                map.map.keys().map(|key| (key.clone().into(), None)),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Evaluates `cel.@mapInsert(map, key, value)` or `cel.@mapInsert(map, entries)`, which
+    /// accumulate the results of `transformMap` and `transformMapEntry`.
+    fn map_insert(args: &[Expression], ctx: &Context) -> ResolveResult {
+        let values = args
+            .iter()
+            .map(|arg| Value::resolve(arg, ctx))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(unknown) = Unknown::merge(&values) {
+            return Ok(Value::Unknown(unknown));
+        }
+        let mut values = values.into_iter();
+        let Some(Value::Map(accu)) = values.next() else {
+            return Err(ExecutionError::NoSuchOverload);
+        };
+        let entries: Vec<(Key, Value)> = match (values.next(), values.next()) {
+            (Some(key), Some(value)) => vec![(
+                key.try_into().map_err(ExecutionError::UnsupportedKeyType)?,
+                value,
+            )],
+            (Some(Value::Map(entries)), None) => entries
+                .map
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            (Some(value), None) => {
+                return Err(ExecutionError::UnexpectedType {
+                    got: value.type_of().to_string(),
+                    want: ValueType::Map.to_string(),
+                })
+            }
+            _ => return Err(ExecutionError::NoSuchOverload),
+        };
+        let mut map = (*accu.map).clone();
+        for (key, value) in entries {
+            if map.contains_key(&key) {
+                return Err(ExecutionError::function_error(
+                    operators::MAP_INSERT,
+                    format!("insert failed: key {key} already exists"),
+                ));
+            }
+            map.insert(key, value);
+        }
+        Ok(Value::Map(Map { map: Arc::new(map) }))
+    }
+
+    /// Applies one of the strict binary operators, i.e. those which need the value of both of
+    /// their operands.
+    fn binary_operator(op: &str, left: Value, right: Value) -> ResolveResult {
+        match op {
+            operators::ADD => left + right,
+            operators::SUBSTRACT => left - right,
+            operators::DIVIDE => left / right,
+            operators::MULTIPLY => left * right,
+            operators::MODULO => left % right,
+            operators::EQUALS => Ok(Value::Bool(left.eq(&right))),
+            operators::NOT_EQUALS => Ok(Value::Bool(left.ne(&right))),
+            operators::LESS
+            | operators::LESS_EQUALS
+            | operators::GREATER
+            | operators::GREATER_EQUALS => {
+                let ordering = left
+                    .partial_cmp(&right)
+                    .ok_or(ExecutionError::ValuesNotComparable(left, right))?;
+                Ok(Value::Bool(match op {
+                    operators::LESS => ordering == Ordering::Less,
+                    operators::LESS_EQUALS => ordering != Ordering::Greater,
+                    operators::GREATER => ordering == Ordering::Greater,
+                    _ => ordering != Ordering::Less,
+                }))
+            }
+            operators::IN => match (left, right) {
+                (Value::String(l), Value::String(r)) => Ok(Value::Bool(r.contains(&*l))),
+                (any, Value::List(v)) => Ok(Value::Bool(v.contains(&any))),
+                (any, Value::Map(m)) => match any.try_into() {
+                    Ok(key) => Ok(Value::Bool(m.map.contains_key(&key))),
+                    Err(_) => Ok(Value::Bool(false)),
+                },
+                (left, right) => Err(ExecutionError::ValuesNotComparable(left, right)),
+            },
+            operators::INDEX => left.index(right),
+            operators::OPT_INDEX => left.optional_index(right),
+            _ => unreachable!("{op} is not a binary operator"),
+        }
+    }
+
+    /// Resolves an attribute, e.g. `a.b[0]`, with the variable resolvers of the context, as
+    /// long as they can resolve it without resolving its variable.
+    fn resolve_attribute(expr: &Expression, ctx: &Context) -> Option<Value> {
+        if !ctx.has_variable_resolver() {
+            return None;
+        }
+        let mut qualifiers = Vec::new();
+        let variable = unknown::attribute(expr, &mut qualifiers, false)?;
+        ctx.resolve_attribute(variable, &qualifiers)
+    }
+
+    /// Evaluates `_||_` (when `absorbing` is `true`) or `_&&_` (when it's `false`).
+    ///
+    /// Either side evaluating to the absorbing value decides the result, even if the other
+    /// side fails or is unknown: `1/0 > 1 || true` is `true`. Otherwise, the unknowns of both
+    /// sides are returned if any, and then the error of the left side takes precedence.
+    fn resolve_logical(args: &[Expression], absorbing: bool, ctx: &Context) -> ResolveResult {
+        let left = Value::resolve(&args[0], ctx);
+        if matches!(left, Ok(Value::Bool(b)) if b == absorbing) {
+            return Ok(Value::Bool(absorbing));
+        }
+        let right = Value::resolve(&args[1], ctx);
+        if matches!(right, Ok(Value::Bool(b)) if b == absorbing) {
+            return Ok(Value::Bool(absorbing));
+        }
+        if let Some(unknown) = Unknown::merge(left.iter().chain(right.iter())) {
+            return Ok(Value::Unknown(unknown));
+        }
+        match (
+            left.and_then(|v| v.to_bool()),
+            right.and_then(|v| v.to_bool()),
+        ) {
+            (Err(err), _) | (_, Err(err)) => Err(err),
+            _ => Ok(Value::Bool(!absorbing)),
+        }
+    }
+
+    fn index(self, idx: Value) -> ResolveResult {
+        match (self, idx) {
+            (Value::Unknown(unknown), _) => Ok(Value::Unknown(unknown)),
+            (Value::Optional(None), _) => Ok(Value::Optional(None)),
+            // Indexing an optional value, e.g. `x.?a[0]`, is optional all along the chain.
+            (Value::Optional(Some(value)), idx) => value.optional_index(idx),
+            (Value::List(items), Value::Int(idx)) => {
+                if idx >= 0 && (idx as usize) < items.len() {
+                    items[idx as usize].clone().into()
+                } else {
+                    Err(ExecutionError::IndexOutOfBounds(idx.into()))
+                }
+            }
+            (Value::List(items), Value::UInt(idx)) => {
+                if (idx as usize) < items.len() {
+                    items[idx as usize].clone().into()
+                } else {
+                    Err(ExecutionError::IndexOutOfBounds(idx.into()))
+                }
+            }
+            (Value::String(_), Value::Int(idx)) => {
+                Err(ExecutionError::NoSuchKey(idx.to_string().into()))
+            }
+            (Value::Map(map), Value::String(property)) => map
+                .get(&property.into())
+                .cloned()
+                .unwrap_or(Value::Null)
+                .into(),
+            (Value::Map(map), Value::Bool(property)) => map
+                .get(&property.into())
+                .cloned()
+                .unwrap_or(Value::Null)
+                .into(),
+            (Value::Map(map), Value::Int(property)) => map
+                .get(&property.into())
+                .cloned()
+                .unwrap_or(Value::Null)
+                .into(),
+            (Value::Map(map), Value::UInt(property)) => map
+                .get(&property.into())
+                .cloned()
+                .unwrap_or(Value::Null)
+                .into(),
+            (Value::Map(_), index) => Err(ExecutionError::UnsupportedMapIndex(index)),
+            (Value::List(_), index) => Err(ExecutionError::UnsupportedListIndex(index)),
+            (value, index) => Err(ExecutionError::UnsupportedIndex(value, index)),
+        }
+    }
+
+    /// Indexes into a list or map, returning `optional.none()` rather than an error when the
+    /// index is out of bounds or the key is missing, as in `m[?k]`.
+    fn optional_index(self, idx: Value) -> ResolveResult {
+        match (self, idx) {
+            (Value::Unknown(unknown), _) => Ok(Value::Unknown(unknown)),
+            (Value::Optional(None), _) => Ok(Value::Optional(None)),
+            (Value::Optional(Some(value)), idx) => value.optional_index(idx),
+            (Value::List(items), Value::Int(idx)) => Ok(Value::Optional(
+                usize::try_from(idx)
+                    .ok()
+                    .and_then(|idx| items.get(idx))
+                    .cloned()
+                    .map(Box::new),
+            )),
+            (Value::List(items), Value::UInt(idx)) => Ok(Value::Optional(
+                items.get(idx as usize).cloned().map(Box::new),
+            )),
+            (Value::Map(map), idx) => {
+                let key = idx
+                    .try_into()
+                    .map_err(ExecutionError::UnsupportedMapIndex)?;
+                Ok(Value::Optional(map.get(&key).cloned().map(Box::new)))
+            }
+            (Value::List(_), index) => Err(ExecutionError::UnsupportedListIndex(index)),
+            (value, index) => Err(ExecutionError::UnsupportedIndex(value, index)),
+        }
+    }
+
+    /// Selects a field, returning `optional.none()` rather than an error when the field is not
+    /// present, as in `a.?b`.
+    fn optional_member(self, name: &str) -> ResolveResult {
+        match self {
+            Value::Unknown(unknown) => Ok(Value::Unknown(unknown)),
+            Value::Optional(None) => Ok(Value::Optional(None)),
+            Value::Optional(Some(value)) => value.optional_member(name),
+            Value::Map(ref m) => Ok(Value::Optional(
+                m.map
+                    .get(&Key::String(name.to_owned().into()))
+                    .cloned()
+                    .map(Box::new),
+            )),
+            Value::Struct(ref s) if !s.has(name) && s.struct_type().field_decl(name).is_some() => {
+                Ok(Value::Optional(None))
+            }
+            value => value.member(name).map(Value::optional_of),
+        }
+    }
+
+    fn optional_of(value: Value) -> Value {
+        Value::Optional(Some(Box::new(value)))
+    }
+
+    /// Unwraps the value of an optional list element, map entry or struct field, e.g. `[?a]`.
+    fn into_optional(self) -> Result<Option<Value>, ExecutionError> {
+        match self {
+            Value::Optional(value) => Ok(value.map(|v| *v)),
+            value => Err(value.error_expected_type(ValueType::Optional)),
+        }
+    }
+
     // >> a(b)
     // Member(Ident("a"),
     //        FunctionCall([Ident("b")]))
@@ -921,12 +1238,21 @@ impl Value {
     fn member(self, name: &str) -> ResolveResult {
         // todo! Ideally we would avoid creating a String just to create a Key for lookup in the
         // map, but this would require something like the `hashbrown` crate's `Equivalent` trait.
+        match self {
+            // Selecting from an optional value, e.g. `x.?a.b`, is optional all along the chain.
+            Value::Optional(Some(value)) => return value.optional_member(name),
+            Value::Optional(None) => return Ok(Value::Optional(None)),
+            Value::Unknown(_) => return Ok(self),
+            _ => (),
+        }
+
         let name: Arc<String> = name.to_owned().into();
 
         // This will always either be because we're trying to access
         // a property on self, or a method on self.
         let child = match self {
             Value::Map(ref m) => m.map.get(&name.clone().into()).cloned(),
+            Value::Struct(ref s) => s.get(&name).cloned(),
             _ => None,
         };
 
@@ -1377,6 +1703,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_absorbing_logical_operators() {
+        for (expr, expected) in [
+            ("1 / 0 > 1 || true", true),
+            ("true || 1 / 0 > 1", true),
+            ("1 / 0 > 1 && false", false),
+            ("false && 1 / 0 > 1", false),
+            ("'a' || true", true),
+            ("{}.missing || !false", true),
+            ("[1, 0].exists(x, 1 / x > 0)", true),
+            ("[0, 1].exists(x, 1 / x > 0)", true),
+            ("[0, 1].all(x, 1 / x > 1)", false),
+            ("{0: 'a', 2: 'b'}.all(k, 4 / k < 2)", false),
+            ("[0, 1].all(x, x >= 0 || 1 / x > 0)", true),
+        ] {
+            let program = Program::compile(expr).unwrap();
+            assert_eq!(
+                program.execute(&Context::default()),
+                Ok(Value::Bool(expected)),
+                "{expr}"
+            );
+        }
+
+        for (expr, err) in [
+            (
+                "1 / 0 > 1 || false",
+                ExecutionError::DivisionByZero(1.into()),
+            ),
+            (
+                "true && 1 / 0 > 1",
+                ExecutionError::DivisionByZero(1.into()),
+            ),
+            (
+                "1 / 0 > 1 && 2 / 0 > 1",
+                ExecutionError::DivisionByZero(1.into()),
+            ),
+            (
+                "[0, 1].exists(x, 1 / x > 1)",
+                ExecutionError::DivisionByZero(1.into()),
+            ),
+            (
+                "[0, 1].all(x, 1 / x > 0)",
+                ExecutionError::DivisionByZero(1.into()),
+            ),
+            (
+                "[0, 1].map(x, 1 / x)",
+                ExecutionError::DivisionByZero(1.into()),
+            ),
+        ] {
+            test_execution_error(expr, err);
+        }
+    }
+
     #[test]
     fn invalid_int_math() {
         use ExecutionError::*;
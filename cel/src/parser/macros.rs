@@ -1,13 +1,125 @@
-use crate::common::ast::{operators, CallExpr, ComprehensionExpr, Expr, IdedExpr, ListExpr};
+use crate::common::ast::{
+    operators, CallExpr, ComprehensionExpr, EntryExpr, Expr, IdedExpr, ListExpr, MapExpr,
+};
 use crate::common::value::CelVal::{Boolean, Int};
 use crate::parser::{MacroExprHelper, ParseError};
 
+/// Expands a macro call, given its target, if called as a method, and its arguments, into the
+/// expression replacing it. New subexpressions must be created with
+/// [`MacroExprHelper::next_expr`], which gives them an id.
 pub type MacroExpander = fn(
     helper: &mut MacroExprHelper,
     target: Option<IdedExpr>,
     args: Vec<IdedExpr>,
 ) -> Result<IdedExpr, ParseError>;
 
+/// A macro expanding, at parse time, the calls to a function of a given name into another
+/// expression. Macros are registered with [`crate::parser::Parser::macros`], and take
+/// precedence over the standard macros, e.g. `has` or `all`.
+///
+/// A macro is either global, e.g. `nonEmpty(x)`, or called on a receiver, e.g.
+/// `headers.any(h, h == 'x')`, and only applies to calls with the declared number of arguments,
+/// unless it's variadic.
+///
+/// # Example
+/// ```
+/// use cel::common::ast::{operators, CallExpr, Expr, IdedExpr};
+/// use cel::common::value::CelVal;
+/// use cel::parser::{Macro, MacroExprHelper, ParseError, Parser};
+/// use cel::{Context, Program};
+///
+/// // nonEmpty(x) -> size(x) > 0
+/// fn non_empty(
+///     helper: &mut MacroExprHelper,
+///     _target: Option<IdedExpr>,
+///     args: Vec<IdedExpr>,
+/// ) -> Result<IdedExpr, ParseError> {
+///     let size = helper.next_expr(Expr::Call(CallExpr {
+///         func_name: "size".to_string(),
+///         target: None,
+///         args,
+///     }));
+///     let zero = helper.next_expr(Expr::Literal(CelVal::Int(0)));
+///     Ok(helper.next_expr(Expr::Call(CallExpr {
+///         func_name: operators::GREATER.to_string(),
+///         target: None,
+///         args: vec![size, zero],
+///     })))
+/// }
+///
+/// let parser = Parser::new().macros([Macro::global("nonEmpty", 1, non_empty)]);
+/// let program = Program::compile_with("nonEmpty('a')", parser).unwrap();
+/// assert_eq!(program.execute(&Context::default()), Ok(true.into()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Macro {
+    name: String,
+    receiver: bool,
+    // `None` for variadic macros.
+    arg_count: Option<usize>,
+    expander: MacroExpander,
+}
+
+impl Macro {
+    /// A global macro taking `arg_count` arguments, e.g. `name(a, b)`.
+    pub fn global<S: Into<String>>(name: S, arg_count: usize, expander: MacroExpander) -> Self {
+        Macro {
+            name: name.into(),
+            receiver: false,
+            arg_count: Some(arg_count),
+            expander,
+        }
+    }
+
+    /// A macro called on a receiver and taking `arg_count` arguments, e.g. `x.name(a, b)`.
+    pub fn receiver<S: Into<String>>(name: S, arg_count: usize, expander: MacroExpander) -> Self {
+        Macro {
+            name: name.into(),
+            receiver: true,
+            arg_count: Some(arg_count),
+            expander,
+        }
+    }
+
+    /// A global macro taking any number of arguments.
+    pub fn global_var_arg<S: Into<String>>(name: S, expander: MacroExpander) -> Self {
+        Macro {
+            name: name.into(),
+            receiver: false,
+            arg_count: None,
+            expander,
+        }
+    }
+
+    /// A macro called on a receiver and taking any number of arguments.
+    pub fn receiver_var_arg<S: Into<String>>(name: S, expander: MacroExpander) -> Self {
+        Macro {
+            name: name.into(),
+            receiver: true,
+            arg_count: None,
+            expander,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the expander of the macro if it applies to a call of `func_name`, with or
+    /// without receiver, with `arg_count` arguments.
+    pub(crate) fn expander_for(
+        &self,
+        func_name: &str,
+        receiver: bool,
+        arg_count: usize,
+    ) -> Option<MacroExpander> {
+        (self.name == func_name
+            && self.receiver == receiver
+            && self.arg_count.is_none_or(|count| count == arg_count))
+        .then_some(self.expander)
+    }
+}
+
 pub fn find_expander(
     func_name: &str,
     target: Option<&IdedExpr>,
@@ -15,19 +127,51 @@ pub fn find_expander(
 ) -> Option<MacroExpander> {
     match func_name {
         operators::HAS if args.len() == 1 && target.is_none() => Some(has_macro_expander),
-        operators::EXISTS if args.len() == 2 && target.is_some() => Some(exists_macro_expander),
-        operators::ALL if args.len() == 2 && target.is_some() => Some(all_macro_expander),
-        operators::EXISTS_ONE | "existsOne" if args.len() == 2 && target.is_some() => {
+        operators::EXISTS if (args.len() == 2 || args.len() == 3) && target.is_some() => {
+            Some(exists_macro_expander)
+        }
+        operators::ALL if (args.len() == 2 || args.len() == 3) && target.is_some() => {
+            Some(all_macro_expander)
+        }
+        operators::EXISTS_ONE | "existsOne"
+            if (args.len() == 2 || args.len() == 3) && target.is_some() =>
+        {
             Some(exists_one_macro_expander)
         }
         operators::MAP if (args.len() == 2 || args.len() == 3) && target.is_some() => {
             Some(map_macro_expander)
         }
         operators::FILTER if args.len() == 2 && target.is_some() => Some(filter_macro_expander),
+        operators::TRANSFORM_LIST if (args.len() == 3 || args.len() == 4) && target.is_some() => {
+            Some(transform_list_macro_expander)
+        }
+        operators::TRANSFORM_MAP if (args.len() == 3 || args.len() == 4) && target.is_some() => {
+            Some(transform_map_macro_expander)
+        }
+        operators::TRANSFORM_MAP_ENTRY
+            if (args.len() == 3 || args.len() == 4) && target.is_some() =>
+        {
+            Some(transform_map_entry_macro_expander)
+        }
+        operators::BIND if args.len() == 3 && is_cel_namespace(target) => Some(bind_macro_expander),
+        operators::BLOCK if args.len() == 2 && is_cel_namespace(target) => {
+            Some(block_macro_expander)
+        }
         _ => None,
     }
 }
 
+/// The prefix of the variables bound by `cel.block`, e.g. `@index0` for `cel.index(0)`.
+pub(crate) const BLOCK_VAR_PREFIX: &str = "@index";
+
+/// The iteration variable of the comprehensions expanded from `cel.bind`, which iterate over
+/// an empty list.
+pub(crate) const BIND_ITER_VAR: &str = "#unused";
+
+fn is_cel_namespace(target: Option<&IdedExpr>) -> bool {
+    matches!(target, Some(IdedExpr { expr: Expr::Ident(name), .. }) if name == operators::CEL_NAMESPACE)
+}
+
 fn has_macro_expander(
     helper: &mut MacroExprHelper,
     target: Option<IdedExpr>,
@@ -64,12 +208,12 @@ fn exists_macro_expander(
     if target.is_none() {
         unreachable!("Expected a target, but got `None`!")
     }
-    if args.len() != 2 {
-        unreachable!("Expected two args!")
+    if args.len() != 2 && args.len() != 3 {
+        unreachable!("Expected two or three args!")
     }
 
-    let mut arguments = vec![args.remove(1)];
-    let v = extract_ident(args.remove(0), helper)?;
+    let mut arguments = vec![args.pop().unwrap()];
+    let (v, v2) = extract_idents(args, helper)?;
 
     let init = helper.next_expr(Expr::Literal(Boolean(false)));
     let result_binding = "@result".to_string();
@@ -98,7 +242,7 @@ fn exists_macro_expander(
         helper.next_expr(Expr::Comprehension(Box::new(ComprehensionExpr {
             iter_range: target.unwrap(),
             iter_var: v,
-            iter_var2: None,
+            iter_var2: v2,
             accu_var: result_binding,
             accu_init: init,
             loop_cond: condition,
@@ -115,12 +259,12 @@ fn all_macro_expander(
     if target.is_none() {
         unreachable!("Expected a target, but got `None`!")
     }
-    if args.len() != 2 {
-        unreachable!("Expected two args!")
+    if args.len() != 2 && args.len() != 3 {
+        unreachable!("Expected two or three args!")
     }
 
-    let mut arguments = vec![args.remove(1)];
-    let v = extract_ident(args.remove(0), helper)?;
+    let mut arguments = vec![args.pop().unwrap()];
+    let (v, v2) = extract_idents(args, helper)?;
 
     let init = helper.next_expr(Expr::Literal(Boolean(true)));
     let result_binding = "@result".to_string();
@@ -144,7 +288,7 @@ fn all_macro_expander(
         helper.next_expr(Expr::Comprehension(Box::new(ComprehensionExpr {
             iter_range: target.unwrap(),
             iter_var: v,
-            iter_var2: None,
+            iter_var2: v2,
             accu_var: result_binding,
             accu_init: init,
             loop_cond: condition,
@@ -162,12 +306,12 @@ fn exists_one_macro_expander(
     if target.is_none() {
         unreachable!("Expected a target, but got `None`!")
     }
-    if args.len() != 2 {
-        unreachable!("Expected two args!")
+    if args.len() != 2 && args.len() != 3 {
+        unreachable!("Expected two or three args!")
     }
 
-    let mut arguments = vec![args.remove(1)];
-    let v = extract_ident(args.remove(0), helper)?;
+    let mut arguments = vec![args.pop().unwrap()];
+    let (v, v2) = extract_idents(args, helper)?;
 
     let init = helper.next_expr(Expr::Literal(Int(0)));
     let result_binding = "@result".to_string();
@@ -202,7 +346,7 @@ fn exists_one_macro_expander(
         helper.next_expr(Expr::Comprehension(Box::new(ComprehensionExpr {
             iter_range: target.unwrap(),
             iter_var: v,
-            iter_var2: None,
+            iter_var2: v2,
             accu_var: result_binding,
             accu_init: init,
             loop_cond: condition,
@@ -227,7 +371,7 @@ fn map_macro_expander(
     let func = args.pop().unwrap();
     let v = extract_ident(args.remove(0), helper)?;
 
-    let init = helper.next_expr(Expr::List(ListExpr { elements: vec![] }));
+    let init = helper.next_expr(Expr::List(ListExpr::default()));
     let result_binding = "@result".to_string();
     let condition = helper.next_expr(Expr::Literal(Boolean(true)));
 
@@ -237,6 +381,7 @@ fn map_macro_expander(
         helper.next_expr(Expr::Ident(result_binding.clone())),
         helper.next_expr(Expr::List(ListExpr {
             elements: vec![func],
+            optional_indices: vec![],
         })),
     ];
     let step = helper.next_expr(Expr::Call(CallExpr {
@@ -289,7 +434,7 @@ fn filter_macro_expander(
     let v = extract_ident(var.clone(), helper)?;
     let filter = args.pop().unwrap();
 
-    let init = helper.next_expr(Expr::List(ListExpr { elements: vec![] }));
+    let init = helper.next_expr(Expr::List(ListExpr::default()));
     let result_binding = "@result".to_string();
     let condition = helper.next_expr(Expr::Literal(Boolean(true)));
 
@@ -297,6 +442,7 @@ fn filter_macro_expander(
         helper.next_expr(Expr::Ident(result_binding.clone())),
         helper.next_expr(Expr::List(ListExpr {
             elements: vec![var],
+            optional_indices: vec![],
         })),
     ];
     let step = helper.next_expr(Expr::Call(CallExpr {
@@ -328,6 +474,321 @@ fn filter_macro_expander(
     )
 }
 
+fn bind_macro_expander(
+    helper: &mut MacroExprHelper,
+    target: Option<IdedExpr>,
+    mut args: Vec<IdedExpr>,
+) -> Result<IdedExpr, ParseError> {
+    if target.is_none() {
+        unreachable!("Expected a target, but got `None`!")
+    }
+    if args.len() != 3 {
+        unreachable!("Expected three args!")
+    }
+
+    let result = args.pop().unwrap();
+    let init = args.pop().unwrap();
+    let var = extract_ident(args.pop().unwrap(), helper)?;
+    Ok(bind(helper, var, init, result))
+}
+
+/// Expands `cel.block([init0, init1, ...], result)`, where `cel.index(n)` refers to the value of
+/// `init{n}`, into nested `cel.bind`s of the `@index{n}` variables.
+///
+/// As in cel-go, the initializers are evaluated lazily, when their index is first used, see
+/// [`is_block_binding`].
+fn block_macro_expander(
+    helper: &mut MacroExprHelper,
+    target: Option<IdedExpr>,
+    mut args: Vec<IdedExpr>,
+) -> Result<IdedExpr, ParseError> {
+    if target.is_none() {
+        unreachable!("Expected a target, but got `None`!")
+    }
+    if args.len() != 2 {
+        unreachable!("Expected two args!")
+    }
+
+    let mut result = args.pop().unwrap();
+    let inits = args.pop().unwrap();
+    let mut inits = match inits.expr {
+        Expr::List(list) if list.optional_indices.is_empty() => list.elements,
+        _ => return Err(helper.error(&inits, "cel.block requires a list of expressions")),
+    };
+    // Each initializer may only refer to the ones before it.
+    for (i, init) in inits.iter_mut().enumerate() {
+        bind_block_indices(init, i, helper)?;
+    }
+    bind_block_indices(&mut result, inits.len(), helper)?;
+
+    for (i, init) in inits.into_iter().enumerate().rev() {
+        result = bind(helper, format!("{BLOCK_VAR_PREFIX}{i}"), init, result);
+    }
+    Ok(result)
+}
+
+/// Replaces the `cel.index(n)` calls in `expr` with the variable `@index{n}`, failing if `n`
+/// isn't lower than `count`.
+fn bind_block_indices(
+    expr: &mut IdedExpr,
+    count: usize,
+    helper: &mut MacroExprHelper,
+) -> Result<(), ParseError> {
+    match &mut expr.expr {
+        Expr::Call(call)
+            if call.func_name == operators::BLOCK_INDEX
+                && call.args.len() == 1
+                && is_cel_namespace(call.target.as_deref()) =>
+        {
+            let index = match &call.args[0].expr {
+                Expr::Literal(Int(index)) if *index >= 0 && (*index as usize) < count => *index,
+                _ => {
+                    return Err(helper.error(
+                        &call.args[0],
+                        format!("cel.index requires an index lower than {count}"),
+                    ))
+                }
+            };
+            expr.expr = Expr::Ident(format!("{BLOCK_VAR_PREFIX}{index}"));
+        }
+        Expr::Call(call) => {
+            if let Some(target) = &mut call.target {
+                bind_block_indices(target, count, helper)?;
+            }
+            for arg in &mut call.args {
+                bind_block_indices(arg, count, helper)?;
+            }
+        }
+        Expr::Comprehension(comprehension) => {
+            let comprehension = &mut **comprehension;
+            for expr in [
+                &mut comprehension.iter_range,
+                &mut comprehension.accu_init,
+                &mut comprehension.loop_cond,
+                &mut comprehension.loop_step,
+                &mut comprehension.result,
+            ] {
+                bind_block_indices(expr, count, helper)?;
+            }
+        }
+        Expr::List(list) => {
+            for element in &mut list.elements {
+                bind_block_indices(element, count, helper)?;
+            }
+        }
+        Expr::Map(map) => {
+            for entry in &mut map.entries {
+                if let EntryExpr::MapEntry(entry) = &mut entry.expr {
+                    bind_block_indices(&mut entry.key, count, helper)?;
+                    bind_block_indices(&mut entry.value, count, helper)?;
+                }
+            }
+        }
+        Expr::Struct(s) => {
+            for entry in &mut s.entries {
+                if let EntryExpr::StructField(field) = &mut entry.expr {
+                    bind_block_indices(&mut field.value, count, helper)?;
+                }
+            }
+        }
+        Expr::Select(select) => bind_block_indices(&mut select.operand, count, helper)?,
+        Expr::Ident(_) | Expr::Literal(_) | Expr::Unspecified => {}
+    }
+    Ok(())
+}
+
+/// Whether `comprehension` binds one of the `cel.block` indexes, whose initializer is only
+/// evaluated once the index is used, unlike the variables of `cel.bind`.
+pub(crate) fn is_block_binding(comprehension: &ComprehensionExpr) -> bool {
+    comprehension.iter_var == BIND_ITER_VAR && comprehension.accu_var.starts_with(BLOCK_VAR_PREFIX)
+}
+
+/// Builds the comprehension binding `var` to the value of `init` in `result`: it iterates over
+/// an empty list, so that `result` is evaluated right away with `init` as accumulator.
+fn bind(helper: &mut MacroExprHelper, var: String, init: IdedExpr, result: IdedExpr) -> IdedExpr {
+    let range = helper.next_expr(Expr::List(ListExpr::default()));
+    let condition = helper.next_expr(Expr::Literal(Boolean(false)));
+    let step = helper.next_expr(Expr::Ident(var.clone()));
+    helper.next_expr(Expr::Comprehension(Box::new(ComprehensionExpr {
+        iter_range: range,
+        iter_var: BIND_ITER_VAR.to_string(),
+        iter_var2: None,
+        accu_var: var,
+        accu_init: init,
+        loop_cond: condition,
+        loop_step: step,
+        result,
+    })))
+}
+
+/// The name of the function sorting a list by the keys the expansion of `sortBy` computes.
+pub(crate) const SORT_BY_ASSOCIATED_KEYS: &str = "@sortByAssociatedKeys";
+
+/// Expands `list.sortBy(x, key)` of the lists extension into
+/// `cel.bind(@__sortBy_input__, list, @__sortBy_input__.@sortByAssociatedKeys(@__sortBy_input__.map(x, key)))`,
+/// so that it can be type checked.
+pub(crate) fn sort_by_macro_expander(
+    helper: &mut MacroExprHelper,
+    target: Option<IdedExpr>,
+    args: Vec<IdedExpr>,
+) -> Result<IdedExpr, ParseError> {
+    let Some(target) = target else {
+        unreachable!("Expected a target, but got `None`!")
+    };
+    if args.len() != 2 {
+        unreachable!("Expected two args!")
+    }
+
+    let input = "@__sortBy_input__".to_string();
+    let list = helper.next_expr(Expr::Ident(input.clone()));
+    let keys = map_macro_expander(helper, Some(list), args)?;
+    let list = helper.next_expr(Expr::Ident(input.clone()));
+    let sorted = helper.next_expr(Expr::Call(CallExpr {
+        func_name: SORT_BY_ASSOCIATED_KEYS.to_string(),
+        target: Some(Box::new(list)),
+        args: vec![keys],
+    }));
+    Ok(bind(helper, input, target, sorted))
+}
+
+fn transform_list_macro_expander(
+    helper: &mut MacroExprHelper,
+    target: Option<IdedExpr>,
+    args: Vec<IdedExpr>,
+) -> Result<IdedExpr, ParseError> {
+    transform_macro_expander(helper, target, args, operators::TRANSFORM_LIST)
+}
+
+fn transform_map_macro_expander(
+    helper: &mut MacroExprHelper,
+    target: Option<IdedExpr>,
+    args: Vec<IdedExpr>,
+) -> Result<IdedExpr, ParseError> {
+    transform_macro_expander(helper, target, args, operators::TRANSFORM_MAP)
+}
+
+fn transform_map_entry_macro_expander(
+    helper: &mut MacroExprHelper,
+    target: Option<IdedExpr>,
+    args: Vec<IdedExpr>,
+) -> Result<IdedExpr, ParseError> {
+    transform_macro_expander(helper, target, args, operators::TRANSFORM_MAP_ENTRY)
+}
+
+/// Expands `transformList(i, v, [filter,] value)`, `transformMap(k, v, [filter,] value)` and
+/// `transformMapEntry(k, v, [filter,] entry)`, where `entry` evaluates to a map whose entries
+/// are added to the result.
+fn transform_macro_expander(
+    helper: &mut MacroExprHelper,
+    target: Option<IdedExpr>,
+    mut args: Vec<IdedExpr>,
+    name: &str,
+) -> Result<IdedExpr, ParseError> {
+    if target.is_none() {
+        unreachable!("Expected a target, but got `None`!")
+    }
+    if args.len() != 3 && args.len() != 4 {
+        unreachable!("Expected three or four args!")
+    }
+
+    let func = args.pop().unwrap();
+    let filter = if args.len() == 3 { args.pop() } else { None };
+    let (v, v2) = extract_idents(args, helper)?;
+
+    let result_binding = "@result".to_string();
+    let accu = helper.next_expr(Expr::Ident(result_binding.clone()));
+    let (init, step) = match name {
+        operators::TRANSFORM_LIST => {
+            let init = helper.next_expr(Expr::List(ListExpr::default()));
+            let list = helper.next_expr(Expr::List(ListExpr {
+                elements: vec![func],
+                optional_indices: vec![],
+            }));
+            let step = helper.next_expr(Expr::Call(CallExpr {
+                func_name: operators::ADD.to_string(),
+                target: None,
+                args: vec![accu, list],
+            }));
+            (init, step)
+        }
+        operators::TRANSFORM_MAP => {
+            let init = helper.next_expr(Expr::Map(MapExpr::default()));
+            let key = helper.next_expr(Expr::Ident(v.clone()));
+            let step = helper.next_expr(Expr::Call(CallExpr {
+                func_name: operators::MAP_INSERT.to_string(),
+                target: None,
+                args: vec![accu, key, func],
+            }));
+            (init, step)
+        }
+        _ => {
+            let init = helper.next_expr(Expr::Map(MapExpr::default()));
+            let step = helper.next_expr(Expr::Call(CallExpr {
+                func_name: operators::MAP_INSERT.to_string(),
+                target: None,
+                args: vec![accu, func],
+            }));
+            (init, step)
+        }
+    };
+    let condition = helper.next_expr(Expr::Literal(Boolean(true)));
+
+    let step = match filter {
+        Some(filter) => {
+            let accu = helper.next_expr(Expr::Ident(result_binding.clone()));
+            helper.next_expr(Expr::Call(CallExpr {
+                func_name: operators::CONDITIONAL.to_string(),
+                target: None,
+                args: vec![filter, step, accu],
+            }))
+        }
+        None => step,
+    };
+
+    let result = helper.next_expr(Expr::Ident(result_binding.clone()));
+
+    Ok(
+        helper.next_expr(Expr::Comprehension(Box::new(ComprehensionExpr {
+            iter_range: target.unwrap(),
+            iter_var: v,
+            iter_var2: v2,
+            accu_var: result_binding,
+            accu_init: init,
+            loop_cond: condition,
+            loop_step: step,
+            result,
+        }))),
+    )
+}
+
+/// Extracts the iteration variables of a comprehension: one, or two for the comprehensions v2
+/// macros iterating over (index, value) or (key, value) pairs, e.g. `m.all(k, v, v > 0)`.
+fn extract_idents(
+    args: Vec<IdedExpr>,
+    helper: &mut MacroExprHelper,
+) -> Result<(String, Option<String>), ParseError> {
+    let mut args = args.into_iter();
+    let v = extract_ident(
+        args.next().expect("Expected an iteration variable!"),
+        helper,
+    )?;
+    let Some(second) = args.next() else {
+        return Ok((v, None));
+    };
+    let id = second.id;
+    let v2 = extract_ident(second, helper)?;
+    if v == v2 {
+        return Err(ParseError {
+            source: None,
+            pos: helper.pos_for(id).unwrap_or_default(),
+            msg: format!("duplicate variable name: {v2}"),
+            expr_id: 0,
+            source_info: None,
+        });
+    }
+    Ok((v, Some(v2)))
+}
+
 fn extract_ident(expr: IdedExpr, helper: &mut MacroExprHelper) -> Result<String, ParseError> {
     match expr.expr {
         Expr::Ident(ident) => Ok(ident),
@@ -340,3 +801,138 @@ fn extract_ident(expr: IdedExpr, helper: &mut MacroExprHelper) -> Result<String,
         }),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::value::CelVal;
+    use crate::parser::Parser;
+    use crate::{Context, ExecutionError, Program, ResolveResult};
+
+    /// `target.any(v, predicate)`, a domain-specific spelling of `exists`.
+    fn any_macro_expander(
+        helper: &mut MacroExprHelper,
+        target: Option<IdedExpr>,
+        args: Vec<IdedExpr>,
+    ) -> Result<IdedExpr, ParseError> {
+        exists_macro_expander(helper, target, args)
+    }
+
+    /// `allOf(a, b, ...)`, which is `a && b && ...`.
+    fn all_of_macro_expander(
+        helper: &mut MacroExprHelper,
+        _target: Option<IdedExpr>,
+        args: Vec<IdedExpr>,
+    ) -> Result<IdedExpr, ParseError> {
+        let mut args = args.into_iter();
+        let Some(first) = args.next() else {
+            return Ok(helper.next_expr(Expr::Literal(Boolean(true))));
+        };
+        Ok(args.fold(first, |all, arg| {
+            helper.next_expr(Expr::Call(CallExpr {
+                func_name: operators::LOGICAL_AND.to_string(),
+                target: None,
+                args: vec![all, arg],
+            }))
+        }))
+    }
+
+    /// `has(x)` restricted to literal strings, overriding the standard macro.
+    fn has_literal_macro_expander(
+        helper: &mut MacroExprHelper,
+        _target: Option<IdedExpr>,
+        mut args: Vec<IdedExpr>,
+    ) -> Result<IdedExpr, ParseError> {
+        let arg = args.remove(0);
+        match arg.expr {
+            Expr::Literal(CelVal::String(_)) => Ok(helper.next_expr(Expr::Literal(Boolean(true)))),
+            _ => Err(helper.error(&arg, "has() only accepts string literals")),
+        }
+    }
+
+    fn parser() -> Parser {
+        Parser::new().macros([
+            Macro::receiver("any", 2, any_macro_expander),
+            Macro::global_var_arg("allOf", all_of_macro_expander),
+        ])
+    }
+
+    fn execute(source: &str) -> ResolveResult {
+        let mut context = Context::default();
+        context.add_variable_from_value("headers", vec!["accept", "x-trace"]);
+        Program::compile_with(source, parser())
+            .unwrap()
+            .execute(&context)
+    }
+
+    #[test]
+    fn block_indices_must_be_bound() {
+        for (source, message) in [
+            (
+                "cel.block([cel.index(0)], 1)",
+                "cel.index requires an index lower than 0",
+            ),
+            (
+                "cel.block([1], cel.index(1))",
+                "cel.index requires an index lower than 1",
+            ),
+            (
+                "cel.block([1], cel.index(x))",
+                "cel.index requires an index lower than 1",
+            ),
+            (
+                "cel.block(x, 1)",
+                "cel.block requires a list of expressions",
+            ),
+        ] {
+            let errors = Parser::new().parse(source).unwrap_err();
+            assert_eq!(errors.errors[0].msg, message, "{source}");
+        }
+    }
+
+    #[test]
+    fn custom_macros() {
+        for (source, expected) in [
+            ("headers.any(h, h.startsWith('x-'))", true.into()),
+            ("headers.any(h, h == 'host')", false.into()),
+            ("allOf()", true.into()),
+            ("allOf(size(headers) == 2)", true.into()),
+            (
+                "allOf(true, 'accept' in headers, 'host' in headers)",
+                false.into(),
+            ),
+            // Standard macros are still available.
+            ("headers.exists(h, h == 'accept')", true.into()),
+        ] {
+            assert_eq!(execute(source), Ok(expected), "{source}");
+        }
+    }
+
+    #[test]
+    fn custom_macros_match_arg_count() {
+        // With a single argument, `any` is a regular function call.
+        assert_eq!(
+            execute("headers.any(true)"),
+            Err(ExecutionError::undeclared_reference("any"))
+        );
+        // Without receiver too.
+        assert_eq!(
+            execute("any(headers, h, true)"),
+            Err(ExecutionError::undeclared_reference("any"))
+        );
+    }
+
+    #[test]
+    fn custom_macros_override_standard_ones() {
+        let parser = Parser::new().macros([Macro::global("has", 1, has_literal_macro_expander)]);
+        let program = Program::compile_with("has('a')", parser).unwrap();
+        assert_eq!(program.execute(&Context::default()), Ok(true.into()));
+
+        let parser = Parser::new().macros([Macro::global("has", 1, has_literal_macro_expander)]);
+        let err = Program::compile_with("has(a.b)", parser).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ERROR: <input>:1:6: has() only accepts string literals\n| has(a.b)\n| .....^"
+        );
+    }
+}
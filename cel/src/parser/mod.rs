@@ -10,6 +10,10 @@ mod macros;
 mod parse;
 #[allow(non_snake_case)]
 mod parser;
+mod unparser;
 
+pub(crate) use macros::{is_block_binding, sort_by_macro_expander, SORT_BY_ASSOCIATED_KEYS};
+pub use macros::{Macro, MacroExpander};
 pub use parser::*;
 pub use references::ExpressionReferences;
+pub use unparser::{unparse, UnparseError};
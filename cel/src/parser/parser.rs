@@ -16,7 +16,7 @@ use crate::parser::gen::{
     PrimaryExprContext, PrimaryExprContextAttrs, RelationContext, RelationContextAttrs,
     SelectContext, SelectContextAttrs, StartContext, StartContextAttrs, StringContext, UintContext,
 };
-use crate::parser::{gen, macros, parse};
+use crate::parser::{gen, macros, parse, Macro, MacroExpander};
 use antlr4rust::common_token_stream::CommonTokenStream;
 use antlr4rust::error_listener::ErrorListener;
 use antlr4rust::errors::ANTLRError;
@@ -48,6 +48,18 @@ impl MacroExprHelper<'_> {
     pub(crate) fn pos_for(&self, id: u64) -> Option<(isize, isize)> {
         self.helper.source_info.pos_for(id)
     }
+
+    /// Creates an error reported at the position of `expr`, e.g. an invalid argument of the
+    /// macro.
+    pub fn error<S: Into<String>>(&self, expr: &IdedExpr, msg: S) -> ParseError {
+        ParseError {
+            source: None,
+            pos: self.pos_for(expr.id).unwrap_or_default(),
+            msg: msg.into(),
+            expr_id: 0,
+            source_info: None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -103,6 +115,7 @@ pub struct Parser {
     helper: ParserHelper,
     errors: Vec<ParseError>,
     max_recursion_depth: u16,
+    macros: Vec<Macro>,
 }
 
 impl Parser {
@@ -114,6 +127,7 @@ impl Parser {
             helper: ParserHelper::default(),
             errors: Vec::default(),
             max_recursion_depth: 96,
+            macros: Vec::new(),
         }
     }
 
@@ -122,6 +136,27 @@ impl Parser {
         self
     }
 
+    /// Registers user-defined macros, see [`Macro`]. They take precedence over the standard
+    /// macros, and over the macros registered before them.
+    pub fn macros<I: IntoIterator<Item = Macro>>(mut self, macros: I) -> Self {
+        let mut macros: Vec<Macro> = macros.into_iter().collect();
+        macros.append(&mut self.macros);
+        self.macros = macros;
+        self
+    }
+
+    fn find_expander(
+        &self,
+        func_name: &str,
+        target: Option<&IdedExpr>,
+        args: &[IdedExpr],
+    ) -> Option<MacroExpander> {
+        self.macros
+            .iter()
+            .find_map(|m| m.expander_for(func_name, target.is_some(), args.len()))
+            .or_else(|| macros::find_expander(func_name, target, args))
+    }
+
     fn new_logic_manager(&self, func: &str, term: IdedExpr) -> LogicManager {
         LogicManager {
             function: func.to_string(),
@@ -136,7 +171,7 @@ impl Parser {
         func_name: String,
         args: Vec<IdedExpr>,
     ) -> IdedExpr {
-        match macros::find_expander(&func_name, None, &args) {
+        match self.find_expander(&func_name, None, &args) {
             None => IdedExpr {
                 id,
                 expr: Expr::Call(CallExpr {
@@ -165,7 +200,7 @@ impl Parser {
         target: IdedExpr,
         args: Vec<IdedExpr>,
     ) -> IdedExpr {
-        match macros::find_expander(&func_name, Some(&target), &args) {
+        match self.find_expander(&func_name, Some(&target), &args) {
             None => IdedExpr {
                 id,
                 expr: Expr::Call(CallExpr {
@@ -187,7 +222,16 @@ impl Parser {
         }
     }
 
-    pub fn parse(mut self, source: &str) -> Result<IdedExpr, ParseErrors> {
+    pub fn parse(self, source: &str) -> Result<IdedExpr, ParseErrors> {
+        self.parse_with_source_info(source).map(|(expr, _)| expr)
+    }
+
+    /// Parses `source`, also returning the source info mapping expression ids to their
+    /// positions in it.
+    pub(crate) fn parse_with_source_info(
+        mut self,
+        source: &str,
+    ) -> Result<(IdedExpr, Arc<SourceInfo>), ParseErrors> {
         let parse_errors = Rc::new(RefCell::new(Vec::<ParseError>::new()));
         let stream = InputStream::new(source);
         let mut lexer = gen::CELLexer::new(stream);
@@ -224,10 +268,11 @@ impl Parser {
 
         let mut errors = parse_errors.take();
         errors.extend(self.errors);
-        errors.sort_by(|a, b| a.pos.cmp(&b.pos));
+        errors.sort_by_key(|a| a.pos);
 
         if errors.is_empty() {
-            r.map_err(|e| ParseErrors { errors: vec![e] })
+            r.map(|expr| (expr, source_info))
+                .map_err(|e| ParseErrors { errors: vec![e] })
         } else {
             Err(ParseErrors {
                 errors: errors
@@ -264,20 +309,12 @@ impl Parser {
                 Some(ident) => {
                     let field_name = ident.get_text().to_string();
                     let value = self.visit(ctx.values[i].as_ref());
-                    if let Some(opt) = &field.opt {
-                        self.report_error::<ParseError, _>(
-                            opt.as_ref(),
-                            None,
-                            "unsupported syntax '?'",
-                        );
-                        continue;
-                    }
                     fields.push(IdedEntryExpr {
                         id,
                         expr: EntryExpr::StructField(StructFieldExpr {
                             field: field_name,
                             value,
-                            optional: false,
+                            optional: field.opt.is_some(),
                         }),
                     });
                 }
@@ -299,38 +336,29 @@ impl Parser {
             }
             let id = self.helper.next_id(col);
             let key = self.visit(keys[i].as_ref());
-            if let Some(opt) = &keys[i].opt {
-                self.report_error::<ParseError, _>(opt.as_ref(), None, "unsupported syntax '?'");
-                continue;
-            }
             let value = self.visit(vals[i].as_ref());
             entries.push(IdedEntryExpr {
                 id,
                 expr: EntryExpr::MapEntry(MapEntryExpr {
                     key,
                     value,
-                    optional: false,
+                    optional: keys[i].opt.is_some(),
                 }),
             })
         }
         entries
     }
 
-    fn list_initializer_list(&mut self, ctx: &ListInitContextAll) -> Vec<IdedExpr> {
-        let mut list = Vec::default();
+    fn list_initializer_list(&mut self, ctx: &ListInitContextAll) -> ListExpr {
+        let mut list = ListExpr::default();
         for e in &ctx.elems {
             match &e.e {
-                None => return Vec::default(),
+                None => return ListExpr::default(),
                 Some(exp) => {
-                    if let Some(opt) = &e.opt {
-                        self.report_error::<ParseError, _>(
-                            opt.as_ref(),
-                            None,
-                            "unsupported syntax '?'",
-                        );
-                        continue;
+                    if e.opt.is_some() {
+                        list.optional_indices.push(list.elements.len());
                     }
-                    list.push(self.visit(exp.as_ref()));
+                    list.elements.push(self.visit(exp.as_ref()));
                 }
             }
         }
@@ -717,12 +745,19 @@ impl gen::CELVisitorCompat<'_> for Parser {
         if let (Some(member), Some(id), Some(op)) = (&ctx.member(), &ctx.id, &ctx.op) {
             let operand = self.visit(member.as_ref());
             let field = id.get_text();
-            if let Some(_opt) = &ctx.opt {
-                return self.report_error::<ParseError, _>(
-                    op.as_ref(),
-                    None,
-                    "unsupported syntax '.?'",
-                );
+            if ctx.opt.is_some() {
+                let op_id = self.helper.next_id(op);
+                let field = self
+                    .helper
+                    .next_expr(op.as_ref(), Expr::Literal(CelVal::String(field)));
+                return IdedExpr {
+                    id: op_id,
+                    expr: Expr::Call(CallExpr {
+                        func_name: operators::OPT_SELECT.to_string(),
+                        target: None,
+                        args: vec![operand, field],
+                    }),
+                };
             }
             self.helper.next_expr(
                 op.as_ref(),
@@ -754,18 +789,12 @@ impl gen::CELVisitorCompat<'_> for Parser {
                 Some(op) => {
                     let op_id = self.helper.next_id(op);
                     let index = self.visit(index.as_ref());
-                    if let Some(_opt) = &ctx.opt {
-                        return self.report_error::<ParseError, _>(
-                            op.as_ref(),
-                            None,
-                            "unsupported syntax '[?'",
-                        );
-                    }
-                    self.global_call_or_macro(
-                        op_id,
-                        operators::INDEX.to_string(),
-                        vec![target, index],
-                    )
+                    let func_name = if ctx.opt.is_some() {
+                        operators::OPT_INDEX
+                    } else {
+                        operators::INDEX
+                    };
+                    self.global_call_or_macro(op_id, func_name.to_string(), vec![target, index])
                 }
             }
         } else {
@@ -823,13 +852,13 @@ impl gen::CELVisitorCompat<'_> for Parser {
 
     fn visit_CreateList(&mut self, ctx: &CreateListContext<'_>) -> Self::Return {
         let list_id = self.helper.next_id_for_token(ctx.op.as_deref());
-        let elements = match &ctx.elems {
-            None => Vec::default(),
+        let list = match &ctx.elems {
+            None => ListExpr::default(),
             Some(elements) => self.list_initializer_list(elements.deref()),
         };
         IdedExpr {
             id: list_id,
-            expr: Expr::List(ListExpr { elements }),
+            expr: Expr::List(list),
         }
     }
 
@@ -1837,33 +1866,37 @@ ERROR: <input>:1:5: Syntax error: extraneous input 'b' expecting <EOF>
             },
             TestInfo {
                 i: "a.?b && a[?b]",
-                p: "",
-                e: "ERROR: <input>:1:2: unsupported syntax '.?'
-| a.?b && a[?b]
-| .^
-ERROR: <input>:1:10: unsupported syntax '[?'
-| a.?b && a[?b]
-| .........^",
+                p: "_&&_(
+    _?._(
+        a^#1:*expr.Expr_IdentExpr#,
+        \"b\"^#3:*expr.Constant_StringValue#
+    )^#2:*expr.Expr_CallExpr#,
+    _[?_](
+        a^#4:*expr.Expr_IdentExpr#,
+        b^#6:*expr.Expr_IdentExpr#
+    )^#5:*expr.Expr_CallExpr#
+)^#7:*expr.Expr_CallExpr#",
+                e: "",
             },
             TestInfo {
-                i: "a.?b && a[?b]",
-                p: "",
-                e: "ERROR: <input>:1:2: unsupported syntax '.?'
-| a.?b && a[?b]
-| .^
-ERROR: <input>:1:10: unsupported syntax '[?'
-| a.?b && a[?b]
-| .........^",
+                i: "Msg{?field: value} && {?'key': value}",
+                p: "_&&_(
+    Msg{
+        ?field:value^#3:*expr.Expr_IdentExpr#^#2:*expr.Expr_CreateStruct_Entry#
+    }^#1:*expr.Expr_StructExpr#,
+    {
+        ?\"key\"^#6:*expr.Constant_StringValue#:value^#7:*expr.Expr_IdentExpr#^#5:*expr.Expr_CreateStruct_Entry#
+    }^#4:*expr.Expr_StructExpr#
+)^#8:*expr.Expr_CallExpr#",
+                e: "",
             },
             TestInfo {
-                i: "Msg{?field: value} && {?'key': value}",
-                p: "",
-                e: "ERROR: <input>:1:5: unsupported syntax '?'
-| Msg{?field: value} && {?'key': value}
-| ....^
-ERROR: <input>:1:24: unsupported syntax '?'
-| Msg{?field: value} && {?'key': value}
-| .......................^",
+                i: "[?a, b]",
+                p: "[
+    ?a^#2:*expr.Expr_IdentExpr#,
+    b^#3:*expr.Expr_IdentExpr#
+]^#1:*expr.Expr_ListExpr#",
+                e: "",
             },
             TestInfo 	{
                 i: "has(m)",
@@ -1871,6 +1904,39 @@ ERROR: <input>:1:24: unsupported syntax '?'
                 e: "ERROR: <input>:1:5: invalid argument to has() macro
 | has(m)
 | ....^"
+            },
+            TestInfo {
+                i: "m.all(k, v, f)",
+                p: "__comprehension__(
+// Variable
+k,
+// Variable2
+v,
+// Target
+m^#1:*expr.Expr_IdentExpr#,
+// Accumulator
+@result,
+// Init
+true^#6:*expr.Constant_BoolValue#,
+// LoopCondition
+@not_strictly_false(
+    @result^#7:*expr.Expr_IdentExpr#
+)^#8:*expr.Expr_CallExpr#,
+// LoopStep
+_&&_(
+    @result^#9:*expr.Expr_IdentExpr#,
+    f^#5:*expr.Expr_IdentExpr#
+)^#10:*expr.Expr_CallExpr#,
+// Result
+@result^#11:*expr.Expr_IdentExpr#)^#12:*expr.Expr_ComprehensionExpr#",
+                e: "",
+            },
+            TestInfo {
+                i: "m.all(x, x, x > 0)",
+                p: "",
+                e: "ERROR: <input>:1:10: duplicate variable name: x
+| m.all(x, x, x > 0)
+| .........^",
             },
             TestInfo {
                 i: "1.all(2, 3)",
@@ -1969,6 +2035,9 @@ ERROR: <input>:1:24: unsupported syntax '?'
                                 self.push(",");
                                 self.newline();
                             }
+                            if list.optional_indices.contains(&i) {
+                                self.push("?");
+                            }
                             self.buffer(element);
                         }
                         self.dec_indent();
@@ -2010,6 +2079,9 @@ ERROR: <input>:1:24: unsupported syntax '?'
                         match &entry.expr {
                             EntryExpr::StructField(_) => panic!("WAT?!"),
                             EntryExpr::MapEntry(e) => {
+                                if e.optional {
+                                    self.push("?");
+                                }
                                 self.buffer(&e.key);
                                 self.push(":");
                                 self.buffer(&e.value);
@@ -2046,6 +2118,9 @@ ERROR: <input>:1:24: unsupported syntax '?'
                     for (i, entry) in s.entries.iter().enumerate() {
                         match &entry.expr {
                             EntryExpr::StructField(field) => {
+                                if field.optional {
+                                    self.push("?");
+                                }
                                 self.push(&field.field);
                                 self.push(":");
                                 self.buffer(&field.value);
@@ -2107,6 +2182,11 @@ ERROR: <input>:1:24: unsupported syntax '?'
             self.push("// Variable\n");
             self.push(comprehension.iter_var.as_str());
             self.push(",\n");
+            if let Some(iter_var2) = &comprehension.iter_var2 {
+                self.push("// Variable2\n");
+                self.push(iter_var2.as_str());
+                self.push(",\n");
+            }
             self.push("// Target\n");
             self.buffer(&comprehension.iter_range);
             self.push(",\n");
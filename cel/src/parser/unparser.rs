@@ -0,0 +1,672 @@
+use crate::common::ast::{operators, CallExpr, ComprehensionExpr, EntryExpr, Expr, IdedExpr};
+use crate::common::value::CelVal;
+use crate::parser::macros::{BIND_ITER_VAR, BLOCK_VAR_PREFIX};
+use std::fmt::Write;
+use thiserror::Error;
+
+/// Turns an expression back into CEL source, adding parentheses only where the precedence of
+/// the operators requires them.
+///
+/// Comprehensions are turned back into the macro they were expanded from, e.g.
+/// `list.all(x, x > 0)`, and the optional syntax is preserved, e.g. `a.?b` or `[?a]`.
+///
+/// Fails if the expression has no CEL syntax, see [`UnparseError`].
+///
+/// # Example
+/// ```
+/// use cel::parser::{unparse, Parser};
+///
+/// let expr = Parser::new().parse("(a+b)*c  &&  'x' in list").unwrap();
+/// assert_eq!(unparse(&expr).unwrap(), r#"(a + b) * c && "x" in list"#);
+/// ```
+pub fn unparse(expr: &IdedExpr) -> Result<String, UnparseError> {
+    let mut unparser = Unparser::default();
+    unparser.visit(expr)?;
+    Ok(unparser.out)
+}
+
+/// The error returned by [`unparse`] for the expressions which can't be written in CEL.
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum UnparseError {
+    /// A comprehension which wasn't expanded from one of the standard macros.
+    #[error("can't unparse a comprehension which wasn't expanded from a macro")]
+    Comprehension,
+    /// A literal of a type which has no literal syntax, e.g. a timestamp.
+    #[error("can't unparse the literal {0:?}")]
+    Literal(CelVal),
+}
+
+// Precedence of the operators, from the loosest to the tightest binding.
+const CONDITIONAL: u8 = 1;
+const LOGICAL_OR: u8 = 2;
+const LOGICAL_AND: u8 = 3;
+const RELATION: u8 = 4;
+const ADDITION: u8 = 5;
+const MULTIPLICATION: u8 = 6;
+const UNARY: u8 = 7;
+const MEMBER: u8 = 8;
+
+#[derive(Default)]
+struct Unparser {
+    out: String,
+}
+
+impl Unparser {
+    fn visit(&mut self, expr: &IdedExpr) -> Result<(), UnparseError> {
+        match &expr.expr {
+            Expr::Unspecified => {}
+            Expr::Call(call) => self.visit_call(call)?,
+            Expr::Comprehension(comprehension) => self.visit_comprehension(comprehension)?,
+            Expr::Ident(name) => match name.strip_prefix(BLOCK_VAR_PREFIX) {
+                Some(index) => write!(self.out, "cel.index({index})").unwrap(),
+                None => self.out.push_str(name),
+            },
+            Expr::List(list) => {
+                self.out.push('[');
+                for (i, element) in list.elements.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    if list.optional_indices.contains(&i) {
+                        self.out.push('?');
+                    }
+                    self.visit(element)?;
+                }
+                self.out.push(']');
+            }
+            Expr::Literal(value) => self.visit_literal(value)?,
+            Expr::Map(map) => {
+                self.out.push('{');
+                for (i, entry) in map.entries.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    if let EntryExpr::MapEntry(entry) = &entry.expr {
+                        if entry.optional {
+                            self.out.push('?');
+                        }
+                        self.visit(&entry.key)?;
+                        self.out.push_str(": ");
+                        self.visit(&entry.value)?;
+                    }
+                }
+                self.out.push('}');
+            }
+            Expr::Select(select) => {
+                if select.test {
+                    self.out.push_str("has(");
+                }
+                self.visit_operand(&select.operand, MEMBER)?;
+                self.out.push('.');
+                self.out.push_str(&select.field);
+                if select.test {
+                    self.out.push(')');
+                }
+            }
+            Expr::Struct(s) => {
+                self.out.push_str(&s.type_name);
+                self.out.push('{');
+                for (i, entry) in s.entries.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    if let EntryExpr::StructField(field) = &entry.expr {
+                        if field.optional {
+                            self.out.push('?');
+                        }
+                        self.out.push_str(&field.field);
+                        self.out.push_str(": ");
+                        self.visit(&field.value)?;
+                    }
+                }
+                self.out.push('}');
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_all(&mut self, exprs: &[IdedExpr]) -> Result<(), UnparseError> {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.visit(expr)?;
+        }
+        Ok(())
+    }
+
+    /// Visits an operand, wrapping it in parentheses if it binds looser than `precedence`.
+    fn visit_operand(&mut self, expr: &IdedExpr, precedence: u8) -> Result<(), UnparseError> {
+        if self::precedence(expr) < precedence {
+            self.out.push('(');
+            self.visit(expr)?;
+            self.out.push(')');
+        } else {
+            self.visit(expr)?;
+        }
+        Ok(())
+    }
+
+    fn visit_call(&mut self, call: &CallExpr) -> Result<(), UnparseError> {
+        match (call.func_name.as_str(), call.args.as_slice()) {
+            (operators::CONDITIONAL, [cond, then, otherwise]) => {
+                // Only the last branch may be a conditional itself without parentheses.
+                self.visit_operand(cond, CONDITIONAL + 1)?;
+                self.out.push_str(" ? ");
+                self.visit_operand(then, CONDITIONAL + 1)?;
+                self.out.push_str(" : ");
+                self.visit_operand(otherwise, CONDITIONAL)?;
+            }
+            (operators::LOGICAL_NOT, [operand]) => {
+                // Repeated unary operators are parenthesized, e.g. `!(!a)`, as the parser
+                // collapses them.
+                self.out.push('!');
+                self.visit_operand(operand, MEMBER)?;
+            }
+            (operators::NEGATE, [operand]) => {
+                self.out.push('-');
+                self.visit_operand(operand, MEMBER)?;
+            }
+            (operators::INDEX, [operand, index]) => {
+                self.visit_operand(operand, MEMBER)?;
+                self.out.push('[');
+                self.visit(index)?;
+                self.out.push(']');
+            }
+            (operators::OPT_INDEX, [operand, index]) => {
+                self.visit_operand(operand, MEMBER)?;
+                self.out.push_str("[?");
+                self.visit(index)?;
+                self.out.push(']');
+            }
+            (
+                operators::OPT_SELECT,
+                [operand, IdedExpr {
+                    expr: Expr::Literal(CelVal::String(field)),
+                    ..
+                }],
+            ) => {
+                self.visit_operand(operand, MEMBER)?;
+                self.out.push_str(".?");
+                self.out.push_str(field);
+            }
+            (name, [left, right]) if binary_operator(name).is_some() => {
+                let (symbol, precedence) = binary_operator(name).unwrap();
+                self.visit_operand(left, precedence)?;
+                self.out.push(' ');
+                self.out.push_str(symbol);
+                self.out.push(' ');
+                // Operators are left associative, except for the logical ones whose operands
+                // may be swapped.
+                if name == operators::LOGICAL_AND || name == operators::LOGICAL_OR {
+                    self.visit_operand(right, precedence)?;
+                } else {
+                    self.visit_operand(right, precedence + 1)?;
+                }
+            }
+            (name, args) => {
+                if let Some(target) = &call.target {
+                    self.visit_operand(target, MEMBER)?;
+                    self.out.push('.');
+                }
+                self.out.push_str(name);
+                self.out.push('(');
+                self.visit_all(args)?;
+                self.out.push(')');
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_comprehension(
+        &mut self,
+        comprehension: &ComprehensionExpr,
+    ) -> Result<(), UnparseError> {
+        if let Some((var, init, result)) = bound(comprehension) {
+            return self.visit_bind(var, init, result);
+        }
+        let Some((name, args)) = resugar(comprehension) else {
+            return Err(UnparseError::Comprehension);
+        };
+        self.visit_operand(&comprehension.iter_range, MEMBER)?;
+        self.out.push('.');
+        self.out.push_str(name);
+        self.out.push('(');
+        self.out.push_str(&comprehension.iter_var);
+        if let Some(iter_var2) = &comprehension.iter_var2 {
+            self.out.push_str(", ");
+            self.out.push_str(iter_var2);
+        }
+        for arg in args {
+            self.out.push_str(", ");
+            self.visit(arg)?;
+        }
+        self.out.push(')');
+        Ok(())
+    }
+
+    fn visit_bind(
+        &mut self,
+        var: &str,
+        init: &IdedExpr,
+        result: &IdedExpr,
+    ) -> Result<(), UnparseError> {
+        if var != format!("{BLOCK_VAR_PREFIX}0") {
+            self.out.push_str("cel.bind(");
+            self.out.push_str(var);
+            self.out.push_str(", ");
+            self.visit(init)?;
+            self.out.push_str(", ");
+            self.visit(result)?;
+            self.out.push(')');
+            return Ok(());
+        }
+        // `cel.block` binds `@index0`, `@index1`, ... in nested comprehensions.
+        let mut inits = vec![init];
+        let mut result = result;
+        while let Expr::Comprehension(comprehension) = &result.expr {
+            match bound(comprehension) {
+                Some((var, init, inner)) if var == format!("{BLOCK_VAR_PREFIX}{}", inits.len()) => {
+                    inits.push(init);
+                    result = inner;
+                }
+                _ => break,
+            }
+        }
+        self.out.push_str("cel.block([");
+        for (i, init) in inits.into_iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.visit(init)?;
+        }
+        self.out.push_str("], ");
+        self.visit(result)?;
+        self.out.push(')');
+        Ok(())
+    }
+
+    fn visit_literal(&mut self, value: &CelVal) -> Result<(), UnparseError> {
+        match value {
+            CelVal::Boolean(b) => write!(self.out, "{b}").unwrap(),
+            CelVal::Int(i) => write!(self.out, "{i}").unwrap(),
+            CelVal::UInt(u) => write!(self.out, "{u}u").unwrap(),
+            CelVal::Double(d) if d.is_nan() => self.out.push_str("double(\"NaN\")"),
+            CelVal::Double(d) if d.is_infinite() && *d > 0.0 => {
+                self.out.push_str("double(\"Infinity\")")
+            }
+            CelVal::Double(d) if d.is_infinite() => self.out.push_str("double(\"-Infinity\")"),
+            // The debug representation always has a fraction or an exponent, e.g. `1.0`.
+            CelVal::Double(d) => write!(self.out, "{d:?}").unwrap(),
+            CelVal::String(s) => {
+                self.out.push('"');
+                for c in s.chars() {
+                    match c {
+                        '"' => self.out.push_str("\\\""),
+                        '\\' => self.out.push_str("\\\\"),
+                        '\n' => self.out.push_str("\\n"),
+                        '\r' => self.out.push_str("\\r"),
+                        '\t' => self.out.push_str("\\t"),
+                        c if c.is_control() => write!(self.out, "\\u{:04x}", c as u32).unwrap(),
+                        c => self.out.push(c),
+                    }
+                }
+                self.out.push('"');
+            }
+            CelVal::Bytes(bytes) => {
+                self.out.push_str("b\"");
+                for b in bytes {
+                    match b {
+                        b'"' => self.out.push_str("\\\""),
+                        b'\\' => self.out.push_str("\\\\"),
+                        b' '..=b'~' => self.out.push(*b as char),
+                        b => write!(self.out, "\\x{b:02x}").unwrap(),
+                    }
+                }
+                self.out.push('"');
+            }
+            CelVal::Null => self.out.push_str("null"),
+            value => return Err(UnparseError::Literal(value.clone())),
+        }
+        Ok(())
+    }
+}
+
+fn binary_operator(name: &str) -> Option<(&'static str, u8)> {
+    Some(match name {
+        operators::LOGICAL_OR => ("||", LOGICAL_OR),
+        operators::LOGICAL_AND => ("&&", LOGICAL_AND),
+        operators::EQUALS => ("==", RELATION),
+        operators::NOT_EQUALS => ("!=", RELATION),
+        operators::LESS => ("<", RELATION),
+        operators::LESS_EQUALS => ("<=", RELATION),
+        operators::GREATER => (">", RELATION),
+        operators::GREATER_EQUALS => (">=", RELATION),
+        operators::IN => ("in", RELATION),
+        operators::ADD => ("+", ADDITION),
+        operators::SUBSTRACT => ("-", ADDITION),
+        operators::MULTIPLY => ("*", MULTIPLICATION),
+        operators::DIVIDE => ("/", MULTIPLICATION),
+        operators::MODULO => ("%", MULTIPLICATION),
+        _ => return None,
+    })
+}
+
+fn precedence(expr: &IdedExpr) -> u8 {
+    match &expr.expr {
+        Expr::Call(call) => match (call.func_name.as_str(), call.args.len()) {
+            (operators::CONDITIONAL, 3) => CONDITIONAL,
+            (operators::LOGICAL_NOT | operators::NEGATE, 1) => UNARY,
+            (name, 2) => binary_operator(name).map_or(MEMBER, |(_, precedence)| precedence),
+            _ => MEMBER,
+        },
+        _ if is_negative_literal(expr) => UNARY,
+        _ => MEMBER,
+    }
+}
+
+fn is_negative_literal(expr: &IdedExpr) -> bool {
+    match &expr.expr {
+        Expr::Literal(CelVal::Int(i)) => *i < 0,
+        Expr::Literal(CelVal::Double(d)) => d.is_sign_negative(),
+        _ => false,
+    }
+}
+
+/// Returns the variable, initializer and result of the `cel.bind` which expanded into
+/// `comprehension`.
+fn bound(comprehension: &ComprehensionExpr) -> Option<(&str, &IdedExpr, &IdedExpr)> {
+    let accu = comprehension.accu_var.as_str();
+    let is_bind = comprehension.iter_var == BIND_ITER_VAR
+        && comprehension.iter_var2.is_none()
+        && matches!(&comprehension.iter_range.expr, Expr::List(list) if list.elements.is_empty())
+        && matches!(
+            comprehension.loop_cond.expr,
+            Expr::Literal(CelVal::Boolean(false))
+        )
+        && matches!(&comprehension.loop_step.expr, Expr::Ident(name) if name == accu);
+    is_bind.then_some((accu, &comprehension.accu_init, &comprehension.result))
+}
+
+/// Returns the name and the arguments, other than the iteration variables, of the macro which
+/// expanded into `comprehension`.
+fn resugar(comprehension: &ComprehensionExpr) -> Option<(&'static str, Vec<&IdedExpr>)> {
+    let two_vars = comprehension.iter_var2.is_some();
+    let accu = comprehension.accu_var.as_str();
+    let is_accu = |expr: &IdedExpr| matches!(&expr.expr, Expr::Ident(name) if name == accu);
+    let Expr::Call(step) = &comprehension.loop_step.expr else {
+        return None;
+    };
+    match (
+        &comprehension.accu_init.expr,
+        step.func_name.as_str(),
+        step.args.as_slice(),
+    ) {
+        (Expr::Literal(CelVal::Boolean(true)), operators::LOGICAL_AND, [a, predicate])
+            if is_accu(a) =>
+        {
+            Some((operators::ALL, vec![predicate]))
+        }
+        (Expr::Literal(CelVal::Boolean(false)), operators::LOGICAL_OR, [a, predicate])
+            if is_accu(a) =>
+        {
+            Some((operators::EXISTS, vec![predicate]))
+        }
+        (Expr::Literal(CelVal::Int(0)), operators::CONDITIONAL, [predicate, _, otherwise])
+            if is_accu(otherwise) =>
+        {
+            match two_vars {
+                true => Some(("existsOne", vec![predicate])),
+                false => Some((operators::EXISTS_ONE, vec![predicate])),
+            }
+        }
+        (Expr::List(init), operators::ADD, _) if init.elements.is_empty() => {
+            let element = appended(&comprehension.loop_step, accu)?;
+            match two_vars {
+                true => Some((operators::TRANSFORM_LIST, vec![element])),
+                false => Some((operators::MAP, vec![element])),
+            }
+        }
+        (Expr::List(init), operators::CONDITIONAL, [predicate, add, otherwise])
+            if init.elements.is_empty() && is_accu(otherwise) =>
+        {
+            let element = appended(add, accu)?;
+            match &element.expr {
+                _ if two_vars => Some((operators::TRANSFORM_LIST, vec![predicate, element])),
+                Expr::Ident(v) if *v == comprehension.iter_var => {
+                    Some((operators::FILTER, vec![predicate]))
+                }
+                _ => Some((operators::MAP, vec![predicate, element])),
+            }
+        }
+        (Expr::Map(init), operators::MAP_INSERT, _) if init.entries.is_empty() && two_vars => {
+            let (name, value) = inserted(&comprehension.loop_step, comprehension)?;
+            Some((name, vec![value]))
+        }
+        (Expr::Map(init), operators::CONDITIONAL, [predicate, insert, otherwise])
+            if init.entries.is_empty() && two_vars && is_accu(otherwise) =>
+        {
+            let (name, value) = inserted(insert, comprehension)?;
+            Some((name, vec![predicate, value]))
+        }
+        _ => None,
+    }
+}
+
+/// Matches `accu + [element]`, as accumulated by the `map` and `filter` macros.
+fn appended<'a>(expr: &'a IdedExpr, accu: &str) -> Option<&'a IdedExpr> {
+    let Expr::Call(call) = &expr.expr else {
+        return None;
+    };
+    match (call.func_name.as_str(), call.args.as_slice()) {
+        (operators::ADD, [a, list]) if matches!(&a.expr, Expr::Ident(name) if name == accu) => {
+            match &list.expr {
+                Expr::List(list) if list.elements.len() == 1 => Some(&list.elements[0]),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Matches `cel.@mapInsert(accu, k, value)` and `cel.@mapInsert(accu, entry)`, as accumulated by
+/// the `transformMap` and `transformMapEntry` macros.
+fn inserted<'a>(
+    expr: &'a IdedExpr,
+    comprehension: &ComprehensionExpr,
+) -> Option<(&'static str, &'a IdedExpr)> {
+    let is_ident =
+        |expr: &IdedExpr, ident: &str| matches!(&expr.expr, Expr::Ident(name) if name == ident);
+    let Expr::Call(call) = &expr.expr else {
+        return None;
+    };
+    if call.func_name != operators::MAP_INSERT {
+        return None;
+    }
+    match call.args.as_slice() {
+        [a, key, value]
+            if is_ident(a, &comprehension.accu_var) && is_ident(key, &comprehension.iter_var) =>
+        {
+            Some((operators::TRANSFORM_MAP, value))
+        }
+        [a, entry] if is_ident(a, &comprehension.accu_var) => {
+            Some((operators::TRANSFORM_MAP_ENTRY, entry))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn assert_unparse(source: &str, expected: &str) {
+        let expr = Parser::new().parse(source).unwrap();
+        assert_eq!(unparse(&expr).unwrap(), expected, "{source}");
+        // The result must parse back to the same expression, ids aside.
+        let reparsed = Parser::new().parse(expected).unwrap();
+        assert_eq!(unparse(&reparsed).unwrap(), expected, "{source}");
+    }
+
+    #[test]
+    fn unparse_canonical() {
+        for source in [
+            "a",
+            "a.b.c",
+            "a[0]",
+            "f(a, b)",
+            "a.f()",
+            "a.f(b, c)",
+            "[1, 2, 3]",
+            "{\"a\": 1, 2: b}",
+            "Account{user_id: 1, name: \"a\"}",
+            "has(a.b)",
+            "!a",
+            "-a",
+            "a + b * c",
+            "(a + b) * c",
+            "a - (b - c)",
+            "a - b - c",
+            "a && b || c",
+            "a && (b || c)",
+            "a || b || c",
+            "a ? b : c",
+            "(a ? b : c) ? d : e ? f : g",
+            "a in [1, 2]",
+            "(a + b).f()",
+            "(-1).f()",
+            "!(a == b)",
+            "!(!a)",
+            "-(-a)",
+            "-(a + b)",
+            "true == false",
+            "null",
+            "1u",
+            "1.5",
+            "1.0",
+            "-2",
+            "\"a\\\"b\\\\c\\n\"",
+            "b\"\\xff\\x00a\"",
+            "[1, 2].all(x, x > 0)",
+            "[1, 2].exists(x, x > 0)",
+            "[1, 2].map(x, x * 2)",
+            "[1, 2].filter(x, x % 2 == 0)",
+            "(a + [1]).exists(x, x > 0)",
+            "optional.of(1)",
+            "[1, 2].exists_one(x, x > 1)",
+            "[1, 2].map(x, x > 1, x * 2)",
+            "[1, 2].all(i, v, i < v)",
+            "m.exists(k, v, k == v)",
+            "m.existsOne(k, v, v > 1)",
+            "[1, 2].transformList(i, v, i * v)",
+            "[1, 2].transformList(i, v, i > 0, v)",
+            "m.transformMap(k, v, v + 1)",
+            "m.transformMap(k, v, v > 0, k)",
+            "m.transformMapEntry(k, v, {v: k})",
+            "m.transformMapEntry(k, v, v > 0, {v: k})",
+            "[[1], [2]].all(l, l.exists(x, x > 0))",
+            "a.?b",
+            "a.?b.c",
+            "a[?0]",
+            "(a ? b : c).?d",
+            "[?a, b]",
+            "{?\"a\": b, \"c\": d}",
+            "Account{?name: a, id: 1}",
+            "\"é\\u0001\"",
+            "cel.bind(x, a + 1, x * x)",
+            "cel.bind(x, 1, cel.bind(y, x + 1, [x, y]))",
+            "cel.block([a + 1, cel.index(0) * 2], cel.index(1) > cel.index(0))",
+            "cel.block([a], [1, 2].all(x, x < cel.index(0)))",
+        ] {
+            assert_unparse(source, source);
+        }
+    }
+
+    #[test]
+    fn unparse_normalizes() {
+        for (source, expected) in [
+            ("((a))", "a"),
+            ("a+b", "a + b"),
+            ("'a'", "\"a\""),
+            ("(a * b) + c", "a * b + c"),
+            ("(a) ? (b) : (c)", "a ? b : c"),
+            ("1.0e100", "1e100"),
+            ("0x10", "16"),
+            ("[1, 2].existsOne(x, x > 1)", "[1, 2].exists_one(x, x > 1)"),
+            ("[1, 2].map(x, x > 1, x)", "[1, 2].filter(x, x > 1)"),
+        ] {
+            assert_unparse(source, expected);
+        }
+    }
+
+    #[test]
+    fn unparse_built_expressions() {
+        let ident = |name: &str| IdedExpr {
+            id: 0,
+            expr: Expr::Ident(name.to_string()),
+        };
+        let call = |name: &str, args: Vec<IdedExpr>| IdedExpr {
+            id: 0,
+            expr: Expr::Call(CallExpr {
+                func_name: name.to_string(),
+                target: None,
+                args,
+            }),
+        };
+
+        let sum = call(operators::ADD, vec![ident("a"), ident("b")]);
+        let product = call(operators::MULTIPLY, vec![sum.clone(), ident("c")]);
+        assert_eq!(unparse(&product).unwrap(), "(a + b) * c");
+
+        let conditional = call(
+            operators::CONDITIONAL,
+            vec![ident("a"), ident("b"), ident("c")],
+        );
+        let select = IdedExpr {
+            id: 0,
+            expr: Expr::Select(crate::common::ast::SelectExpr {
+                operand: Box::new(conditional.clone()),
+                field: "d".to_string(),
+                test: false,
+            }),
+        };
+        assert_eq!(unparse(&select).unwrap(), "(a ? b : c).d");
+
+        let nested = call(
+            operators::CONDITIONAL,
+            vec![conditional.clone(), conditional.clone(), conditional],
+        );
+        assert_eq!(
+            unparse(&nested).unwrap(),
+            "(a ? b : c) ? (a ? b : c) : a ? b : c"
+        );
+
+        let negated = call(operators::LOGICAL_NOT, vec![sum]);
+        assert_eq!(unparse(&negated).unwrap(), "!(a + b)");
+    }
+
+    #[test]
+    fn unparse_errors() {
+        // A `map` accumulating from 0 rather than from an empty list, which no macro expands to.
+        let mut expr = Parser::new().parse("[1, 2].map(x, x)").unwrap();
+        let Expr::Comprehension(comprehension) = &mut expr.expr else {
+            unreachable!()
+        };
+        comprehension.accu_init.expr = Expr::Literal(CelVal::Int(0));
+        assert_eq!(unparse(&expr), Err(UnparseError::Comprehension));
+
+        let literal = IdedExpr {
+            id: 0,
+            expr: Expr::Literal(CelVal::Unknown),
+        };
+        let list = IdedExpr {
+            id: 1,
+            expr: Expr::List(crate::common::ast::ListExpr {
+                elements: vec![literal],
+                optional_indices: vec![],
+            }),
+        };
+        assert_eq!(unparse(&list), Err(UnparseError::Literal(CelVal::Unknown)));
+    }
+}
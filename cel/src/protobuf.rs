@@ -0,0 +1,545 @@
+//! Conversion of protobuf messages to CEL values, backed by the descriptors of
+//! [`prost_reflect`].
+//!
+//! Messages become [`Value::Struct`]s whose fields can be selected and tested with `has()`,
+//! with the presence semantics of protobuf: singular messages, and scalars declared
+//! `optional` or in a proto2 file, are present when set, while other scalars are present
+//! when they don't have their default value, and repeated fields when they aren't empty.
+//!
+//! The well-known types are converted to the matching CEL values:
+//! - `google.protobuf.Timestamp` and `google.protobuf.Duration` to timestamps and durations
+//! - the wrapper types, e.g. `google.protobuf.Int64Value`, to the value they wrap, and unset
+//!   wrapper fields to `null`
+//! - `google.protobuf.Struct`, `ListValue` and `Value` to the maps, lists and values of the
+//!   matching JSON
+//! - `google.protobuf.Any` to the value of the message it packs, whose type is looked up in
+//!   the descriptor pool
+//!
+//! # Example
+//! ```
+//! use cel::protobuf::ProtoTypes;
+//! use cel::{Context, Program};
+//! use prost_reflect::{DescriptorPool, DynamicMessage, Value as ProtoValue};
+//!
+//! // Usually `ProtoTypes::from_descriptor_set(include_bytes!(...))`.
+//! let types = ProtoTypes::new(DescriptorPool::global());
+//!
+//! let descriptor = types.pool().get_message_by_name("google.protobuf.Api").unwrap();
+//! let mut api = DynamicMessage::new(descriptor);
+//! api.set_field_by_name("name", ProtoValue::String("Library".to_string()));
+//!
+//! let mut context = Context::default();
+//! context.add_variable_from_value("api", types.to_value(&api).unwrap());
+//! context.set_type_provider(types);
+//!
+//! let program = Program::compile("api.name == 'Library' && !has(api.version)").unwrap();
+//! assert_eq!(program.execute(&context), Ok(true.into()));
+//! ```
+
+use crate::common::types::{Type, DURATION_TYPE, DYN_TYPE, STRING_TYPE, TIMESTAMP_TYPE};
+use crate::objects::{Key, Map};
+use crate::structs::{Struct, StructType, TypeProvider};
+use crate::Value;
+use prost_reflect::prost::DecodeError;
+use prost_reflect::{
+    DescriptorError, DescriptorPool, DynamicMessage, FieldDescriptor, Kind, MapKey,
+    MessageDescriptor, ReflectMessage, Value as ProtoValue,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+const ANY: &str = "google.protobuf.Any";
+const DURATION: &str = "google.protobuf.Duration";
+const LIST_VALUE: &str = "google.protobuf.ListValue";
+const STRUCT: &str = "google.protobuf.Struct";
+const TIMESTAMP: &str = "google.protobuf.Timestamp";
+const VALUE: &str = "google.protobuf.Value";
+const WRAPPERS: [&str; 9] = [
+    "google.protobuf.BoolValue",
+    "google.protobuf.BytesValue",
+    "google.protobuf.DoubleValue",
+    "google.protobuf.FloatValue",
+    "google.protobuf.Int32Value",
+    "google.protobuf.Int64Value",
+    "google.protobuf.StringValue",
+    "google.protobuf.UInt32Value",
+    "google.protobuf.UInt64Value",
+];
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ProtoError {
+    /// The type of a message packed in a `google.protobuf.Any` isn't in the descriptor pool.
+    #[error("unknown message type '{0}'")]
+    UnknownType(String),
+    #[error("failed to decode message: {0}")]
+    Decode(#[from] DecodeError),
+    /// A timestamp or duration which is out of the range of CEL values.
+    #[error("{0} out of range")]
+    OutOfRange(&'static str),
+}
+
+/// The message types of a protobuf [`DescriptorPool`], which converts messages to [`Value`]s
+/// and, as a [`TypeProvider`], lets expressions construct them, e.g. `pkg.Message{id: 1}`.
+///
+/// Cloning is cheap: the pool and the converted struct types are shared.
+#[derive(Clone, Debug)]
+pub struct ProtoTypes {
+    pool: DescriptorPool,
+    types: Arc<Mutex<HashMap<String, Arc<StructType>>>>,
+}
+
+impl ProtoTypes {
+    pub fn new(pool: DescriptorPool) -> Self {
+        ProtoTypes {
+            pool,
+            types: Default::default(),
+        }
+    }
+
+    /// Creates the types of an encoded `google.protobuf.FileDescriptorSet`, e.g. as written by
+    /// `protoc --descriptor_set_out`, in addition to the well-known types.
+    pub fn from_descriptor_set(bytes: &[u8]) -> Result<Self, DescriptorError> {
+        let mut pool = DescriptorPool::global();
+        pool.decode_file_descriptor_set(bytes)?;
+        Ok(ProtoTypes::new(pool))
+    }
+
+    pub fn pool(&self) -> &DescriptorPool {
+        &self.pool
+    }
+
+    /// Decodes a message of the type named `name`, e.g. `pkg.Message`, and converts it to a
+    /// value.
+    pub fn decode(&self, name: &str, bytes: &[u8]) -> Result<Value, ProtoError> {
+        let descriptor = self
+            .pool
+            .get_message_by_name(name)
+            .ok_or_else(|| ProtoError::UnknownType(name.to_string()))?;
+        self.to_value(&DynamicMessage::decode(descriptor, bytes)?)
+    }
+
+    /// Converts a message to a value, see the [module documentation](self).
+    pub fn to_value(&self, message: &DynamicMessage) -> Result<Value, ProtoError> {
+        let descriptor = message.descriptor();
+        match descriptor.full_name() {
+            TIMESTAMP => {
+                let (seconds, nanos) = seconds_and_nanos(message);
+                chrono::DateTime::from_timestamp(seconds, nanos as u32)
+                    .map(|timestamp| Value::Timestamp(timestamp.fixed_offset()))
+                    .ok_or(ProtoError::OutOfRange("timestamp"))
+            }
+            DURATION => {
+                let (seconds, nanos) = seconds_and_nanos(message);
+                chrono::Duration::try_seconds(seconds)
+                    .and_then(|d| d.checked_add(&chrono::Duration::nanoseconds(nanos as i64)))
+                    .map(Value::Duration)
+                    .ok_or(ProtoError::OutOfRange("duration"))
+            }
+            STRUCT | LIST_VALUE => match message.fields().next() {
+                Some((_, value)) => self.convert(value),
+                None if descriptor.full_name() == STRUCT => {
+                    Ok(HashMap::<String, Value>::new().into())
+                }
+                None => Ok(Value::List(Arc::default())),
+            },
+            // The kind of a `google.protobuf.Value` is a oneof, of which at most one field is
+            // present.
+            VALUE => match message.fields().next() {
+                Some((field, _)) if field.name() == "null_value" => Ok(Value::Null),
+                Some((_, value)) => self.convert(value),
+                None => Ok(Value::Null),
+            },
+            ANY => {
+                let type_url = message
+                    .get_field_by_name("type_url")
+                    .and_then(|url| url.as_str().map(str::to_string))
+                    .unwrap_or_default();
+                let value = message
+                    .get_field_by_name("value")
+                    .and_then(|value| value.as_bytes().cloned())
+                    .unwrap_or_default();
+                // The name of the type is the last segment of its URL.
+                let name = type_url.rsplit('/').next().unwrap_or_default();
+                self.decode(name, &value)
+            }
+            name if WRAPPERS.contains(&name) => match message.get_field_by_name("value") {
+                Some(value) => self.convert(&value),
+                None => Ok(Value::Null),
+            },
+            _ => {
+                let fields = message
+                    .fields()
+                    .map(|(field, value)| Ok((field.name().to_string(), self.convert(value)?)))
+                    .collect::<Result<BTreeMap<_, _>, ProtoError>>()?;
+                let ty = self.struct_type(&descriptor);
+                Ok(Value::Struct(Struct::new(ty, fields).expect(
+                    "the fields of a message match the types of its descriptor",
+                )))
+            }
+        }
+    }
+
+    fn convert(&self, value: &ProtoValue) -> Result<Value, ProtoError> {
+        Ok(match value {
+            ProtoValue::Bool(b) => Value::Bool(*b),
+            ProtoValue::I32(i) => Value::Int(*i as i64),
+            ProtoValue::I64(i) => Value::Int(*i),
+            ProtoValue::U32(u) => Value::UInt(*u as u64),
+            ProtoValue::U64(u) => Value::UInt(*u),
+            ProtoValue::F32(f) => Value::Float(*f as f64),
+            ProtoValue::F64(f) => Value::Float(*f),
+            ProtoValue::String(s) => Value::String(Arc::new(s.clone())),
+            ProtoValue::Bytes(b) => Value::Bytes(Arc::new(b.to_vec())),
+            ProtoValue::EnumNumber(n) => Value::Int(*n as i64),
+            ProtoValue::Message(message) => self.to_value(message)?,
+            ProtoValue::List(values) => Value::List(Arc::new(
+                values
+                    .iter()
+                    .map(|value| self.convert(value))
+                    .collect::<Result<_, _>>()?,
+            )),
+            ProtoValue::Map(entries) => {
+                let map = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        let key = match key {
+                            MapKey::Bool(b) => Key::Bool(*b),
+                            MapKey::I32(i) => Key::Int(*i as i64),
+                            MapKey::I64(i) => Key::Int(*i),
+                            MapKey::U32(u) => Key::Uint(*u as u64),
+                            MapKey::U64(u) => Key::Uint(*u),
+                            MapKey::String(s) => Key::String(Arc::new(s.clone())),
+                        };
+                        Ok((key, self.convert(value)?))
+                    })
+                    .collect::<Result<_, ProtoError>>()?;
+                Value::Map(Map { map: Arc::new(map) })
+            }
+        })
+    }
+
+    /// The struct type of a message, with the CEL types of its fields.
+    fn struct_type(&self, descriptor: &MessageDescriptor) -> Arc<StructType> {
+        let mut types = self.types.lock().unwrap();
+        if let Some(ty) = types.get(descriptor.full_name()) {
+            return ty.clone();
+        }
+        let ty = Arc::new(
+            descriptor
+                .fields()
+                .fold(StructType::new(descriptor.full_name()), |ty, field| {
+                    ty.field(field.name(), field_type(&field))
+                }),
+        );
+        types.insert(descriptor.full_name().to_string(), ty.clone());
+        ty
+    }
+}
+
+impl TypeProvider for ProtoTypes {
+    fn find_struct_type(&self, name: &str) -> Option<Arc<StructType>> {
+        let descriptor = self.pool.get_message_by_name(name)?;
+        if is_well_known(descriptor.full_name()) {
+            return None;
+        }
+        Some(self.struct_type(&descriptor))
+    }
+}
+
+fn is_well_known(name: &str) -> bool {
+    [ANY, DURATION, LIST_VALUE, STRUCT, TIMESTAMP, VALUE].contains(&name)
+        || WRAPPERS.contains(&name)
+}
+
+fn seconds_and_nanos(message: &DynamicMessage) -> (i64, i32) {
+    let seconds = message
+        .get_field_by_name("seconds")
+        .and_then(|v| v.as_i64());
+    let nanos = message.get_field_by_name("nanos").and_then(|v| v.as_i32());
+    (seconds.unwrap_or_default(), nanos.unwrap_or_default())
+}
+
+fn field_type(field: &FieldDescriptor) -> Type<'static> {
+    match field.kind() {
+        Kind::Message(entry) if field.is_map() => Type::new_map(
+            &kind_type(entry.map_entry_key_field().kind()),
+            &kind_type(entry.map_entry_value_field().kind()),
+        ),
+        kind if field.is_list() => Type::new_list(&kind_type(kind)),
+        kind => kind_type(kind),
+    }
+}
+
+fn kind_type(kind: Kind) -> Type<'static> {
+    use crate::common::types::{BOOL_TYPE, BYTES_TYPE, DOUBLE_TYPE, INT_TYPE, UINT_TYPE};
+    match kind {
+        Kind::Double | Kind::Float => DOUBLE_TYPE,
+        Kind::Int32 | Kind::Int64 | Kind::Sint32 | Kind::Sint64 | Kind::Sfixed32 => INT_TYPE,
+        Kind::Sfixed64 | Kind::Enum(_) => INT_TYPE,
+        Kind::Uint32 | Kind::Uint64 | Kind::Fixed32 | Kind::Fixed64 => UINT_TYPE,
+        Kind::Bool => BOOL_TYPE,
+        Kind::String => STRING_TYPE,
+        Kind::Bytes => BYTES_TYPE,
+        Kind::Message(message) => match message.full_name() {
+            TIMESTAMP => TIMESTAMP_TYPE,
+            DURATION => DURATION_TYPE,
+            STRUCT => Type::new_map(&STRING_TYPE, &DYN_TYPE),
+            LIST_VALUE => Type::new_list(&DYN_TYPE),
+            // A wrapper holds either its value or null, a `Value` or an `Any` any value.
+            name if is_well_known(name) => DYN_TYPE,
+            name => Type::new_object(name),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Context, ExecutionError, Program};
+    use prost_reflect::prost::Message;
+    use prost_reflect::prost_types::field_descriptor_proto::{Label, Type as FieldType};
+    use prost_reflect::prost_types::{
+        DescriptorProto, FieldDescriptorProto, FileDescriptorProto, MessageOptions,
+        OneofDescriptorProto,
+    };
+
+    fn field(name: &str, number: i32, ty: FieldType) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(ty as i32),
+            ..Default::default()
+        }
+    }
+
+    fn message_field(name: &str, number: i32, type_name: &str) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            type_name: Some(format!(".{type_name}")),
+            ..field(name, number, FieldType::Message)
+        }
+    }
+
+    /// The types of `test.proto`:
+    /// ```proto
+    /// syntax = "proto3";
+    /// package test;
+    ///
+    /// message Account {
+    ///   int64 id = 1;
+    ///   optional string nickname = 2;
+    ///   repeated string groups = 3;
+    ///   map<string, int64> limits = 4;
+    ///   google.protobuf.Int64Value quota = 5;
+    ///   google.protobuf.Timestamp created = 6;
+    ///   google.protobuf.Struct labels = 7;
+    ///   google.protobuf.Any extra = 8;
+    ///   Account owner = 9;
+    /// }
+    /// ```
+    fn types() -> ProtoTypes {
+        let account = DescriptorProto {
+            name: Some("Account".to_string()),
+            field: vec![
+                field("id", 1, FieldType::Int64),
+                FieldDescriptorProto {
+                    oneof_index: Some(0),
+                    proto3_optional: Some(true),
+                    ..field("nickname", 2, FieldType::String)
+                },
+                FieldDescriptorProto {
+                    label: Some(Label::Repeated as i32),
+                    ..field("groups", 3, FieldType::String)
+                },
+                FieldDescriptorProto {
+                    label: Some(Label::Repeated as i32),
+                    ..message_field("limits", 4, "test.Account.LimitsEntry")
+                },
+                message_field("quota", 5, "google.protobuf.Int64Value"),
+                message_field("created", 6, TIMESTAMP),
+                message_field("labels", 7, STRUCT),
+                message_field("extra", 8, ANY),
+                message_field("owner", 9, "test.Account"),
+            ],
+            nested_type: vec![DescriptorProto {
+                name: Some("LimitsEntry".to_string()),
+                field: vec![
+                    field("key", 1, FieldType::String),
+                    field("value", 2, FieldType::Int64),
+                ],
+                options: Some(MessageOptions {
+                    map_entry: Some(true),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+            oneof_decl: vec![OneofDescriptorProto {
+                name: Some("_nickname".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("test.proto".to_string()),
+            package: Some("test".to_string()),
+            dependency: vec![
+                "google/protobuf/any.proto".to_string(),
+                "google/protobuf/struct.proto".to_string(),
+                "google/protobuf/timestamp.proto".to_string(),
+                "google/protobuf/wrappers.proto".to_string(),
+            ],
+            message_type: vec![account],
+            syntax: Some("proto3".to_string()),
+            ..Default::default()
+        };
+        let mut pool = DescriptorPool::global();
+        pool.add_file_descriptor_proto(file).unwrap();
+        ProtoTypes::new(pool)
+    }
+
+    fn account(types: &ProtoTypes) -> DynamicMessage {
+        let descriptor = types.pool().get_message_by_name("test.Account").unwrap();
+        let mut owner = DynamicMessage::new(descriptor.clone());
+        owner.set_field_by_name("id", ProtoValue::I64(1));
+
+        let labels = prost_reflect::prost_types::Struct {
+            fields: [
+                (
+                    "team",
+                    prost_reflect::prost_types::value::Kind::StringValue("core".into()),
+                ),
+                (
+                    "level",
+                    prost_reflect::prost_types::value::Kind::NumberValue(2.0),
+                ),
+                (
+                    "archived",
+                    prost_reflect::prost_types::value::Kind::NullValue(0),
+                ),
+            ]
+            .into_iter()
+            .map(|(k, v)| {
+                (
+                    k.to_string(),
+                    prost_reflect::prost_types::Value { kind: Some(v) },
+                )
+            })
+            .collect(),
+        };
+        let extra = prost_reflect::prost_types::Any {
+            type_url: "type.googleapis.com/test.Account".to_string(),
+            value: owner.encode_to_vec(),
+        };
+
+        let mut account = DynamicMessage::new(descriptor);
+        account.set_field_by_name("id", ProtoValue::I64(42));
+        account.set_field_by_name(
+            "groups",
+            ProtoValue::List(vec![ProtoValue::String("admins".to_string())]),
+        );
+        account.set_field_by_name(
+            "limits",
+            ProtoValue::Map(HashMap::from([(
+                MapKey::String("requests".to_string()),
+                ProtoValue::I64(100),
+            )])),
+        );
+        account.set_field_by_name(
+            "created",
+            ProtoValue::Message(
+                prost_reflect::prost_types::Timestamp {
+                    seconds: 1_700_000_000,
+                    nanos: 0,
+                }
+                .transcode_to_dynamic(),
+            ),
+        );
+        account.set_field_by_name("labels", ProtoValue::Message(labels.transcode_to_dynamic()));
+        account.set_field_by_name("extra", ProtoValue::Message(extra.transcode_to_dynamic()));
+        account.set_field_by_name("owner", ProtoValue::Message(owner));
+        account
+    }
+
+    fn execute(expr: &str) -> Result<Value, ExecutionError> {
+        let types = types();
+        let mut context = Context::default();
+        context.add_variable_from_value("account", types.to_value(&account(&types)).unwrap());
+        context.set_type_provider(types);
+        Program::compile(expr).unwrap().execute(&context)
+    }
+
+    #[test]
+    fn messages() {
+        for expr in [
+            "account.id == 42",
+            "account.groups == ['admins']",
+            "account.limits.requests == 100",
+            "account.owner.id == 1",
+            "account.owner.owner == null",
+            "account.created == timestamp('2023-11-14T22:13:20Z')",
+            "account.labels == {'team': 'core', 'level': 2.0, 'archived': null}",
+            "account.extra == test.Account{id: 1}",
+            "account.extra.id == 1",
+            "account.quota == null",
+            "account.nickname == ''",
+            "test.Account{quota: 1}.quota == 1",
+        ] {
+            assert_eq!(execute(expr), Ok(Value::Bool(true)), "{expr}");
+        }
+    }
+
+    #[test]
+    fn presence() {
+        for (expr, expected) in [
+            ("has(account.id)", true),
+            ("has(account.owner.id)", true),
+            ("has(account.owner.groups)", false),
+            ("has(account.nickname)", false),
+            ("has(account.quota)", false),
+            ("has(account.created)", true),
+            ("has(account.owner.owner)", false),
+        ] {
+            assert_eq!(execute(expr), Ok(Value::Bool(expected)), "{expr}");
+        }
+
+        // A scalar without explicit presence isn't present when it has its default value,
+        // unlike one declared `optional`.
+        let types = types();
+        let descriptor = types.pool().get_message_by_name("test.Account").unwrap();
+        let mut message = DynamicMessage::new(descriptor);
+        message.set_field_by_name("id", ProtoValue::I64(0));
+        message.set_field_by_name("nickname", ProtoValue::String(String::new()));
+        message.set_field_by_name("quota", ProtoValue::Message(0i64.transcode_to_dynamic()));
+        let Value::Struct(account) = types.to_value(&message).unwrap() else {
+            panic!("expected a struct");
+        };
+        assert!(!account.has("id"));
+        assert!(account.has("nickname"));
+        assert!(account.has("quota"));
+        assert_eq!(account.get("quota"), Some(&Value::Int(0)));
+    }
+
+    #[test]
+    fn unknown_any_type() {
+        let types = types();
+        let any = prost_reflect::prost_types::Any {
+            type_url: "type.googleapis.com/test.Unknown".to_string(),
+            value: Vec::new(),
+        };
+        assert_eq!(
+            types.to_value(&any.transcode_to_dynamic()),
+            Err(ProtoError::UnknownType("test.Unknown".to_string()))
+        );
+        assert_eq!(
+            types.decode("test.Account", &[0xff]).map(|_| ()),
+            Err(ProtoError::Decode(
+                DynamicMessage::decode(
+                    types.pool().get_message_by_name("test.Account").unwrap(),
+                    &[0xff][..]
+                )
+                .unwrap_err()
+            ))
+        );
+    }
+}
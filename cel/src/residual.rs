@@ -0,0 +1,508 @@
+use crate::common::ast::{
+    operators, CallExpr, ComprehensionExpr, EntryExpr, Expr, IdedEntryExpr, IdedExpr, ListExpr,
+    MapEntryExpr, MapExpr, SelectExpr, StructExpr, StructFieldExpr,
+};
+use crate::common::value::CelVal;
+use crate::context::Context;
+use crate::objects::{ResolveResult, Value};
+use crate::parser;
+use crate::unknown;
+
+/// Returns the residual of `expr`, in which every subexpression which could be evaluated in
+/// `ctx` is replaced by its value. See [`crate::Program::residual`].
+pub(crate) fn residual(expr: &IdedExpr, ctx: &Context) -> IdedExpr {
+    let mut residual = Residual {
+        ctx,
+        next_id: max_id(expr) + 1,
+    };
+    residual.prune(expr).expr
+}
+
+/// The prefix of the variables holding the values of the children of an expression when it is
+/// evaluated.
+const PLACEHOLDER_PREFIX: &str = "@residual";
+
+struct Residual<'c, 'a> {
+    ctx: &'c Context<'a>,
+    // The id of the next expression created to hold an evaluated value.
+    next_id: u64,
+}
+
+/// A pruned expression, along with its value if it could be evaluated.
+struct Pruned {
+    expr: IdedExpr,
+    value: Option<Value>,
+}
+
+impl Residual<'_, '_> {
+    /// Prunes the children of `expr` before evaluating it, once, from their values.
+    fn prune(&mut self, expr: &IdedExpr) -> Pruned {
+        // The known parts of an unknown attribute, e.g. `request` in `request.auth`, may not
+        // hold its actual value.
+        if unknown::is_attribute(expr) || matches!(expr.expr, Expr::Literal(_)) {
+            let value = Value::resolve(expr, self.ctx);
+            return self.fold(expr.clone(), value);
+        }
+        match &expr.expr {
+            Expr::Call(call) => self.prune_call(expr.id, call),
+            // The initializers of `cel.block` are only evaluated if their index is used.
+            Expr::Comprehension(comprehension) if parser::is_block_binding(comprehension) => {
+                self.evaluate(expr.id, Vec::new(), |_| expr.expr.clone())
+            }
+            Expr::Comprehension(comprehension) => {
+                // The other expressions depend on the iteration and accumulator variables.
+                let children = vec![
+                    self.prune(&comprehension.iter_range),
+                    self.prune(&comprehension.accu_init),
+                ];
+                self.evaluate(expr.id, children, |mut children| {
+                    let accu_init = children.pop().unwrap();
+                    let iter_range = children.pop().unwrap();
+                    Expr::Comprehension(Box::new(ComprehensionExpr {
+                        iter_range,
+                        accu_init,
+                        ..comprehension.as_ref().clone()
+                    }))
+                })
+            }
+            Expr::List(list) => {
+                let children = list.elements.iter().map(|e| self.prune(e)).collect();
+                self.evaluate(expr.id, children, |elements| {
+                    Expr::List(ListExpr {
+                        elements,
+                        optional_indices: list.optional_indices.clone(),
+                    })
+                })
+            }
+            Expr::Map(MapExpr { entries }) | Expr::Struct(StructExpr { entries, .. }) => {
+                let children = entries
+                    .iter()
+                    .flat_map(|entry| match &entry.expr {
+                        EntryExpr::StructField(field) => vec![&field.value],
+                        EntryExpr::MapEntry(entry) => vec![&entry.key, &entry.value],
+                    })
+                    .map(|e| self.prune(e))
+                    .collect();
+                self.evaluate(expr.id, children, |children| {
+                    let mut children = children.into_iter();
+                    let entries = entries
+                        .iter()
+                        .map(|entry| IdedEntryExpr {
+                            id: entry.id,
+                            expr: match &entry.expr {
+                                EntryExpr::StructField(field) => {
+                                    EntryExpr::StructField(StructFieldExpr {
+                                        value: children.next().unwrap(),
+                                        ..field.clone()
+                                    })
+                                }
+                                EntryExpr::MapEntry(entry) => EntryExpr::MapEntry(MapEntryExpr {
+                                    key: children.next().unwrap(),
+                                    value: children.next().unwrap(),
+                                    optional: entry.optional,
+                                }),
+                            },
+                        })
+                        .collect();
+                    match &expr.expr {
+                        Expr::Struct(s) => Expr::Struct(StructExpr {
+                            type_name: s.type_name.clone(),
+                            entries,
+                        }),
+                        _ => Expr::Map(MapExpr { entries }),
+                    }
+                })
+            }
+            Expr::Select(select) => {
+                let operand = self.prune(&select.operand);
+                self.evaluate(expr.id, vec![operand], |mut children| {
+                    Expr::Select(SelectExpr {
+                        operand: Box::new(children.pop().unwrap()),
+                        field: select.field.clone(),
+                        test: select.test,
+                    })
+                })
+            }
+            Expr::Ident(_) | Expr::Literal(_) | Expr::Unspecified => Pruned {
+                expr: expr.clone(),
+                value: None,
+            },
+        }
+    }
+
+    fn prune_call(&mut self, id: u64, call: &CallExpr) -> Pruned {
+        match (call.func_name.as_str(), call.args.as_slice()) {
+            (operators::LOGICAL_AND | operators::LOGICAL_OR, [left, right]) => {
+                // Either side may decide the result, e.g. `x || true` is `true`, while the other
+                // value can be dropped, e.g. `true && x` is `x`.
+                let absorbing = call.func_name == operators::LOGICAL_OR;
+                let left = self.prune(left);
+                let right = self.prune(right);
+                if is_bool(&left, absorbing) || is_bool(&right, absorbing) {
+                    return Pruned {
+                        expr: IdedExpr {
+                            id,
+                            expr: Expr::Literal(CelVal::Boolean(absorbing)),
+                        },
+                        value: Some(Value::Bool(absorbing)),
+                    };
+                }
+                if is_bool(&left, !absorbing) {
+                    return right;
+                }
+                if is_bool(&right, !absorbing) {
+                    return left;
+                }
+                return self.evaluate(id, vec![left, right], |args| {
+                    global_call(&call.func_name, args)
+                });
+            }
+            (operators::CONDITIONAL, [cond, then, otherwise]) => {
+                let cond = self.prune(cond);
+                if is_bool(&cond, true) {
+                    return self.prune(then);
+                }
+                if is_bool(&cond, false) {
+                    return self.prune(otherwise);
+                }
+                let children = vec![cond, self.prune(then), self.prune(otherwise)];
+                return self.evaluate(id, children, |args| global_call(&call.func_name, args));
+            }
+            _ => {}
+        }
+        // The target of namespaced functions, e.g. `optional.of(x)`, isn't a value.
+        let namespaced = call
+            .target
+            .as_ref()
+            .and_then(|target| target.to_qualified_name())
+            .is_some_and(|namespace| {
+                let name = format!("{namespace}.{}", call.func_name);
+                self.ctx.get_function(&name).is_some()
+            });
+        let target = call.target.as_ref().filter(|_| !namespaced);
+        let children = target
+            .iter()
+            .map(|target| &***target)
+            .chain(&call.args)
+            .map(|e| self.prune(e))
+            .collect();
+        self.evaluate(id, children, |mut children| {
+            let target = match target {
+                Some(_) => Some(Box::new(children.remove(0))),
+                None => call.target.clone(),
+            };
+            Expr::Call(CallExpr {
+                func_name: call.func_name.clone(),
+                target,
+                args: children,
+            })
+        })
+    }
+
+    /// Builds the expression of `id` from its pruned children, which is evaluated if all of them
+    /// could be, with the children bound to their values rather than evaluated again.
+    fn evaluate<F>(&mut self, id: u64, children: Vec<Pruned>, build: F) -> Pruned
+    where
+        F: Fn(Vec<IdedExpr>) -> Expr,
+    {
+        let mut ctx = self.ctx.new_inner_scope();
+        let mut placeholders = Vec::with_capacity(children.len());
+        let mut known = true;
+        for child in &children {
+            match (&child.value, &child.expr.expr) {
+                (Some(_), Expr::Literal(_)) => placeholders.push(child.expr.clone()),
+                (Some(value), _) => {
+                    let name = format!("{PLACEHOLDER_PREFIX}{}", child.expr.id);
+                    ctx.add_variable_from_value(name.clone(), value.clone());
+                    placeholders.push(IdedExpr {
+                        id: child.expr.id,
+                        expr: Expr::Ident(name),
+                    });
+                }
+                (None, _) => known = false,
+            }
+        }
+        let value = known.then(|| {
+            let expr = IdedExpr {
+                id,
+                expr: build(placeholders),
+            };
+            Value::resolve(&expr, &ctx)
+        });
+        let expr = IdedExpr {
+            id,
+            expr: build(children.into_iter().map(|child| child.expr).collect()),
+        };
+        match value {
+            Some(value) => self.fold(expr, value),
+            None => Pruned { expr, value: None },
+        }
+    }
+
+    /// Replaces `expr` by its value, if it could be evaluated and turned back into an
+    /// expression. Failing expressions are kept, so that the error is reported when evaluating
+    /// the residual.
+    fn fold(&mut self, expr: IdedExpr, value: ResolveResult) -> Pruned {
+        match value {
+            Ok(Value::Unknown(_)) | Err(_) => Pruned { expr, value: None },
+            Ok(value) => Pruned {
+                expr: self.value_expr(value.clone(), expr.id).unwrap_or(expr),
+                value: Some(value),
+            },
+        }
+    }
+
+    /// Returns an expression evaluating to `value`, if there is one.
+    fn value_expr(&mut self, value: Value, id: u64) -> Option<IdedExpr> {
+        let expr = match value {
+            Value::Int(i) => Expr::Literal(CelVal::Int(i)),
+            Value::UInt(u) => Expr::Literal(CelVal::UInt(u)),
+            Value::Float(f) => Expr::Literal(CelVal::Double(f)),
+            Value::String(s) => Expr::Literal(CelVal::String(s.to_string())),
+            Value::Bytes(b) => Expr::Literal(CelVal::Bytes(b.to_vec())),
+            Value::Bool(b) => Expr::Literal(CelVal::Boolean(b)),
+            Value::Null => Expr::Literal(CelVal::Null),
+            Value::List(items) => Expr::List(ListExpr {
+                elements: items
+                    .iter()
+                    .map(|item| {
+                        let id = self.next_id();
+                        self.value_expr(item.clone(), id)
+                    })
+                    .collect::<Option<_>>()?,
+                optional_indices: vec![],
+            }),
+            Value::Map(map) => {
+                // Sorted, so that residuals are deterministic.
+                let mut entries: Vec<_> = map.map.iter().collect();
+                entries.sort_by_key(|(key, _)| *key);
+                let mut map_entries = Vec::with_capacity(entries.len());
+                for (key, value) in entries {
+                    let (entry_id, key_id, value_id) =
+                        (self.next_id(), self.next_id(), self.next_id());
+                    map_entries.push(IdedEntryExpr {
+                        id: entry_id,
+                        expr: EntryExpr::MapEntry(MapEntryExpr {
+                            key: self.value_expr(key.into(), key_id)?,
+                            value: self.value_expr(value.clone(), value_id)?,
+                            optional: false,
+                        }),
+                    });
+                }
+                Expr::Map(MapExpr {
+                    entries: map_entries,
+                })
+            }
+            Value::Struct(s) => {
+                let mut entries = Vec::with_capacity(s.fields().len());
+                for (field, value) in s.fields() {
+                    let (entry_id, value_id) = (self.next_id(), self.next_id());
+                    entries.push(IdedEntryExpr {
+                        id: entry_id,
+                        expr: EntryExpr::StructField(StructFieldExpr {
+                            field: field.clone(),
+                            value: self.value_expr(value.clone(), value_id)?,
+                            optional: false,
+                        }),
+                    });
+                }
+                Expr::Struct(StructExpr {
+                    type_name: s.type_name().to_string(),
+                    entries,
+                })
+            }
+            Value::Optional(None) => self.namespaced_call("optional", "none", vec![]),
+            Value::Optional(Some(value)) => {
+                let value_id = self.next_id();
+                let value = self.value_expr(*value, value_id)?;
+                self.namespaced_call("optional", "of", vec![value])
+            }
+            #[cfg(feature = "chrono")]
+            Value::Duration(d) => {
+                let arg = self.literal(CelVal::String(crate::duration::format_duration(&d)));
+                global_call("duration", vec![arg])
+            }
+            #[cfg(feature = "chrono")]
+            Value::Timestamp(t) => {
+                let arg = self.literal(CelVal::String(t.to_rfc3339()));
+                global_call("timestamp", vec![arg])
+            }
+            Value::Function(_, _) | Value::Opaque(_) | Value::Unknown(_) => return None,
+        };
+        Some(IdedExpr { id, expr })
+    }
+
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    #[cfg(feature = "chrono")]
+    fn literal(&mut self, value: CelVal) -> IdedExpr {
+        IdedExpr {
+            id: self.next_id(),
+            expr: Expr::Literal(value),
+        }
+    }
+
+    fn namespaced_call(&mut self, namespace: &str, name: &str, args: Vec<IdedExpr>) -> Expr {
+        Expr::Call(CallExpr {
+            func_name: name.to_string(),
+            target: Some(Box::new(IdedExpr {
+                id: self.next_id(),
+                expr: Expr::Ident(namespace.to_string()),
+            })),
+            args,
+        })
+    }
+}
+
+fn global_call(name: &str, args: Vec<IdedExpr>) -> Expr {
+    Expr::Call(CallExpr {
+        func_name: name.to_string(),
+        target: None,
+        args,
+    })
+}
+
+fn is_bool(pruned: &Pruned, value: bool) -> bool {
+    matches!(pruned.value, Some(Value::Bool(b)) if b == value)
+}
+
+fn max_id(expr: &IdedExpr) -> u64 {
+    let children = match &expr.expr {
+        Expr::Call(call) => call
+            .target
+            .iter()
+            .map(|t| max_id(t))
+            .chain(call.args.iter().map(max_id))
+            .max(),
+        Expr::Comprehension(c) => [
+            &c.iter_range,
+            &c.accu_init,
+            &c.loop_cond,
+            &c.loop_step,
+            &c.result,
+        ]
+        .into_iter()
+        .map(max_id)
+        .max(),
+        Expr::List(list) => list.elements.iter().map(max_id).max(),
+        Expr::Map(MapExpr { entries }) | Expr::Struct(StructExpr { entries, .. }) => entries
+            .iter()
+            .map(|entry| match &entry.expr {
+                EntryExpr::StructField(field) => entry.id.max(max_id(&field.value)),
+                EntryExpr::MapEntry(e) => entry.id.max(max_id(&e.key)).max(max_id(&e.value)),
+            })
+            .max(),
+        Expr::Select(select) => Some(max_id(&select.operand)),
+        Expr::Ident(_) | Expr::Literal(_) | Expr::Unspecified => None,
+    };
+    children.map_or(expr.id, |id| id.max(expr.id))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::unparse;
+    use crate::unknown::AttributePattern;
+    use crate::{Context, Program, Value};
+    use std::collections::HashMap;
+
+    fn context() -> Context<'static> {
+        let mut context = Context::default();
+        context.add_variable_from_value("path", "/admin");
+        context.add_variable_from_value("groups", vec!["admins", "users"]);
+        context.add_variable_from_value("request", HashMap::from([("auth", Value::Null)]));
+        context.add_unknown(AttributePattern::new("request").field("auth").wildcard());
+        context
+    }
+
+    fn residual(expr: &str) -> String {
+        let program = Program::compile(expr).unwrap();
+        unparse(&program.residual(&context())).unwrap()
+    }
+
+    #[test]
+    fn folds_known_subexpressions() {
+        for (expr, expected) in [
+            ("path + '!'", r#""/admin!""#),
+            ("[1, 2].map(x, x * 2)", "[2, 4]"),
+            ("{'b': 1, 'a': size(groups)}", r#"{"a": 2, "b": 1}"#),
+            ("optional.of(path)", r#"optional.of("/admin")"#),
+            (
+                "request.auth.level > size(groups)",
+                "request.auth.level > 2",
+            ),
+            (
+                "path.startsWith('/a') && request.auth.admin",
+                "request.auth.admin",
+            ),
+            ("request.auth.admin || path == '/'", "request.auth.admin"),
+            ("path == '/admin' || request.auth.admin", "true"),
+            (
+                "path.startsWith('/a') ? request.auth.level : 0",
+                "request.auth.level",
+            ),
+            (
+                "request.auth.admin ? path : 'none'",
+                r#"request.auth.admin ? "/admin" : "none""#,
+            ),
+            (
+                "groups.exists(g, g == request.auth.group)",
+                r#"["admins", "users"].exists(g, g == request.auth.group)"#,
+            ),
+            (
+                "request.auth.admin || 1 / 0 > size(groups)",
+                "request.auth.admin || 1 / 0 > 2",
+            ),
+            ("has(request.auth.admin)", "has(request.auth.admin)"),
+        ] {
+            assert_eq!(residual(expr), expected, "{expr}");
+        }
+    }
+
+    #[test]
+    fn evaluates_subexpressions_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut context = context();
+        let counter = calls.clone();
+        context.add_function("inc", move |x: i64| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            x + 1
+        });
+
+        let program = Program::compile("[inc(inc(inc(0)))] + [request.auth.level]").unwrap();
+        assert_eq!(
+            unparse(&program.residual(&context)).unwrap(),
+            "[3] + [request.auth.level]"
+        );
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn residual_evaluates_like_the_program() {
+        let mut known = Context::default();
+        known.add_variable_from_value("path", "/admin");
+        known.add_variable_from_value("groups", vec!["admins", "users"]);
+        known.add_variable_from_value(
+            "request",
+            HashMap::from([("auth", HashMap::from([("group", "admins")]))]),
+        );
+
+        for expr in [
+            "groups.exists(g, g == request.auth.group) && path != '/'",
+            "request.auth.group + path",
+            "[request.auth.group, path].filter(x, x in groups)",
+            "request.auth.group == 'users' ? 1 / 0 : size(groups)",
+            "cel.block([1 / 0, request.auth.group], path == '/' && cel.index(0) > 0 || cel.index(1) in groups)",
+        ] {
+            let program = Program::compile(expr).unwrap();
+            let residual = Program::compile(&residual(expr)).unwrap();
+            assert_eq!(residual.execute(&known), program.execute(&known), "{expr}");
+        }
+    }
+}
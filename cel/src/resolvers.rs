@@ -40,7 +40,24 @@ impl Resolver for Argument {
                 index + 1,
                 ctx.args.len(),
             ))?;
-        Value::resolve(arg, ctx.ptx)
+        let value = match &ctx.resolved_args {
+            Some(resolved) => resolved[index].clone(),
+            None => Value::resolve(arg, ctx.ptx),
+        }?;
+        record_unknown(ctx, &value);
+        Ok(value)
+    }
+}
+
+/// Records the unknown arguments of a function call, whose result is then unknown too. Only the
+/// arguments resolved as values are, so that e.g. the variable of `list.sortBy(x, -x)` may
+/// shadow an unknown one.
+fn record_unknown(ctx: &FunctionContext, value: &Value) {
+    if let Value::Unknown(value) = value {
+        match &mut *ctx.unknown.borrow_mut() {
+            Some(unknown) => unknown.extend(value),
+            unknown => *unknown = Some(value.clone()),
+        }
     }
 }
 
@@ -56,8 +73,20 @@ pub(crate) struct AllArguments;
 impl Resolver for AllArguments {
     fn resolve(&self, ctx: &FunctionContext) -> ResolveResult {
         let mut args = Vec::with_capacity(ctx.args.len());
-        for arg in ctx.args.iter() {
-            args.push(Value::resolve(arg, ctx.ptx)?);
+        match &ctx.resolved_args {
+            Some(resolved) => {
+                for arg in resolved {
+                    args.push(arg.clone()?);
+                }
+            }
+            None => {
+                for arg in ctx.args.iter() {
+                    args.push(Value::resolve(arg, ctx.ptx)?);
+                }
+            }
+        }
+        for arg in &args {
+            record_unknown(ctx, arg);
         }
         Ok(Value::List(args.into()))
     }
@@ -58,10 +58,10 @@ impl Duration {
     // Since serde can't natively represent durations, we serialize a special
     // newtype to indicate we want to rebuild the duration in the result, while
     // remaining compatible with most other Serializer implementations.
-    const NAME: &str = "$__cel_private_Duration";
-    const STRUCT_NAME: &str = "Duration";
-    const SECS_FIELD: &str = "secs";
-    const NANOS_FIELD: &str = "nanos";
+    pub(crate) const NAME: &str = "$__cel_private_Duration";
+    pub(crate) const STRUCT_NAME: &str = "Duration";
+    pub(crate) const SECS_FIELD: &str = "secs";
+    pub(crate) const NANOS_FIELD: &str = "nanos";
 }
 
 #[cfg(feature = "chrono")]
@@ -147,7 +147,7 @@ impl Timestamp {
     // Since serde can't natively represent timestamps, we serialize a special
     // newtype to indicate we want to rebuild the timestamp in the result,
     // while remaining compatible with most other Serializer implementations.
-    const NAME: &str = "$__cel_private_Timestamp";
+    pub(crate) const NAME: &str = "$__cel_private_Timestamp";
 }
 
 #[cfg(feature = "chrono")]
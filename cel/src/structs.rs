@@ -0,0 +1,303 @@
+use crate::common::types::{Kind, Type};
+use crate::objects::Value;
+use crate::ExecutionError;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// The declaration of a struct type, i.e. a message that can be constructed from CEL using
+/// the `Name{field: value}` syntax.
+///
+/// # Example
+/// ```
+/// use cel::common::types::{INT_TYPE, STRING_TYPE};
+/// use cel::structs::{StructRegistry, StructType};
+/// use cel::{Context, Program};
+///
+/// let mut types = StructRegistry::default();
+/// types.add(
+///     StructType::new("Account")
+///         .field("user_id", INT_TYPE)
+///         .field_with_default("role", STRING_TYPE, "viewer"),
+/// );
+///
+/// let mut context = Context::default();
+/// context.set_type_provider(types);
+///
+/// let program = Program::compile("Account{user_id: 123}.role == 'viewer'").unwrap();
+/// assert_eq!(program.execute(&context), Ok(true.into()));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructType {
+    name: String,
+    fields: BTreeMap<String, FieldDecl>,
+}
+
+/// The type of a field and the value it has when it is not set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldDecl {
+    pub ty: Type<'static>,
+    pub default: Value,
+}
+
+impl StructType {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        StructType {
+            name: name.into(),
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Declares a field, which defaults to the zero value of its type: `0`, `''`, `[]`, etc.
+    /// Fields of any other type default to `null`.
+    pub fn field<S: Into<String>>(self, name: S, ty: Type<'static>) -> Self {
+        let default = zero_value(&ty);
+        self.field_with_default(name, ty, default)
+    }
+
+    pub fn field_with_default<S: Into<String>, V: Into<Value>>(
+        mut self,
+        name: S,
+        ty: Type<'static>,
+        default: V,
+    ) -> Self {
+        self.fields.insert(
+            name.into(),
+            FieldDecl {
+                ty,
+                default: default.into(),
+            },
+        );
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn field_decl(&self, name: &str) -> Option<&FieldDecl> {
+        self.fields.get(name)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &FieldDecl)> {
+        self.fields.iter().map(|(name, decl)| (name.as_str(), decl))
+    }
+
+    /// The type of values of this struct, as seen by the [`crate::checker::Checker`].
+    pub fn as_type(&self) -> Type<'static> {
+        Type::new_object(&self.name)
+    }
+}
+
+/// TypeProvider looks up the struct types which expressions can construct and select
+/// fields from.
+///
+/// A provider is set on the root [`crate::Context`] with [`crate::Context::set_type_provider`],
+/// and on a [`crate::checker::Checker`] so that field selections can be type checked.
+pub trait TypeProvider: Send + Sync {
+    fn find_struct_type(&self, name: &str) -> Option<Arc<StructType>>;
+}
+
+impl<T: TypeProvider + ?Sized> TypeProvider for Arc<T> {
+    fn find_struct_type(&self, name: &str) -> Option<Arc<StructType>> {
+        (**self).find_struct_type(name)
+    }
+}
+
+/// A [`TypeProvider`] backed by the struct types registered with [`StructRegistry::add`].
+#[derive(Clone, Debug, Default)]
+pub struct StructRegistry {
+    types: HashMap<String, Arc<StructType>>,
+}
+
+impl StructRegistry {
+    pub fn add(&mut self, ty: StructType) {
+        self.types.insert(ty.name.clone(), Arc::new(ty));
+    }
+}
+
+impl TypeProvider for StructRegistry {
+    fn find_struct_type(&self, name: &str) -> Option<Arc<StructType>> {
+        self.types.get(name).cloned()
+    }
+}
+
+/// An instance of a [`StructType`], as stored in [`Value::Struct`].
+///
+/// Only the fields that were explicitly set are stored; reading any other declared field
+/// returns its default.
+#[derive(Clone, Debug)]
+pub struct Struct {
+    ty: Arc<StructType>,
+    fields: Arc<BTreeMap<String, Value>>,
+}
+
+impl Struct {
+    /// Creates a struct, making sure that every field is declared by `ty` and holds a value
+    /// of the declared type.
+    pub fn new(
+        ty: Arc<StructType>,
+        fields: BTreeMap<String, Value>,
+    ) -> Result<Self, ExecutionError> {
+        for (name, value) in &fields {
+            let decl = ty
+                .field_decl(name)
+                .ok_or_else(|| ExecutionError::no_such_key(name))?;
+            if !is_instance_of(value, &decl.ty) {
+                return Err(ExecutionError::UnexpectedType {
+                    got: value.type_of().to_string(),
+                    want: decl.ty.to_string(),
+                });
+            }
+        }
+        Ok(Struct {
+            ty,
+            fields: Arc::new(fields),
+        })
+    }
+
+    pub fn type_name(&self) -> &str {
+        self.ty.name()
+    }
+
+    pub fn struct_type(&self) -> &Arc<StructType> {
+        &self.ty
+    }
+
+    /// Returns the value of a field, or its default if it wasn't set. Returns `None` if the
+    /// field isn't declared.
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        self.fields
+            .get(field)
+            .or_else(|| self.ty.field_decl(field).map(|decl| &decl.default))
+    }
+
+    /// The fields which were explicitly set.
+    pub fn fields(&self) -> &BTreeMap<String, Value> {
+        &self.fields
+    }
+
+    /// Returns whether a field was explicitly set, as tested by `has(s.field)`.
+    pub fn has(&self, field: &str) -> bool {
+        self.fields.contains_key(field)
+    }
+}
+
+impl PartialEq for Struct {
+    fn eq(&self, other: &Self) -> bool {
+        self.ty.name() == other.ty.name()
+            && self
+                .ty
+                .fields()
+                .all(|(name, _)| self.get(name) == other.get(name))
+    }
+}
+
+fn zero_value(ty: &Type) -> Value {
+    match ty.kind() {
+        Kind::Boolean => Value::Bool(false),
+        Kind::Int => Value::Int(0),
+        Kind::UInt => Value::UInt(0),
+        Kind::Double => Value::Float(0.0),
+        Kind::String => Value::String(Arc::new(String::new())),
+        Kind::Bytes => Value::Bytes(Arc::new(Vec::new())),
+        Kind::List => Value::List(Arc::new(Vec::new())),
+        Kind::Map => HashMap::<String, Value>::new().into(),
+        _ => Value::Null,
+    }
+}
+
+fn is_instance_of(value: &Value, ty: &Type) -> bool {
+    match (ty.kind(), value) {
+        (Kind::Dyn | Kind::Any | Kind::Error | Kind::TypeParam, _) => true,
+        (Kind::Boolean, Value::Bool(_)) => true,
+        (Kind::Int, Value::Int(_)) => true,
+        (Kind::UInt, Value::UInt(_)) => true,
+        (Kind::Double, Value::Float(_)) => true,
+        (Kind::String, Value::String(_)) => true,
+        (Kind::Bytes, Value::Bytes(_)) => true,
+        (Kind::List, Value::List(_)) => true,
+        (Kind::Map, Value::Map(_)) => true,
+        #[cfg(feature = "chrono")]
+        (Kind::Duration, Value::Duration(_)) => true,
+        #[cfg(feature = "chrono")]
+        (Kind::Timestamp, Value::Timestamp(_)) => true,
+        (Kind::Struct, Value::Struct(s)) => s.type_name() == ty.name(),
+        (Kind::Opaque, Value::Opaque(o)) => o.runtime_type_name() == ty.name(),
+        (Kind::Opaque, Value::Optional(_)) => ty.name() == "optional_type",
+        (Kind::Struct | Kind::Opaque | Kind::NullType, Value::Null) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::types::{INT_TYPE, STRING_TYPE};
+    use crate::{Context, Program};
+
+    fn context() -> Context<'static> {
+        let mut types = StructRegistry::default();
+        types.add(
+            StructType::new("Account")
+                .field("user_id", INT_TYPE)
+                .field("name", STRING_TYPE)
+                .field_with_default("role", STRING_TYPE, "viewer")
+                .field("owner", Type::new_object("Account")),
+        );
+        let mut context = Context::default();
+        context.set_type_provider(types);
+        context
+    }
+
+    fn execute(expr: &str) -> Result<Value, ExecutionError> {
+        Program::compile(expr).unwrap().execute(&context())
+    }
+
+    #[test]
+    fn construction_and_selection() {
+        for (expr, expected) in [
+            ("Account{user_id: 123}.user_id", Value::Int(123)),
+            ("Account{user_id: 123}.name", "".into()),
+            ("Account{}.role", "viewer".into()),
+            (
+                "Account{owner: Account{user_id: 1}}.owner.user_id",
+                1.into(),
+            ),
+            ("Account{}.owner", Value::Null),
+            ("has(Account{user_id: 1}.user_id)", true.into()),
+            ("has(Account{}.user_id)", false.into()),
+            ("Account{user_id: 1} == Account{user_id: 1}", true.into()),
+            ("Account{user_id: 0} == Account{}", true.into()),
+            ("Account{user_id: 1} == Account{user_id: 2}", false.into()),
+            ("has(Account{?name: optional.none()}.name)", false.into()),
+            ("Account{?name: optional.of('a')}.name", "a".into()),
+            ("Account{}.?name", Value::Optional(None)),
+            ("Account{name: 'a'}.?name.value()", "a".into()),
+        ] {
+            assert_eq!(execute(expr), Ok(expected), "{expr}");
+        }
+    }
+
+    #[test]
+    fn invalid_construction() {
+        assert_eq!(
+            execute("Unknown{}"),
+            Err(ExecutionError::undeclared_reference("Unknown"))
+        );
+        assert_eq!(
+            execute("Account{id: 1}"),
+            Err(ExecutionError::no_such_key("id"))
+        );
+        assert_eq!(
+            execute("Account{user_id: 'a'}"),
+            Err(ExecutionError::UnexpectedType {
+                got: "string".to_string(),
+                want: "int".to_string()
+            })
+        );
+        assert_eq!(
+            execute("Account{}.id"),
+            Err(ExecutionError::no_such_key("id"))
+        );
+    }
+}
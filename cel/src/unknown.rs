@@ -0,0 +1,323 @@
+use crate::common::ast::{operators, Expr};
+use crate::common::value::CelVal;
+use crate::objects::{Key, Value};
+use crate::parser::Expression;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+/// A pattern matching the attributes, i.e. variables and the fields or indices selected from
+/// them, whose value isn't known yet.
+///
+/// Patterns are added to a [`crate::Context`] with [`crate::Context::add_unknown`]. An
+/// attribute is unknown if it is qualified by at least as many fields or indices as the
+/// pattern, and each of them matches the qualifier of the pattern at the same position:
+/// `request.auth.*` matches `request.auth.claims` and `request.auth.claims['sub']`, but
+/// neither `request.auth` nor `request.path`.
+///
+/// # Example
+/// ```
+/// use cel::unknown::AttributePattern;
+///
+/// // request.auth.*
+/// let pattern = AttributePattern::new("request").field("auth").wildcard();
+/// // groups[0]
+/// let pattern = AttributePattern::new("groups").index(0i64);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttributePattern {
+    variable: String,
+    // `None` matches any qualifier.
+    qualifiers: Vec<Option<Key>>,
+}
+
+impl AttributePattern {
+    pub fn new<S: Into<String>>(variable: S) -> Self {
+        AttributePattern {
+            variable: variable.into(),
+            qualifiers: Vec::new(),
+        }
+    }
+
+    /// Matches the field `name`, selected either as `a.name` or as `a['name']`.
+    pub fn field<S: Into<String>>(mut self, name: S) -> Self {
+        self.qualifiers
+            .push(Some(Key::String(Arc::new(name.into()))));
+        self
+    }
+
+    /// Matches a list index or map key, e.g. `a[0]`.
+    pub fn index<K: Into<Key>>(mut self, key: K) -> Self {
+        self.qualifiers.push(Some(key.into()));
+        self
+    }
+
+    /// Matches any field, index or key.
+    pub fn wildcard(mut self) -> Self {
+        self.qualifiers.push(None);
+        self
+    }
+
+    pub(crate) fn matches(&self, variable: &str, qualifiers: &[Key]) -> bool {
+        self.variable == variable
+            && qualifiers.len() >= self.qualifiers.len()
+            && self
+                .qualifiers
+                .iter()
+                .zip(qualifiers)
+                .all(|(pattern, qualifier)| match pattern {
+                    Some(key) => key == qualifier,
+                    None => true,
+                })
+    }
+}
+
+/// The value of an expression which couldn't be evaluated because it depends on unknown
+/// attributes, as stored in [`Value::Unknown`].
+///
+/// It holds the ids of the attribute expressions (see [`crate::IdedExpr::id`]) whose value
+/// was unknown, which can then be resolved before evaluating the program again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unknown {
+    ids: Arc<BTreeSet<u64>>,
+}
+
+impl Unknown {
+    pub(crate) fn new(id: u64) -> Self {
+        Unknown {
+            ids: Arc::new(BTreeSet::from([id])),
+        }
+    }
+
+    /// The ids of the unknown attribute expressions, in ascending order.
+    pub fn ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.ids.iter().copied()
+    }
+
+    pub fn contains(&self, id: u64) -> bool {
+        self.ids.contains(&id)
+    }
+
+    /// Merges all the unknown values, returning `None` if none of them is unknown.
+    pub(crate) fn merge<'a, I>(values: I) -> Option<Unknown>
+    where
+        I: IntoIterator<Item = &'a Value>,
+    {
+        let mut merged: Option<Unknown> = None;
+        for value in values {
+            if let Value::Unknown(unknown) = value {
+                match &mut merged {
+                    None => merged = Some(unknown.clone()),
+                    Some(merged) => merged.extend(unknown),
+                }
+            }
+        }
+        merged
+    }
+
+    /// Adds the ids of `other` to these.
+    pub(crate) fn extend(&mut self, other: &Unknown) {
+        Arc::make_mut(&mut self.ids).extend(other.ids.iter());
+    }
+}
+
+/// Returns the unknown value of `expr` if it is an attribute matching one of the unknown
+/// patterns of the context.
+pub(crate) fn unknown_attribute(expr: &Expression, ctx: &crate::Context) -> Option<Value> {
+    if !ctx.has_unknowns() {
+        return None;
+    }
+    let mut qualifiers = Vec::new();
+    let variable = attribute(expr, &mut qualifiers, true)?;
+    ctx.is_unknown(variable, &qualifiers)
+        .then(|| Value::Unknown(Unknown::new(expr.id)))
+}
+
+/// Returns whether `expr` is an attribute, e.g. `a.b[0]`.
+pub(crate) fn is_attribute(expr: &Expression) -> bool {
+    attribute(expr, &mut Vec::new(), true).is_some()
+}
+
+/// Collects the qualifiers of an attribute, e.g. `a.b[0]`, and returns its variable. With
+/// `optional`, the qualifiers may be optional selections and indices, e.g. `a.?b[?0]`.
+pub(crate) fn attribute<'a>(
+    expr: &'a Expression,
+    qualifiers: &mut Vec<Key>,
+    optional: bool,
+) -> Option<&'a str> {
+    match &expr.expr {
+        Expr::Ident(name) => Some(name),
+        Expr::Select(select) => {
+            let variable = attribute(&select.operand, qualifiers, optional)?;
+            qualifiers.push(Key::String(Arc::new(select.field.clone())));
+            Some(variable)
+        }
+        Expr::Call(call)
+            if call.target.is_none()
+                && call.args.len() == 2
+                && (call.func_name == operators::INDEX
+                    || optional
+                        && matches!(
+                            call.func_name.as_str(),
+                            operators::OPT_INDEX | operators::OPT_SELECT
+                        )) =>
+        {
+            let key = match &call.args[1].expr {
+                Expr::Literal(CelVal::String(s)) => Key::String(Arc::new(s.clone())),
+                Expr::Literal(CelVal::Int(i)) => Key::Int(*i),
+                Expr::Literal(CelVal::UInt(u)) => Key::Uint(*u),
+                Expr::Literal(CelVal::Boolean(b)) => Key::Bool(*b),
+                _ => return None,
+            };
+            let variable = attribute(&call.args[0], qualifiers, optional)?;
+            qualifiers.push(key);
+            Some(variable)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ast::EntryExpr;
+    use crate::{Context, ExecutionError, Program};
+    use std::collections::HashMap;
+
+    fn context() -> Context<'static> {
+        let mut context = Context::default();
+        context.add_lists_extension();
+        context.add_variable_from_value(
+            "request",
+            HashMap::from([
+                ("path", Value::from("/admin")),
+                ("auth", HashMap::<String, Value>::new().into()),
+            ]),
+        );
+        context.add_variable_from_value("groups", vec!["admins"]);
+        context.add_unknown(AttributePattern::new("request").field("auth").wildcard());
+        context.add_unknown(AttributePattern::new("user"));
+        context
+    }
+
+    fn execute(expr: &str) -> Result<Value, ExecutionError> {
+        Program::compile(expr).unwrap().execute(&context())
+    }
+
+    /// Evaluates `expr` and returns the names of the unknown attributes it depends on.
+    fn unknowns(expr: &str) -> Vec<String> {
+        let program = Program::compile(expr).unwrap();
+        let result = program.execute(&context());
+        let Ok(Value::Unknown(unknown)) = result else {
+            panic!("{expr}: expected an unknown value, got {result:?}");
+        };
+        let mut names = Vec::new();
+        collect_names(program.expression(), &unknown, &mut names);
+        names
+    }
+
+    fn collect_names(expr: &Expression, unknown: &Unknown, names: &mut Vec<String>) {
+        if unknown.contains(expr.id) {
+            let mut qualifiers = Vec::new();
+            let variable = attribute(expr, &mut qualifiers, true).unwrap();
+            names.push(
+                std::iter::once(variable.to_string())
+                    .chain(qualifiers.iter().map(|key| key.to_string()))
+                    .collect::<Vec<_>>()
+                    .join("."),
+            );
+            return;
+        }
+        let children: Vec<&Expression> = match &expr.expr {
+            Expr::Call(call) => call.target.iter().map(|t| &**t).chain(&call.args).collect(),
+            Expr::Select(select) => vec![&select.operand],
+            Expr::List(list) => list.elements.iter().collect(),
+            Expr::Map(map) => map
+                .entries
+                .iter()
+                .flat_map(|entry| match &entry.expr {
+                    EntryExpr::MapEntry(entry) => vec![&entry.key, &entry.value],
+                    EntryExpr::StructField(field) => vec![&field.value],
+                })
+                .collect(),
+            Expr::Comprehension(comp) => vec![&comp.iter_range, &comp.loop_step],
+            _ => vec![],
+        };
+        for child in children {
+            collect_names(child, unknown, names);
+        }
+    }
+
+    #[test]
+    fn unknown_attributes() {
+        for (expr, expected) in [
+            ("user", &["user"][..]),
+            ("request.auth.claims", &["request.auth.claims"]),
+            ("request.auth.claims.sub", &["request.auth.claims.sub"]),
+            ("request['auth'].claims", &["request.auth.claims"]),
+            ("request.auth[0]", &["request.auth.0"]),
+            ("has(request.auth.claims)", &["request.auth.claims"]),
+            ("request.auth.?claims", &["request.auth.claims"]),
+            ("request.auth.claims == 'a'", &["request.auth.claims"]),
+            ("size(request.auth.claims) > 1", &["request.auth.claims"]),
+            (
+                "request.auth.claims.startsWith('a')",
+                &["request.auth.claims"],
+            ),
+            ("request.auth.claims ? 1 : 2", &["request.auth.claims"]),
+            ("[request.auth.claims]", &["request.auth.claims"]),
+            ("{'a': user.name}", &["user.name"]),
+            ("user.name + user.id", &["user.name", "user.id"]),
+            ("[1, 2].map(x, user.id + x)", &["user.id"]),
+            ("max(user.a, 1, user.b)", &["user.a", "user.b"]),
+            ("[user.id].sortBy(x, x)", &["user.id"]),
+        ] {
+            assert_eq!(unknowns(expr), expected, "{expr}");
+        }
+    }
+
+    #[test]
+    fn known_attributes() {
+        for (expr, expected) in [
+            ("request.path", Value::from("/admin")),
+            ("size(request.auth)", Value::Int(0)),
+            ("groups[0]", "admins".into()),
+            ("[1].map(user, user + 1)", vec![2].into()),
+        ] {
+            assert_eq!(execute(expr), Ok(expected), "{expr}");
+        }
+    }
+
+    #[test]
+    fn lambda_variables_shadow_unknowns() {
+        assert_eq!(
+            execute("[2, 1, 3].sortBy(user, -user)"),
+            Ok(vec![3, 2, 1].into())
+        );
+    }
+
+    #[test]
+    fn logical_operators_prune_unknowns() {
+        for (expr, expected) in [
+            ("user.admin || true", true),
+            ("false && user.admin", false),
+            ("user.admin && request.path == '/'", false),
+            ("1 / 0 > 1 || user.admin || true", true),
+            ("[1, 2].exists(x, x == 2 || user.id == x)", true),
+            ("[1, 2].all(x, x == 1 && user.id == x)", false),
+        ] {
+            assert_eq!(execute(expr), Ok(Value::Bool(expected)), "{expr}");
+        }
+
+        for (expr, expected) in [
+            ("user.admin || false", &["user.admin"][..]),
+            (
+                "user.admin || request.auth.admin",
+                &["user.admin", "request.auth.admin"],
+            ),
+            ("1 / 0 > 1 || user.admin", &["user.admin"]),
+            ("[1, 2].exists(x, user.id == x)", &["user.id"]),
+        ] {
+            assert_eq!(unknowns(expr), expected, "{expr}");
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! Runs the cel-spec conformance tests of `testdata`, in the format of the `simple` tests of
+//! <https://github.com/google/cel-spec/tree/master/tests/simple>.
+//!
+//! The files of `testdata` hold a subset of the upstream tests of the same names; upstream
+//! files can be copied there as they are.
+//!
+//! Each test is reported as passed, failed or skipped, per section. Tests which are known to
+//! fail are listed in `skip.txt`, so that any other failure fails the run. Run with
+//! `cargo test --test conformance -- --nocapture` to see the report.
+#![cfg(all(feature = "chrono", feature = "regex"))]
+
+mod textproto;
+
+use cel::objects::{Key, Map};
+use cel::{Context, Program, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::Arc;
+use textproto::{Message, Node};
+
+/// The tests known to fail, as `file/section/test`, or `file/section` or `file` to skip all
+/// of their tests, unless they are followed by `unless <feature>` and the feature is enabled.
+struct SkipList {
+    entries: BTreeSet<String>,
+    used: BTreeSet<String>,
+}
+
+impl SkipList {
+    fn load(path: &Path) -> Self {
+        let text = std::fs::read_to_string(path).unwrap();
+        let entries = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap().trim())
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| match line.split_once(" unless ") {
+                Some((entry, feature)) => (!enabled(feature.trim())).then_some(entry),
+                None => Some(line),
+            })
+            .map(str::to_string)
+            .collect();
+        SkipList {
+            entries,
+            used: BTreeSet::new(),
+        }
+    }
+
+    fn contains(&mut self, id: &str) -> bool {
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| id == *entry || id.starts_with(&format!("{entry}/")));
+        match entry {
+            Some(entry) => {
+                self.used.insert(entry.clone());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Whether the crate feature of a skip list entry is enabled.
+fn enabled(feature: &str) -> bool {
+    match feature {
+        "chrono-tz" => cfg!(feature = "chrono-tz"),
+        feature => panic!("unknown feature '{feature}' in skip.txt"),
+    }
+}
+
+#[derive(Default)]
+struct Counts {
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+}
+
+#[test]
+fn conformance() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let mut skips = SkipList::load(&dir.join("skip.txt"));
+    let mut files: Vec<_> = std::fs::read_dir(dir.join("testdata"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "textproto"))
+        .collect();
+    files.sort();
+
+    // Panics are reported as failures, without their messages interleaving with the report.
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+
+    let mut total = Counts::default();
+    let mut failures = Vec::new();
+    let mut fixed = Vec::new();
+    for path in files {
+        let file = textproto::parse(&std::fs::read_to_string(&path).unwrap())
+            .unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+        let file_name = file.string("name").unwrap_or_default();
+        for section in file.messages("section") {
+            let section_name = section.string("name").unwrap_or_default();
+            let mut counts = Counts::default();
+            for test in section.messages("test") {
+                let id = format!(
+                    "{file_name}/{section_name}/{}",
+                    test.string("name").unwrap_or_default()
+                );
+                let result = run(test);
+                if skips.contains(&id) {
+                    counts.skipped += 1;
+                    if result.is_ok() {
+                        fixed.push(id);
+                    }
+                } else if let Err(reason) = result {
+                    counts.failed += 1;
+                    failures.push(format!("{id}: {reason}"));
+                } else {
+                    counts.passed += 1;
+                }
+            }
+            println!(
+                "{file_name}/{section_name}: {} passed, {} failed, {} skipped",
+                counts.passed, counts.failed, counts.skipped
+            );
+            total.passed += counts.passed;
+            total.failed += counts.failed;
+            total.skipped += counts.skipped;
+        }
+    }
+    std::panic::set_hook(hook);
+
+    println!(
+        "total: {} passed, {} failed, {} skipped",
+        total.passed, total.failed, total.skipped
+    );
+    for id in fixed {
+        println!("skipped, but passes: {id}");
+    }
+    for entry in skips.entries.difference(&skips.used) {
+        println!("unused skip list entry: {entry}");
+    }
+    assert!(
+        failures.is_empty(),
+        "{} conformance tests failed, fix them or add them to skip.txt:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+/// Runs a `SimpleTest`, returning why it failed if it did.
+fn run(test: &Message) -> Result<(), String> {
+    let expr = test.string("expr").ok_or("missing expression")?;
+    if test.string("container").is_some_and(|c| !c.is_empty()) {
+        return Err("containers aren't supported".to_string());
+    }
+    if test.flag("disable_macros") {
+        return Err("disabling the standard macros isn't supported".to_string());
+    }
+
+    let mut context = Context::default();
+    for binding in test.messages("bindings") {
+        let name = binding.string("key").ok_or("missing binding name")?;
+        let value = binding
+            .message("value")
+            .and_then(|value| value.message("value"))
+            .ok_or_else(|| format!("unsupported binding of '{name}'"))?;
+        context.add_variable_from_value(name, value_of(value)?);
+    }
+
+    // Parse errors are errors of the expression too, e.g. for integer literals overflowing.
+    let result = catch_unwind(AssertUnwindSafe(|| match Program::compile(&expr) {
+        Ok(program) => program.execute(&context).map_err(|err| err.to_string()),
+        Err(err) => Err(format!("parse error: {err}")),
+    }))
+    .map_err(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        format!("panicked: {message}")
+    })?;
+
+    let expected = test.message("value").or_else(|| {
+        test.message("typed_result")
+            .and_then(|r| r.message("result"))
+    });
+    if let Some(expected) = expected {
+        let expected = value_of(expected)?;
+        return match result {
+            Ok(actual) if same(&actual, &expected) => Ok(()),
+            Ok(actual) => Err(format!("expected {expected:?}, got {actual:?}")),
+            Err(err) => Err(format!("expected {expected:?}, got error: {err}")),
+        };
+    }
+    if test.get("eval_error").is_some() || test.get("any_eval_errors").is_some() {
+        return match result {
+            Ok(actual) => Err(format!("expected an error, got {actual:?}")),
+            Err(_) => Ok(()),
+        };
+    }
+    if let Some((matcher, _)) = test
+        .fields
+        .iter()
+        .find(|(name, _)| ["unknown", "any_unknowns"].contains(&name.as_str()))
+    {
+        return Err(format!("unsupported result matcher '{matcher}'"));
+    }
+    // Without a result matcher, the expression is expected to be true.
+    match result {
+        Ok(Value::Bool(true)) => Ok(()),
+        Ok(actual) => Err(format!("expected true, got {actual:?}")),
+        Err(err) => Err(format!("expected true, got error: {err}")),
+    }
+}
+
+/// Converts a `cel.expr.Value`.
+fn value_of(value: &Message) -> Result<Value, String> {
+    let (kind, node) = value.fields.first().ok_or("empty value")?;
+    let unsupported = || format!("unsupported expected value '{kind}'");
+    Ok(match (kind.as_str(), node) {
+        ("null_value", _) => Value::Null,
+        ("bool_value", Node::Literal(b)) => Value::Bool(b == "true"),
+        ("int64_value", Node::Literal(i)) => Value::Int(i.parse().map_err(|_| unsupported())?),
+        ("uint64_value", Node::Literal(u)) => Value::UInt(u.parse().map_err(|_| unsupported())?),
+        ("double_value", Node::Literal(f)) => {
+            Value::Float(parse_double(f).ok_or_else(unsupported)?)
+        }
+        ("string_value", Node::Bytes(s)) => Value::String(Arc::new(
+            String::from_utf8(s.clone()).map_err(|_| unsupported())?,
+        )),
+        ("bytes_value", Node::Bytes(b)) => Value::Bytes(Arc::new(b.clone())),
+        ("list_value", Node::Message(list)) => Value::List(Arc::new(
+            list.messages("values")
+                .map(value_of)
+                .collect::<Result<_, _>>()?,
+        )),
+        ("map_value", Node::Message(map)) => {
+            let mut entries = HashMap::new();
+            for entry in map.messages("entries") {
+                let key = entry.message("key").ok_or("missing map key")?;
+                let key = match value_of(key)? {
+                    Value::Int(i) => Key::Int(i),
+                    Value::UInt(u) => Key::Uint(u),
+                    Value::Bool(b) => Key::Bool(b),
+                    Value::String(s) => Key::String(s),
+                    key => return Err(format!("unsupported map key {key:?}")),
+                };
+                let value = entry.message("value").ok_or("missing map value")?;
+                entries.insert(key, value_of(value)?);
+            }
+            Value::Map(Map {
+                map: Arc::new(entries),
+            })
+        }
+        ("object_value", Node::Message(any)) => object_value(any).ok_or_else(unsupported)?,
+        _ => return Err(unsupported()),
+    })
+}
+
+/// Converts the well-known types packed in an `object_value`, e.g.
+/// `[type.googleapis.com/google.protobuf.Timestamp] { seconds: 1 }`.
+fn object_value(any: &Message) -> Option<Value> {
+    let (type_url, Node::Message(message)) = any.fields.first()? else {
+        return None;
+    };
+    let number = |field: &str| match message.get(field) {
+        Some(Node::Literal(n)) => n.parse::<i64>().ok(),
+        None => Some(0),
+        _ => None,
+    };
+    match type_url.rsplit('/').next()?.trim_end_matches(']') {
+        "google.protobuf.Timestamp" => {
+            chrono::DateTime::from_timestamp(number("seconds")?, number("nanos")? as u32)
+                .map(|ts| Value::Timestamp(ts.fixed_offset()))
+        }
+        "google.protobuf.Duration" => Some(Value::Duration(
+            chrono::Duration::seconds(number("seconds")?)
+                + chrono::Duration::nanoseconds(number("nanos")?),
+        )),
+        _ => None,
+    }
+}
+
+fn parse_double(f: &str) -> Option<f64> {
+    match f.to_ascii_lowercase().as_str() {
+        "inf" | "infinity" => Some(f64::INFINITY),
+        "-inf" | "-infinity" => Some(f64::NEG_INFINITY),
+        "nan" => Some(f64::NAN),
+        f => f.trim_end_matches('f').parse().ok(),
+    }
+}
+
+/// Whether two values are equal and of the same type, unlike `==` which e.g. equates `1` and
+/// `1u`.
+fn same(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Float(a), Value::Float(b)) => a == b || (a.is_nan() && b.is_nan()),
+        (Value::List(a), Value::List(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same(a, b))
+        }
+        (Value::Map(a), Value::Map(b)) => {
+            a.map.len() == b.map.len()
+                && b.map
+                    .iter()
+                    .all(|(key, b)| a.map.get(key).is_some_and(|a| same(a, b)))
+        }
+        (a, b) => std::mem::discriminant(a) == std::mem::discriminant(b) && a == b,
+    }
+}
@@ -0,0 +1,314 @@
+//! A reader for the protobuf text format, covering what the cel-spec test files use: nested
+//! messages, scalars, quoted strings with escapes, repeated fields and `[type/name]` field
+//! names. Field values aren't checked against a schema.
+
+use std::fmt::{self, Display, Formatter};
+
+/// A message, with its fields in the order of the text.
+#[derive(Debug, Default)]
+pub struct Message {
+    pub fields: Vec<(String, Node)>,
+}
+
+#[derive(Debug)]
+pub enum Node {
+    /// An identifier or a number, e.g. `true`, `NULL_VALUE` or `-1.5e3`.
+    Literal(String),
+    /// The bytes of a quoted string, after unescaping and concatenating adjacent strings.
+    Bytes(Vec<u8>),
+    Message(Message),
+}
+
+impl Message {
+    pub fn get(&self, name: &str) -> Option<&Node> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, node)| node)
+    }
+
+    pub fn all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Node> + 'a {
+        self.fields
+            .iter()
+            .filter(move |(field, _)| field == name)
+            .map(|(_, node)| node)
+    }
+
+    pub fn message(&self, name: &str) -> Option<&Message> {
+        match self.get(name) {
+            Some(Node::Message(message)) => Some(message),
+            _ => None,
+        }
+    }
+
+    pub fn messages<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Message> + 'a {
+        self.all(name).filter_map(|node| match node {
+            Node::Message(message) => Some(message),
+            _ => None,
+        })
+    }
+
+    pub fn string(&self, name: &str) -> Option<String> {
+        match self.get(name) {
+            Some(Node::Bytes(bytes)) => Some(String::from_utf8_lossy(bytes).into_owned()),
+            _ => None,
+        }
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        matches!(self.get(name), Some(Node::Literal(l)) if l == "true")
+    }
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Message, Error> {
+    let mut reader = Reader {
+        text: text.as_bytes(),
+        pos: 0,
+    };
+    let message = reader.message(None)?;
+    reader.skip_blank();
+    if reader.pos < reader.text.len() {
+        return Err(reader.error("unexpected character"));
+    }
+    Ok(message)
+}
+
+struct Reader<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error {
+            line: 1 + self.text[..self.pos]
+                .iter()
+                .filter(|&&c| c == b'\n')
+                .count(),
+            message: message.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    /// Skips whitespace, comments and the optional `,` or `;` separating fields.
+    fn skip_blank(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                b'#' => {
+                    while self.peek().is_some_and(|c| c != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                c if c.is_ascii_whitespace() || c == b',' || c == b';' => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), Error> {
+        self.skip_blank();
+        if self.peek() != Some(c) {
+            return Err(self.error(format!("expected '{}'", c as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Reads fields until `end`, or the end of the text if `end` is `None`.
+    fn message(&mut self, end: Option<u8>) -> Result<Message, Error> {
+        let mut message = Message::default();
+        loop {
+            self.skip_blank();
+            match (self.peek(), end) {
+                (None, None) => return Ok(message),
+                (None, Some(_)) => return Err(self.error("unterminated message")),
+                (Some(c), Some(end)) if c == end => {
+                    self.pos += 1;
+                    return Ok(message);
+                }
+                _ => {}
+            }
+            let name = self.field_name()?;
+            self.skip_blank();
+            let has_colon = self.peek() == Some(b':');
+            if has_colon {
+                self.pos += 1;
+                self.skip_blank();
+            }
+            match self.peek() {
+                Some(b'{') => {
+                    self.pos += 1;
+                    let value = self.message(Some(b'}'))?;
+                    message.fields.push((name, Node::Message(value)));
+                }
+                Some(b'<') => {
+                    self.pos += 1;
+                    let value = self.message(Some(b'>'))?;
+                    message.fields.push((name, Node::Message(value)));
+                }
+                Some(b'[') if has_colon => {
+                    self.pos += 1;
+                    loop {
+                        self.skip_blank();
+                        if self.peek() == Some(b']') {
+                            self.pos += 1;
+                            break;
+                        }
+                        let value = match self.peek() {
+                            Some(b'{') => {
+                                self.pos += 1;
+                                Node::Message(self.message(Some(b'}'))?)
+                            }
+                            _ => self.scalar()?,
+                        };
+                        message.fields.push((name.clone(), value));
+                    }
+                }
+                _ if has_colon => {
+                    let value = self.scalar()?;
+                    message.fields.push((name, value));
+                }
+                _ => return Err(self.error(format!("expected a value for '{name}'"))),
+            }
+        }
+    }
+
+    /// Reads a field name, or an extension or `Any` type name in brackets, e.g.
+    /// `[type.googleapis.com/google.protobuf.Int32Value]`.
+    fn field_name(&mut self) -> Result<String, Error> {
+        if self.peek() == Some(b'[') {
+            self.pos += 1;
+            let start = self.pos;
+            while self.peek().is_some_and(|c| c != b']') {
+                self.pos += 1;
+            }
+            let name = String::from_utf8_lossy(&self.text[start..self.pos]);
+            let name = format!("[{}]", name.trim());
+            self.expect(b']')?;
+            return Ok(name);
+        }
+        let name = self.literal();
+        if name.is_empty() {
+            return Err(self.error("expected a field name"));
+        }
+        Ok(name)
+    }
+
+    fn literal(&mut self) -> String {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || b"_.+-".contains(&c))
+        {
+            self.pos += 1;
+        }
+        String::from_utf8_lossy(&self.text[start..self.pos]).into_owned()
+    }
+
+    fn scalar(&mut self) -> Result<Node, Error> {
+        if !matches!(self.peek(), Some(b'"' | b'\'')) {
+            let literal = self.literal();
+            if literal.is_empty() {
+                return Err(self.error("expected a value"));
+            }
+            return Ok(Node::Literal(literal));
+        }
+        let mut bytes = Vec::new();
+        while let Some(quote @ (b'"' | b'\'')) = self.peek() {
+            self.pos += 1;
+            self.string(quote, &mut bytes)?;
+            self.skip_blank();
+        }
+        Ok(Node::Bytes(bytes))
+    }
+
+    fn string(&mut self, quote: u8, bytes: &mut Vec<u8>) -> Result<(), Error> {
+        loop {
+            let c = self
+                .peek()
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            match c {
+                c if c == quote => return Ok(()),
+                b'\\' => self.escape(bytes)?,
+                c => bytes.push(c),
+            }
+        }
+    }
+
+    fn escape(&mut self, bytes: &mut Vec<u8>) -> Result<(), Error> {
+        let c = self
+            .peek()
+            .ok_or_else(|| self.error("unterminated string"))?;
+        self.pos += 1;
+        match c {
+            b'n' => bytes.push(b'\n'),
+            b'r' => bytes.push(b'\r'),
+            b't' => bytes.push(b'\t'),
+            b'a' => bytes.push(0x07),
+            b'b' => bytes.push(0x08),
+            b'f' => bytes.push(0x0c),
+            b'v' => bytes.push(0x0b),
+            b'0'..=b'7' => {
+                let mut value = (c - b'0') as u32;
+                for _ in 0..2 {
+                    match self.peek() {
+                        Some(d @ b'0'..=b'7') => {
+                            value = value * 8 + (d - b'0') as u32;
+                            self.pos += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                bytes.push(value as u8);
+            }
+            b'x' | b'X' => {
+                let value = self.hex(2, false)?;
+                bytes.push(value as u8);
+            }
+            b'u' | b'U' => {
+                let value = self.hex(if c == b'u' { 4 } else { 8 }, true)?;
+                let c = char::from_u32(value)
+                    .ok_or_else(|| self.error(format!("invalid code point {value:x}")))?;
+                bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+            c => bytes.push(c),
+        }
+        Ok(())
+    }
+
+    /// Reads up to `digits` hexadecimal digits, or exactly `digits` if `exact`.
+    fn hex(&mut self, digits: usize, exact: bool) -> Result<u32, Error> {
+        let mut value = 0;
+        let mut count = 0;
+        while count < digits {
+            match self.peek().and_then(|c| (c as char).to_digit(16)) {
+                Some(digit) => {
+                    value = value * 16 + digit;
+                    self.pos += 1;
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+        if count == 0 || (exact && count < digits) {
+            return Err(self.error("invalid hexadecimal escape"));
+        }
+        Ok(value)
+    }
+}